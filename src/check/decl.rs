@@ -3,7 +3,8 @@ use std::mem::swap;
 use voile_util::loc::ToLoc;
 
 use crate::syntax::abs::AbsDecl;
-use crate::syntax::core::{Neutral, TraverseNeutral, Val, ValInfo, TYPE_OMEGA};
+use crate::syntax::core::{Neutral, Val, ValInfo, TYPE_OMEGA};
+use crate::syntax::surf::Pragma;
 
 use super::monad::{ValTCM, TCE, TCM, TCS};
 
@@ -24,16 +25,18 @@ fn unimplemented_to_glob(v: &mut [ValInfo], i: usize) {
     swap(&mut placeholder, &mut v[i]);
 }
 
-fn inline_metas(mut tcs: TCS, val: ValInfo) -> ValTCM {
-    use Neutral::*;
+fn inline_metas(tcs: TCS, val: ValInfo) -> ValTCM {
     let info = val.loc;
-    let val = val.ast.try_map_neutral(&mut |neut| match neut {
-        Meta(mi) => tcs
-            .meta_context
-            .take_meta(mi)
-            .ok_or_else(|| TCE::MetaUnsolved(mi)),
+    let val = tcs.solved_metas(val.ast);
+    let val = val.try_map_leaves(0, &mut |neut, _| match neut {
+        Neutral::Meta(mi) => Err(TCE::MetaUnsolved(mi)),
+        Neutral::App(head, args) => match *head {
+            Neutral::Meta(mi) => Err(TCE::MetaUnsolved(mi)),
+            head => Ok(Val::app(head, args)),
+        },
         e => Ok(Val::Neut(e)),
     })?;
+    let val = tcs.inline_levels(val);
     Ok((val.into_info(info), tcs))
 }
 
@@ -86,14 +89,22 @@ $$
 fn check_decl(tcs: TCS, decl: AbsDecl) -> TCM {
     debug_assert_eq!(tcs.gamma.len(), tcs.env.len());
     let tcs = match decl {
-        AbsDecl::Impl(impl_abs, sign_dbi) => {
+        AbsDecl::Impl(impl_abs, sign_dbi, pragmas) => {
             let sign = tcs.glob_type(sign_dbi);
             let sign_cloned = sign.ast.clone();
             let (val_fake, tcs) = tcs.check(&impl_abs, &sign_cloned)?;
+            let tcs = tcs.solve_constraints()?;
+            let (val, mut tcs) = inline_metas(tcs, val_fake)?;
             // We generate axioms for lambda parameters during type-checking.
             // Now it's time to change them back to `var` references.
-            let val = val_fake.map_ast(|ast| ast.generated_to_var());
-            let (val, mut tcs) = inline_metas(tcs, val)?;
+            let val = val.map_ast(|ast| ast.generated_to_var());
+
+            if !pragmas.contains(&Pragma::NonTerminating) {
+                (tcs.call_graph.add_definition(sign_dbi, &val.ast)).map_err(|cycle| {
+                    let names = cycle.into_iter().map(|gi| tcs.glob_names[&gi].clone());
+                    TCE::NonTerminating(val.loc, names.collect())
+                })?;
+            }
 
             tcs.env[sign_dbi.0] = val;
 
@@ -109,20 +120,23 @@ fn check_decl(tcs: TCS, decl: AbsDecl) -> TCM {
             // Err(TCE::DbiOverflow(tcs.env.len(), new_dbi))
             tcs
         }
-        AbsDecl::Sign(sign_abs, self_index) => {
+        AbsDecl::Sign(sign_abs, self_index, name) => {
             let loc = sign_abs.loc();
             let (sign_fake, tcs) = tcs.check(&sign_abs, &TYPE_OMEGA)?;
+            let tcs = tcs.solve_constraints()?;
             let (sign_fake, mut tcs) = inline_metas(tcs, sign_fake)?;
             let sign = sign_fake.map_ast(|ast| ast.generated_to_var());
             let val_info = Val::fresh_unimplemented(self_index).into_info(loc);
             tcs.env.push(val_info);
             tcs.gamma.push(sign);
+            tcs.glob_names.insert(self_index, name.text);
 
             // Give warning on axiom?
             tcs
         }
         AbsDecl::Decl(impl_abs) => {
             let (inferred, tcs) = tcs.infer(&impl_abs)?;
+            let tcs = tcs.solve_constraints()?;
             let (inferred, tcs) = inline_metas(tcs, inferred)?;
             let (compiled, tcs) = tcs.evaluate(impl_abs);
             let (compiled, mut tcs) = inline_metas(tcs, compiled)?;
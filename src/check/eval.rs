@@ -1,9 +1,10 @@
 use voile_util::level::LiftEx;
-use voile_util::loc::{merge_info, Ident};
-use voile_util::meta::MetaSolution;
-use voile_util::uid::DBI;
+use voile_util::loc::{merge_info, Ident, Loc};
+use voile_util::tags::Plicit;
+use voile_util::uid::{DBI, UID};
 
 use crate::check::monad::TCS;
+use crate::check::pattern::evaluate_pattern_lambda;
 use crate::syntax::abs::{Abs, LabAbs};
 use crate::syntax::core::{CaseSplit, Closure, Neutral, TraverseNeutral, Val, ValInfo, Variants};
 
@@ -56,6 +57,8 @@ $$
   \alpha_1 & \text{if} & \eval{a} = \alpha\_0, \alpha\_1
 \end{matrix}\right\\} \\\\
 & \eval{\lambda \xx. a} &&= \lambda \langle \xx . \eval{a} \rangle \\\\
+& \eval{\lambda \\{\xx\\}. a} &&= \eval{a} \\\\
+& \eval{a\ \\{b\\}} &&= \eval{a} \\\\
 & \eval{\xx} &&= [\xx] \\\\
 & \eval{a\ b} &&= \left\\{\begin{matrix}
   [k\\ \eval{b}] & \text{if} & \eval{a} = [k] \\\\
@@ -99,6 +102,19 @@ $$
   [k.n] & \text{if} & \eval{a} = [k] \\\\
   \alpha & \text{if} & \eval{a} = \\{ n = \alpha, \ctyLab \\} \\\\
 \end{matrix}\right\\} \\\\
+& \eval{\\{\| a\ \textbf{with}\ \labVal \|\\}} &&=
+\left\\{\begin{matrix}
+  \\{ \fields{\labVal} \cup (\clabVal \setminus \labVal) \\} & \text{if} & \eval{a} = \\{ \clabVal \\} \\\\
+  [\\{ \fields{\labVal}\recExt{k \setminus \labVal} \\}] & \text{if} & \eval{a} = [k] \\\\
+\end{matrix}\right\\} \\\\
+& \eval{a \setminus n} &&=
+\left\\{\begin{matrix}
+  [k \setminus n] & \text{if} & \eval{a} = [k] \\\\
+  \\{ \clabVal \\} & \text{if} & \eval{a} = \\{ n = \alpha, \clabVal \\} \\\\
+\end{matrix}\right\\} \\\\
+& \eval{a [n \Rightarrow m]} &&= \\{ m = \eval{a.n}\recExt{\eval{a \setminus n}} \\} \\\\
+& \eval{(a : A)} &&= \eval{a} \\\\
+& \eval{\textbf{let}\ \xx = a\ \textbf{in}\ b} &&= \eval{b} [\xx := \eval{a}] \\\\
 & \eval{\nocases} &&= \lambda \langle \rangle \\\\
 & \eval{\case{n}{\xx}{a}{b}} &&=
 \left\\{\begin{matrix}
@@ -117,7 +133,9 @@ fn evaluate(tcs: TCS, abs: Abs) -> (ValInfo, TCS) {
     use Abs::*;
     match abs {
         Type(info, level) => (Val::Type(level).into_info(info), tcs),
-        Var(ident, _, i) => {
+        Var(ident, uid, i) => {
+            let mut tcs = tcs;
+            tcs.local_names.insert(uid, ident.text.clone());
             let resolved = tcs.local_val(i).ast.clone().attach_dbi(i);
             (resolved.into_info(ident.loc), tcs)
         }
@@ -132,9 +150,33 @@ fn evaluate(tcs: TCS, abs: Abs) -> (ValInfo, TCS) {
                 }
             }
         }
+        DepRec(info, fields) => {
+            let mut tcs = tcs;
+            let mut types = Vec::with_capacity(fields.len());
+            for (field, uid) in fields {
+                let (ty, new_tcs) = evaluate(tcs, field.expr);
+                tcs = new_tcs;
+                let loc = field.label.loc;
+                tcs.local_gamma.push(Val::fresh_axiom().into_info(loc));
+                tcs.local_env.push(Val::postulate(uid).into_info(loc));
+                types.push((field.label.text, ty.ast));
+            }
+            types.iter().for_each(|_| tcs.pop_local());
+            (Val::dependent_record_type(types).into_info(info), tcs)
+        }
         Ref(ident, dbi) => (tcs.glob_val(dbi).ast.clone().into_info(ident.loc), tcs),
         Cons(info) => (compile_cons(info), tcs),
-        App(info, f, _, a) => {
+        App(info, f, Plicit::Ex, a) if matches!(*f, LabCons(..)) => {
+            let (f, tcs) = evaluate(tcs, *f);
+            let (a, tcs) = evaluate(tcs, *a);
+            (Val::label_cons(f.ast, a.ast).into_info(info), tcs)
+        }
+        App(info, f, Plicit::Im, _) => {
+            // Implicit arguments are erased
+            let (f, tcs) = evaluate(tcs, *f);
+            (f.ast.into_info(info), tcs)
+        }
+        App(info, f, Plicit::Ex, a) => {
             // The function should always be compiled to DBI-based terms
             let (f, tcs) = evaluate(tcs, *f);
             let (a, tcs) = evaluate(tcs, *a);
@@ -142,9 +184,9 @@ fn evaluate(tcs: TCS, abs: Abs) -> (ValInfo, TCS) {
             let applied = f.apply(a.ast);
             (applied.into_info(info), tcs)
         }
-        Dt(info, kind, _, param_plicit, param_ty, ret_ty) => {
+        Dt(info, kind, uid, param_plicit, param_ty, ret_ty) => {
             let (param_ty, tcs) = evaluate(tcs, *param_ty);
-            let (ret_ty, tcs) = evaluate(tcs, *ret_ty);
+            let (ret_ty, tcs) = evaluate_under_binder(tcs, param_ty.loc, uid, *ret_ty);
             let term = Val::closure_dependent_type(kind, param_plicit, param_ty.ast, ret_ty.ast);
             (term.into_info(info), tcs)
         }
@@ -163,22 +205,75 @@ fn evaluate(tcs: TCS, abs: Abs) -> (ValInfo, TCS) {
             let (p, tcs) = tcs.expand_global(p.ast);
             (p.second().into_info(info), tcs)
         }
-        Proj(info, rec, field) => {
+        Proj(info, rec, field, _) => {
             let (rec, tcs) = evaluate(tcs, *rec);
             let (rec, tcs) = tcs.expand_global(rec.ast);
             (rec.project(field.text).into_info(info), tcs)
         }
-        // This branch is not likely to be reached.
-        Lam(info, _, _, body) => {
-            let (body, tcs) = evaluate(tcs, *body);
-            (body.ast.into_info(info), tcs)
+        LabProj(info, rec, label) => {
+            let (rec, tcs) = evaluate(tcs, *rec);
+            let (label, tcs) = evaluate(tcs, *label);
+            let (rec, tcs) = tcs.expand_global(rec.ast);
+            (rec.project_label(label.ast).into_info(info), tcs)
+        }
+        // Only evaluated as the function of an application, which gives the label.
+        LabCons(_, label) => evaluate(tcs, *label),
+        Update(info, rec, fields) => {
+            let (rec, tcs) = evaluate(tcs, *rec);
+            let (fields, tcs) = evaluate_variants(tcs, fields);
+            let (rec, tcs) = tcs.expand_global(rec.ast);
+            (rec.update_fields(fields).into_info(info), tcs)
+        }
+        Remove(info, rec, field) => {
+            let (rec, tcs) = evaluate(tcs, *rec);
+            let (rec, tcs) = tcs.expand_global(rec.ast);
+            (rec.remove_field(&field.text).into_info(info), tcs)
+        }
+        Rename(info, rec, from, to) => {
+            let (rec, tcs) = evaluate(tcs, *rec);
+            let (rec, tcs) = tcs.expand_global(rec.ast);
+            (rec.rename_field(from.text, to.text).into_info(info), tcs)
+        }
+        Let(info, name, uid, _, def, body) => {
+            let (def, tcs) = evaluate(tcs, *def);
+            // The body is instantiated with the definition, like a beta-redex.
+            let (body, tcs) = evaluate_under_binder(tcs, name.loc, uid, *body);
+            let body = Closure::plain(body.ast).strengthen().instantiate(def.ast);
+            (body.into_info(info), tcs)
+        }
+        Ascription(_, expr, _) => evaluate(tcs, *expr),
+        Lam(info, param, _, Plicit::Im, body) => {
+            // Implicit lambdas are erased, the parameter is never applied.
+            let mut tcs = tcs;
+            let placeholder = Val::fresh_axiom().into_info(param.loc);
+            tcs.push_definition(placeholder, Val::fresh_implicit().into_info(param.loc));
+            let (body, mut tcs) = evaluate(tcs, *body);
+            tcs.pop_local();
+            (tcs.exit_definition(body.ast).into_info(info), tcs)
+        }
+        Lam(info, param, uid, Plicit::Ex, body) => {
+            let (body, tcs) = evaluate_under_binder(tcs, param.loc, uid, *body);
+            (Val::closure_lam(body.ast).into_info(info), tcs)
+        }
+        Fix(info, name, uid, body) => {
+            let (body, tcs) = evaluate_under_binder(tcs, name.loc, uid, *body);
+            (Val::fix(body.ast).into_info(info), tcs)
+        }
+        // Values of recursive types are represented by their unfoldings.
+        Fold(info, expr) | Unfold(info, expr) => {
+            let (expr, tcs) = evaluate(tcs, *expr);
+            (expr.ast.into_info(info), tcs)
         }
         Lift(info, levels, expr) => {
             let (expr, tcs) = evaluate(tcs, *expr);
             let (expr, tcs) = tcs.expand_global(expr.ast);
             (expr.lift(levels).into_info(info), tcs)
         }
-        Meta(ident, mi) => (Val::meta(mi).into_info(ident.loc), tcs),
+        Meta(ident, mi) => {
+            let mut tcs = tcs;
+            tcs.scope_meta(mi);
+            (Val::meta(mi).into_info(ident.loc), tcs)
+        }
         RowPoly(info, kind, variants, ext) => {
             let (variants, tcs) = evaluate_variants(tcs, variants);
             let row_poly = Val::RowPoly(kind, variants);
@@ -195,19 +290,124 @@ fn evaluate(tcs: TCS, abs: Abs) -> (ValInfo, TCS) {
             let expr = Val::RowKind(Default::default(), kind, labels);
             (expr.into_info(info), tcs)
         }
-        CaseOr(label, _, _, body, or) => {
+        LabelType(info) => (Val::LabelType.into_info(info), tcs),
+        Label(label) => (Val::Label(label.text).into_info(label.loc), tcs),
+        LabRow(info, kind, label, ty, rest) => {
+            let (label, tcs) = evaluate(tcs, *label);
+            let (ty, tcs) = evaluate(tcs, *ty);
+            let (rest, tcs) = match rest {
+                Some(rest) => evaluate(tcs, *rest),
+                None => (Val::RowPoly(kind, Default::default()).into_info(info), tcs),
+            };
+            let row = Val::label_row(kind, label.ast, ty.ast, rest.ast);
+            (row.into_info(info), tcs)
+        }
+        LabKind(info, label, kind) => {
+            let (label, tcs) = evaluate(tcs, *label);
+            let (kind, tcs) = evaluate(tcs, *kind);
+            (Val::label_kind(label.ast, kind.ast).into_info(info), tcs)
+        }
+        RowConcat(info, left, right) => {
+            let (left, tcs) = evaluate(tcs, *left);
+            let (right, tcs) = evaluate(tcs, *right);
+            let (left, tcs) = tcs.expand_global(left.ast);
+            let (right, tcs) = tcs.expand_global(right.ast);
+            (left.row_concat(right).into_info(info), tcs)
+        }
+        RowMap(info, function, row) => {
+            let (function, tcs) = evaluate(tcs, *function);
+            let (row, tcs) = evaluate(tcs, *row);
+            let (function, tcs) = tcs.expand_global(function.ast);
+            let (row, tcs) = tcs.expand_global(row.ast);
+            (row.row_map(function).into_info(info), tcs)
+        }
+        RowDiff(info, row, labels) => {
+            let (row, tcs) = evaluate(tcs, *row);
+            let (row, tcs) = tcs.expand_global(row.ast);
+            let labels: Vec<_> = labels.into_iter().map(|l| l.text).collect();
+            (row.row_diff(&labels).into_info(info), tcs)
+        }
+        CaseOr(label, binding, uid, body, or) => {
             let (or, tcs) = tcs.evaluate(*or);
-            let (body, tcs) = tcs.evaluate(*body);
+            let (body, tcs) = evaluate_under_binder(tcs, binding.loc, uid, *body);
             let info = merge_info(&label, &or);
+            // Like in type-checking, the first clause of a label wins over the duplicates.
+            let mut or = or.ast;
+            if let Val::Lam(Closure::Tree(split)) | Val::Neut(Neutral::OrSplit(split, _)) = &mut or
+            {
+                split.remove(&label.text);
+            }
             let mut split = CaseSplit::default();
             split.insert(label.text, Closure::plain(body.ast));
             let lam = Val::case_tree(split);
-            (or.ast.split_extend(lam).into_info(info), tcs)
+            (or.split_extend(lam).into_info(info), tcs)
         }
         Whatever(info) => (Val::Lam(Closure::default()).into_info(info), tcs),
+        // Merged into the case tree by `split_extend` as its fallback.
+        CaseDefault(info, binding, uid, body) => {
+            let (body, tcs) = evaluate_under_binder(tcs, binding.loc, uid, *body);
+            (Val::closure_lam(body.ast).into_info(info), tcs)
+        }
+        PatLam(info, uid, clauses) => {
+            let not_checked = "Pattern-matching lambdas are evaluated after being checked.";
+            let tree = tcs.case_trees.get(&uid).cloned().expect(not_checked);
+            let (lam, tcs) = evaluate_pattern_lambda(tcs, &tree, clauses);
+            (lam.into_info(info), tcs)
+        }
+        Match(info, scrutinee, _, _, _, cases) => {
+            let (cases, tcs) = evaluate(tcs, *cases);
+            let (scrutinee, tcs) = evaluate(tcs, *scrutinee);
+            let (cases, tcs) = tcs.expand_global(cases.ast);
+            (cases.apply(scrutinee.ast).into_info(info), tcs)
+        }
+        Eq(info, a, b) => {
+            let (a, tcs) = evaluate(tcs, *a);
+            let (b, tcs) = evaluate(tcs, *b);
+            (Val::id(a.ast, b.ast).into_info(info), tcs)
+        }
+        Refl(info) => (Val::Refl.into_info(info), tcs),
+        J(info, proof, _, _, _, refl) => {
+            let (refl, tcs) = evaluate(tcs, *refl);
+            let (proof, tcs) = evaluate(tcs, *proof);
+            let (proof, tcs) = tcs.expand_global(proof.ast);
+            (proof.eliminate_id(refl.ast).into_info(info), tcs)
+        }
+        NatType(info) => (Val::NatType.into_info(info), tcs),
+        Nat(info, n) => (Val::Nat(n).into_info(info), tcs),
+        NatOp(info, op, a, b) => {
+            let (a, tcs) = evaluate(tcs, *a);
+            let (b, tcs) = evaluate(tcs, *b);
+            let (a, tcs) = tcs.expand_global(a.ast);
+            let (b, tcs) = tcs.expand_global(b.ast);
+            (Val::nat_op(op, a, b).into_info(info), tcs)
+        }
+        NatElim(info, nat, _, _, zero, (pred, pred_uid), (result, result_uid), step) => {
+            let (zero, tcs) = evaluate(tcs, *zero);
+            // The step is a curried function of the predecessor and the result.
+            let step = Abs::lam(info, result, result_uid, Plicit::Ex, *step);
+            let (step, tcs) = evaluate_under_binder(tcs, pred.loc, pred_uid, step);
+            let step = Val::closure_lam(step.ast);
+            let (nat, tcs) = evaluate(tcs, *nat);
+            let (nat, tcs) = tcs.expand_global(nat.ast);
+            (nat.eliminate_nat(zero.ast, step).into_info(info), tcs)
+        }
     }
 }
 
+/// Evaluate the body of a lambda (or a case-split clause),
+/// where the parameter is a postulated value.
+/// The parameter is turned into the variable bound by the closure.
+///
+/// Evaluation erases what only type-checking needs: the parameter gets a dummy type,
+/// just like the fields of dependent records, and the motives of eliminators are dropped.
+fn evaluate_under_binder(mut tcs: TCS, loc: Loc, uid: UID, body: Abs) -> (ValInfo, TCS) {
+    tcs.local_gamma.push(Val::fresh_axiom().into_info(loc));
+    tcs.local_env.push(Val::postulate(uid).into_info(loc));
+    let (body, mut tcs) = evaluate(tcs, body);
+    tcs.pop_local();
+    (body.map_ast(|body| body.abstract_local(uid)), tcs)
+}
+
 fn evaluate_variants(mut tcs: TCS, variants: Vec<LabAbs>) -> (Variants, TCS) {
     let mut out_variants = Variants::new();
     for labelled in variants.into_iter() {
@@ -220,15 +420,14 @@ fn evaluate_variants(mut tcs: TCS, variants: Vec<LabAbs>) -> (Variants, TCS) {
 
 /// Expand global references to concrete values,
 /// like meta references or global references due to recursion.
-fn expand_global(tcs: TCS, expr: Val) -> (Val, TCS) {
+fn expand_global(mut tcs: TCS, expr: Val) -> (Val, TCS) {
     let val = expr.map_neutral(&mut |neut| match neut {
-        Neutral::Ref(index) => tcs.glob_val(index).ast.clone(),
-        Neutral::Meta(mi) => match &tcs.meta_context.solution(mi) {
-            MetaSolution::Solved(val) => *val.clone(),
-            MetaSolution::Unsolved => panic!("Cannot eval unsolved meta: {:?}", mi),
-            MetaSolution::Inlined => unreachable!(),
-        },
-        neut => Val::Neut(neut),
+        Neutral::Ref(index) => {
+            let val = tcs.glob_val(index).ast.clone();
+            tcs.instantiate_levels(val)
+        }
+        // Unsolved metas stay neutral, they may be solved later.
+        neut => tcs.unfold_meta(&neut).unwrap_or(Val::Neut(neut)),
     });
     (val, tcs)
 }
@@ -1,12 +1,20 @@
+use std::collections::BTreeSet;
+
+use voile_util::axiom::Axiom;
 use voile_util::level::{Level, LiftEx};
 use voile_util::loc::*;
-use voile_util::tags::{PiSig::*, Plicit, VarRec, VarRec::*};
+use voile_util::tags::{NatOp as NatOpKind, PiSig::*, Plicit, VarRec, VarRec::*};
+use voile_util::uid::{next_uid, UID};
 
 use crate::syntax::abs::{Abs, LabAbs};
-use crate::syntax::core::{CaseSplit, Closure, Fields, Neutral, Val, Variants, TYPE_OMEGA};
+use crate::syntax::core::{
+    CaseSplit, Closure, Fields, Neutral, TVal, Val, ValInfo, Variants, TYPE_OMEGA,
+};
 
 use super::eval::compile_cons;
 use super::monad::{ValTCM, TCE, TCM, TCS};
+use super::pattern::{check_pattern_lambda, substitute_local, Motive};
+use super::positivity::check_positivity;
 
 /**
 Check an abstract term against an expected type and produce a well-typed term.
@@ -85,7 +93,7 @@ $$
 }
 \\\\ \space \\\\
 \cfrac{
-  \cheval{\Gamma,\xx : \cA\_1}{b}{\cB} \quad
+  \cheval{\Gamma,\xx : \cA\_1}{b}{\cB[\xx := \texttt{@}n\ \xx]} \quad
   \Gtyck{A\_1}{\ty}{\cA\_1}
 }{
   \cfrac{
@@ -107,63 +115,124 @@ $$
     }
   }
 }
+\quad
+\cfrac{
+  \Gcheval{a}{\piTy{\xx : \variant{\ctyLab} . \cB}} \quad
+  n \notin \ctyLab
+}{
+  \Gcheval{
+    (\case{n}{\xx}{b}{a})
+  }{
+    \piTy{\xx : \variant{\ctyLab} . \cB}
+  }
+}
+\\\\ \space \\\\
+\cfrac{
+  \Gtyck{A}{\ty}{\cA} \quad
+  \Gtyck{a}{\cA}{\alpha} \quad
+  \tyck{\Gamma, \xx : \cA = \alpha}{b}{\cB}{\beta}
+}{
+  \Gtyck{\textbf{let}\ \xx : A = a\ \textbf{in}\ b}{\cB}{\beta}
+}
+\\\\ \space \\\\
+\cfrac{
+  \Gtyck{a}{\cB[\xx := \alpha]}{\beta}
+}{
+  \Gtyck{a}{\sigTy{\\{\xx : \cA\\} . \cB}}{\alpha, \beta}
+} \quad
+\cfrac{
+  \Gtyck{a}{\cA[\xx := \alpha]}{\beta}
+}{
+  \Gtyck{a}{\piTy{\\{\xx : \cB\\} . \cA}}{\beta}
+}
 $$
 */
 fn check(mut tcs: TCS, expr: &Abs, expected_type: &Val) -> ValTCM {
     use Abs::*;
     match (expr, expected_type) {
         (Type(info, lower), Val::Type(upper)) => {
-            if upper > lower {
-                Ok((Val::Type(*lower).into_info(*info), tcs))
-            } else {
-                Err(TCE::LevelMismatch(expr.loc(), *lower + 1, *upper))
-            }
+            let tcs = tcs
+                .subtype_level(&(lower.clone() + 1), upper)
+                .map_err(|_| TCE::LevelMismatch(expr.loc(), lower.clone() + 1, upper.clone()))?;
+            Ok((Val::Type(lower.clone()).into_info(*info), tcs))
         }
-        (RowKind(info, kind, labels), Val::Type(upper)) if *upper > From::from(0u32) => {
+        (RowKind(info, kind, labels), Val::Type(upper)) if *upper >= Level::from(1u32) => {
             let labels = labels.iter().map(|l| &l.text).cloned().collect();
             let expr = Val::RowKind(Default::default(), *kind, labels);
             Ok((expr.into_info(*info), tcs))
         }
-        (Meta(ident, mi), _) => Ok((Val::meta(*mi).into_info(ident.loc), tcs)),
+        (LabelType(info), Val::Type(..)) => Ok((Val::LabelType.into_info(*info), tcs)),
+        (NatType(info), Val::Type(..)) => Ok((Val::NatType.into_info(*info), tcs)),
+        (LabKind(info, label, kind), Val::Type(upper)) if *upper >= Level::from(1u32) => {
+            let (label, tcs) = tcs.check(label, &Val::LabelType)?;
+            let (kind, tcs) = tcs.check(kind, expected_type)?;
+            let kind = Val::label_kind(label.ast, kind.ast);
+            Ok((kind.into_info(*info), tcs))
+        }
+        (Meta(ident, mi), _) => {
+            let mut tcs = tcs;
+            tcs.scope_meta(*mi);
+            Ok((Val::meta(*mi).into_info(ident.loc), tcs))
+        }
         (Pair(info, fst, snd), Val::Dt(Sigma, Plicit::Ex, param_ty, closure)) => {
-            let (fst_term, mut tcs) = tcs.check(&**fst, &**param_ty).map_err(|e| e.wrap(*info))?;
-            let fst_term_ast = fst_term.ast.clone();
+            let (fst_term, tcs) = tcs.check(&**fst, &**param_ty).map_err(|e| e.wrap(*info))?;
+            let fst_term_ast = fst_term.ast;
+            // The pair does not bind any variable (unlike the sigma type),
+            // so we only substitute the first component into the type.
             let snd_ty = closure.instantiate_borrow(&fst_term_ast);
-            // This `fst_term.loc()` is probably wrong, but I'm not sure how to fix
-            let param_type = param_ty.clone().into_info(fst_term.loc());
-            tcs.local_gamma.push(param_type);
-            tcs.local_env.push(fst_term);
-            let (snd_term, mut tcs) = tcs.check(&**snd, &snd_ty).map_err(|e| e.wrap(*info))?;
-            tcs.pop_local();
+            let (snd_term, tcs) = tcs.check(&**snd, &snd_ty).map_err(|e| e.wrap(*info))?;
             let pair = Val::pair(fst_term_ast, snd_term.ast).into_info(*info);
             Ok((pair, tcs))
         }
-        (Lam(full_loc, param_loc, uid, body), Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty)) => {
+        (
+            Lam(full_loc, param_loc, uid, Plicit::Ex, body),
+            Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty),
+        ) => {
             let param_type = param_ty.clone().into_info(param_loc.loc);
             tcs.local_gamma.push(param_type);
             let mocked = Val::postulate(*uid);
             let mocked_term = mocked.clone().into_info(param_loc.loc);
             tcs.local_env.push(mocked_term);
+            tcs.local_names.insert(*uid, param_loc.text.clone());
             let ret_ty_body = ret_ty.instantiate_cloned(mocked);
             let (lam_term, mut tcs) = tcs
                 .check(body, &ret_ty_body)
                 .map_err(|e| e.wrap(*full_loc))?;
             tcs.pop_local();
-            let lam = Val::closure_lam(lam_term.ast);
+            let lam = Val::closure_lam(lam_term.ast.abstract_local(*uid));
             Ok((lam.into_info(*full_loc), tcs))
         }
-        (Lam(..), Val::Dt(Pi, Plicit::Im, param_ty, ret_ty)) => {
-            let param_type = param_ty.clone().into_info(Default::default());
-            tcs.local_gamma.push(param_type);
-            let mocked = Val::fresh_implicit();
-            let mocked_term = mocked.clone().into_info(Default::default());
-            tcs.local_env.push(mocked_term);
-            let ret_ty_body = ret_ty.instantiate_cloned(mocked);
-
-            let (lam, mut tcs) = tcs.check(&expr.clone(), &ret_ty_body)?;
+        (
+            Lam(full_loc, param_loc, _, Plicit::Im, body),
+            Val::Dt(Pi, Plicit::Im, param_ty, ret_ty),
+        ) => {
+            // Implicit lambdas are erased, so the parameter is a local definition.
+            let implicit = unsafe { next_uid() };
+            let mocked = Val::implicit(implicit);
+            let ret_ty_body = tcs.enter_definition(ret_ty.instantiate_cloned(mocked.clone()));
+            let param_type = param_ty.clone().into_info(param_loc.loc);
+            tcs.push_definition(param_type, mocked.into_info(param_loc.loc));
+            let (body, mut tcs) = tcs
+                .check(body, &ret_ty_body)
+                .map_err(|e| e.wrap(*full_loc))?;
             tcs.pop_local();
-            Ok((lam, tcs))
+            let body = tcs.exit_definition(body.ast);
+            if uses_implicit(implicit, body.clone()) {
+                return Err(TCE::RelevantImplicit(param_loc.clone()));
+            }
+            Ok((body.into_info(*full_loc), tcs))
+        }
+        (Lam(..), Val::Dt(Pi, Plicit::Im, _, ret_ty))
+        | (CaseOr(..), Val::Dt(Pi, Plicit::Im, _, ret_ty))
+        | (PatLam(..), Val::Dt(Pi, Plicit::Im, _, ret_ty))
+        | (CaseDefault(..), Val::Dt(Pi, Plicit::Im, _, ret_ty))
+        | (Whatever(..), Val::Dt(Pi, Plicit::Im, _, ret_ty)) => {
+            // The implicit parameter is not bound by the lambda (or the case-split),
+            // so it does not take a de-bruijn index.
+            let ret_ty_body = ret_ty.instantiate_cloned(Val::fresh_implicit());
+            tcs.check(expr, &ret_ty_body)
         }
+        (Lam(info, _, _, Plicit::Im, _), ty) => Err(TCE::NotImplicitPi(*info, ty.clone())),
         (Cons(info), Val::Dt(Pi, ..)) => Ok((compile_cons(info.clone()), tcs)),
         (Dt(info, kind, uid, param_plicit, param, ret), Val::Type(..)) => {
             let (param, mut tcs) = tcs
@@ -180,14 +249,75 @@ fn check(mut tcs: TCS, expr: &Abs, expected_type: &Val) -> ValTCM {
                 .into_info(*info);
             Ok((dt, tcs))
         }
+        (Fix(info, name, uid, body), Val::Type(..)) => {
+            let mut tcs = tcs;
+            tcs.local_gamma
+                .push(expected_type.clone().into_info(name.loc));
+            tcs.local_env.push(Val::postulate(*uid).into_info(name.loc));
+            let (body, mut tcs) = tcs.check(body, expected_type).map_err(|e| e.wrap(*info))?;
+            tcs.pop_local();
+            check_positivity(name, *uid, &body.ast)?;
+            Ok((Val::fix(body.ast).into_info(*info), tcs))
+        }
+        (Fold(info, expr), ty @ Val::Fix(..)) => {
+            let (expr, tcs) = (tcs.check(expr, &ty.clone().unfold())).map_err(|e| e.wrap(*info))?;
+            Ok((expr.ast.into_info(*info), tcs))
+        }
+        (Fold(info, _), ty) => Err(TCE::NotFix(*info, ty.clone())),
+        (Refl(info), Val::Id(a, b)) => {
+            let tcs = tcs.unify(a, b).map_err(|e| e.wrap(*info))?;
+            Ok((Val::Refl.into_info(*info), tcs))
+        }
+        (Refl(info), ty) => Err(TCE::NotId(*info, ty.clone())),
+        (DepRec(info, fields), Val::Type(..)) => {
+            let (record, _, tcs) = check_dependent_record(tcs, *info, fields, expected_type)?;
+            Ok((record, tcs))
+        }
         (RowPoly(info, Record, variants, ext), Val::RowKind(l, Record, labels)) => {
-            check_row_polymorphic_type(tcs, *info, *l, Record, variants, ext, labels)
+            check_row_polymorphic_type(tcs, *info, l, Record, variants, ext, labels)
         }
         (RowPoly(info, Variant, variants, ext), Val::RowKind(l, Variant, labels)) => {
-            check_row_polymorphic_type(tcs, *info, *l, Variant, variants, ext, labels)
+            check_row_polymorphic_type(tcs, *info, l, Variant, variants, ext, labels)
         }
         (RowPoly(info, kind, variants, ext), Val::Type(l)) => {
-            check_row_polymorphic_type(tcs, *info, *l, *kind, variants, ext, &[])
+            check_row_polymorphic_type(tcs, *info, l, *kind, variants, ext, &[])
+        }
+        // The label variable may be any of the other labels of the row,
+        // so it has to be the only entry (the rest lacks it).
+        (LabRow(info, kind, label, ty, rest), Val::RowKind(l, row_kind, labels))
+            if kind == row_kind && labels.is_empty() =>
+        {
+            check_label_row(tcs, *info, l, *kind, label, ty, rest)
+        }
+        (LabRow(info, kind, label, ty, rest), Val::Type(l)) => {
+            check_label_row(tcs, *info, l, *kind, label, ty, rest)
+        }
+        (LabRow(_, kind, label, ..), Val::RowKind(_, _, labels)) if !labels.is_empty() => Err(
+            TCE::MayEqualLabel(*kind, label_name(label), labels[0].clone()),
+        ),
+        (LabRow(_, kind, label, ..), _) => Err(TCE::MayContainLabel(*kind, label_name(label))),
+        (RowConcat(..), Val::Type(l)) | (RowDiff(..), Val::Type(l)) => {
+            let (_, tcs) = check_row_operator(tcs, expr, Some(l))?;
+            Ok(tcs.evaluate(expr.clone()))
+        }
+        (RowConcat(info, ..), Val::RowKind(l, kind, labels))
+        | (RowMap(info, ..), Val::RowKind(l, kind, labels))
+        | (RowDiff(info, ..), Val::RowKind(l, kind, labels)) => {
+            let (_, tcs) = check_row_operator(tcs, expr, Some(l))?;
+            let (row, tcs) = tcs.evaluate(expr.clone());
+            if row_kind_of(&tcs, &row.ast) != Some(*kind) {
+                return Err(TCE::NotRowType(*kind, *info, row.ast));
+            }
+            match (labels.iter()).find(|label| !row_type_lacks(&tcs, &row.ast, label)) {
+                Some(label) => Err(TCE::MayContainLabel(*kind, label.clone())),
+                None => Ok((row, tcs)),
+            }
+        }
+        (App(info, f, Plicit::Ex, a), _) if matches!(**f, LabCons(..)) => {
+            check_label_cons(tcs, *info, f, a, expected_type)
+        }
+        (Rec(info, fields, None), Val::DepRec(..)) => {
+            check_dependent_fields(tcs, *info, fields, expected_type)
         }
         (Rec(info, fields, more), Val::RowPoly(Record, field_types)) => {
             // Warn about unneeded fields?
@@ -195,7 +325,8 @@ fn check(mut tcs: TCS, expr: &Abs, expected_type: &Val) -> ValTCM {
             match more {
                 Some(more) => {
                     let more_type = Val::record_type(rest_field_types);
-                    let (more, tcs) = tcs.check(&**more, &more_type)?;
+                    let (more, tcs) =
+                        check_record_ext(tcs, fields, &nice_fields, more, &more_type)?;
                     let record = Val::Rec(nice_fields).rec_extend(more.ast);
                     Ok((record.into_info(*info), tcs))
                 }
@@ -211,7 +342,8 @@ fn check(mut tcs: TCS, expr: &Abs, expected_type: &Val) -> ValTCM {
                     } else {
                         Val::neutral_record_type(rest_field_types, *more_types.clone())
                     };
-                    let (more, tcs) = tcs.check(&**more, &more_type)?;
+                    let (more, tcs) =
+                        check_record_ext(tcs, fields, &nice_fields, more, &more_type)?;
                     let record = Val::Rec(nice_fields).rec_extend(more.ast);
                     Ok((record.into_info(*info), tcs))
                 }
@@ -224,53 +356,456 @@ fn check(mut tcs: TCS, expr: &Abs, expected_type: &Val) -> ValTCM {
                 .map_err(|e| e.wrap(*info))?;
             Ok((expr.map_ast(|ast| ast.lift(*levels)), tcs))
         }
-        (Whatever(info), Val::Dt(Pi, _, param_ty, ..)) => match &**param_ty {
+        (Whatever(info), Val::Dt(Pi, Plicit::Ex, param_ty, ..)) => match &**param_ty {
             Val::RowPoly(Variant, variants) if variants.is_empty() => {
                 Ok((Val::Lam(Closure::default()).into_info(*info), tcs))
             }
             ty => Err(TCE::NotEmpty(info.clone(), ty.clone())),
         },
-        // How about when `Dt` is `Plicit::Im`?
-        (CaseOr(label, binding, uid, body, or), Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty)) => {
-            let lam_info = merge_info(binding, &**body);
-            let lam = Lam(lam_info, binding.clone(), *uid, body.clone());
-            let (variants, ext) = match &**param_ty {
-                Val::Neut(Neutral::Row(Variant, variants, ext)) => (variants, Some(&**ext)),
-                Val::RowPoly(Variant, variants) => (variants, None),
-                ty => {
-                    let info = merge_info(label, &**or);
-                    return Err(TCE::NotRowType(Variant, info, ty.clone()));
+        (CaseOr(..), Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty))
+        | (PatLam(..), Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty))
+        | (CaseDefault(..), Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty)) => {
+            let motive = Motive::Closure(ret_ty);
+            check_case_or(tcs, expr, param_ty, &Default::default(), &motive)
+        }
+        (Ascription(info, expr, ty), expected) => {
+            let (ty, tcs) = tcs.check(ty, &TYPE_OMEGA).map_err(|e| e.wrap(*info))?;
+            let (term, tcs) = tcs.check(expr, &ty.ast).map_err(|e| e.wrap(*info))?;
+            let tcs = tcs.subtype(&ty.ast, expected).map_err(|e| e.wrap(*info))?;
+            Ok((term.ast.into_info(*info), tcs))
+        }
+        (Let(info, name, _, ty, def, body), expected) => {
+            let tcs = define_local(tcs, name, ty, def).map_err(|e| e.wrap(*info))?;
+            let expected = tcs.enter_definition(expected.clone());
+            let (body, mut tcs) = tcs.check(body, &expected).map_err(|e| e.wrap(*info))?;
+            tcs.pop_local();
+            let body = tcs.exit_definition(body.ast);
+            Ok((body.into_info(*info), tcs))
+        }
+        (expr, Val::Dt(Sigma, Plicit::Im, _, closure)) if !is_elimination(expr) => {
+            // The hidden component is inferred through a meta,
+            // which is solved when checking the visible component.
+            let mut tcs = tcs;
+            let hidden = tcs.fresh_meta();
+            let snd_ty = closure.instantiate_borrow(&hidden);
+            let (snd_term, tcs) = tcs.check(expr, &snd_ty)?;
+            let info = snd_term.loc;
+            let pair = Val::pair(hidden, snd_term.ast);
+            Ok((pair.into_info(info), tcs))
+        }
+        (expr, anything) => check_fallback(tcs, expr, anything),
+    }
+}
+
+/// Check a case-split against a Pi type from `param_ty`,
+/// where each clause is checked against the return type instantiated
+/// with the constructor call it matches.
+/// `handled` are the variants handled by the clauses above.
+fn check_case_or(
+    mut tcs: TCS,
+    expr: &Abs,
+    param_ty: &Val,
+    handled: &Variants,
+    motive: &Motive,
+) -> ValTCM {
+    // Values of recursive types are split as their unfoldings.
+    if let Val::Fix(..) = param_ty {
+        let param_ty = param_ty.clone().unfold();
+        return check_case_or(tcs, expr, &param_ty, handled, motive);
+    }
+    let (label, binding, uid, body, or) = match expr {
+        Abs::CaseOr(label, binding, uid, body, or) => (label, binding, uid, body, or),
+        Abs::PatLam(info, uid, clauses) => {
+            let tcs = check_pattern_lambda(tcs, *info, *uid, clauses, param_ty, motive)?;
+            return Ok(tcs.evaluate(expr.clone()));
+        }
+        Abs::CaseDefault(info, binding, uid, body) => {
+            return check_case_default(tcs, *info, binding, *uid, body, param_ty, motive);
+        }
+        expr => {
+            let function = Val::dependent_type(Pi, Plicit::Ex, param_ty.clone(), motive.closure());
+            return tcs.check(expr, &function);
+        }
+    };
+    let (variants, ext) = match param_ty {
+        Val::Neut(Neutral::Row(Variant, variants, ext)) => (variants, Some(&**ext)),
+        Val::RowPoly(Variant, variants) => (variants, None),
+        ty => {
+            let info = merge_info(label, &**or);
+            return Err(TCE::NotRowType(Variant, info, ty.clone()));
+        }
+    };
+    if ext.is_none() {
+        let missing = uncovered_labels(expr, variants);
+        if !missing.is_empty() {
+            return Err(TCE::MissingClauses(merge_info(label, &**or), missing));
+        }
+    }
+    let mut variants = variants.clone();
+    let clause_param_ty = match variants.remove(&label.text) {
+        Some(param_ty) => param_ty,
+        // The clause is never reached, but it is still checked.
+        None if ext.is_none() => {
+            let info = merge_info(label, &**body);
+            let (warning, clause_param_ty) = match handled.get(&label.text) {
+                Some(ty) => (TCE::DuplicateClause(info, label.text.clone()), ty.clone()),
+                // The scrutinee can never be constructed with this label,
+                // so the argument type is whatever the body needs.
+                None => {
+                    let param_ty = tcs.fresh_meta();
+                    (TCE::UnreachableClause(info, label.text.clone()), param_ty)
                 }
             };
-            let mut variants = variants.clone();
-            let param_ty = variants
-                .remove(&label.text)
-                .ok_or_else(|| TCE::MissingVariant(Variant, label.text.clone()))?;
-            let input = match ext {
-                None => Val::variant_type(variants),
-                Some(ext) => Val::neutral_variant_type(variants, ext.clone()),
-            };
-            let stripped_function = Val::pi(Plicit::Ex, input, ret_ty.clone());
-            let dt = Val::pi(Plicit::Ex, param_ty, ret_ty.clone());
-            let (body, tcs) = tcs.check(&lam, &dt)?;
-            let mut split = CaseSplit::default();
-            split.insert(label.text.clone(), Closure::plain(body.ast));
-            let ext = Val::case_tree(split);
-            let (or, tcs) = tcs.check(&**or, &stripped_function)?;
-            Ok((or.ast.split_extend(ext).into_info(or.loc), tcs))
+            tcs.warnings.push(warning);
+            let clause = Val::cons(label.text.clone(), Val::postulate(*uid));
+            let clause_ty = motive.clause_type(&clause);
+            let tcs = check_dead_clause(tcs, binding, *uid, body, clause_param_ty, &clause_ty)?;
+            return check_case_or(tcs, or, param_ty, handled, motive);
         }
-        (expr, anything) => check_fallback(tcs, expr, anything),
+        None => return Err(TCE::MissingVariant(Variant, label.text.clone())),
+    };
+    let input = match ext {
+        None => Val::variant_type(variants),
+        Some(ext) => Val::variant_type(variants).row_extend(Val::Neut(ext.clone())),
+    };
+    // The clause binds the variant's argument, just like a lambda.
+    let lam_info = merge_info(binding, &**body);
+    let mut handled = handled.clone();
+    handled.insert(label.text.clone(), clause_param_ty.clone());
+    tcs.local_gamma.push(clause_param_ty.into_info(binding.loc));
+    let mocked = Val::postulate(*uid);
+    tcs.local_env.push(mocked.clone().into_info(binding.loc));
+    let clause_ty = motive.clause_type(&Val::cons(label.text.clone(), mocked));
+    let (body, mut tcs) = tcs.check(body, &clause_ty).map_err(|e| e.wrap(lam_info))?;
+    tcs.pop_local();
+    let mut split = CaseSplit::default();
+    split.insert(
+        label.text.clone(),
+        Closure::plain(body.ast.abstract_local(*uid)),
+    );
+    let ext = Val::case_tree(split);
+    let (or, tcs) = check_case_or(tcs, or, &input, &handled, motive)?;
+    Ok((or.ast.split_extend(ext).into_info(or.loc), tcs))
+}
+
+/// Check a default clause of a case-split, which binds the scrutinee
+/// at the variant type of the labels not handled by the clauses above.
+fn check_case_default(
+    mut tcs: TCS,
+    info: Loc,
+    binding: &Ident,
+    uid: UID,
+    body: &Abs,
+    param_ty: &Val,
+    motive: &Motive,
+) -> ValTCM {
+    match param_ty {
+        // Every label is handled above, so it is checked but dropped like `whatever`.
+        Val::RowPoly(Variant, variants) if variants.is_empty() => {
+            tcs.warnings.push(TCE::RedundantClause(info));
+            let clause_ty = motive.clause_type(&Val::postulate(uid));
+            let tcs = check_dead_clause(tcs, binding, uid, body, param_ty.clone(), &clause_ty)?;
+            return Ok((Val::Lam(Closure::default()).into_info(info), tcs));
+        }
+        ty if row_kind_of(&tcs, ty) == Some(Variant) => {}
+        ty => return Err(TCE::NotRowType(Variant, info, ty.clone())),
+    }
+    tcs.local_gamma
+        .push(param_ty.clone().into_info(binding.loc));
+    let mocked = Val::postulate(uid);
+    tcs.local_env.push(mocked.clone().into_info(binding.loc));
+    let clause_ty = motive.clause_type(&mocked);
+    let (body, mut tcs) = tcs.check(body, &clause_ty).map_err(|e| e.wrap(info))?;
+    tcs.pop_local();
+    Ok((
+        Val::closure_lam(body.ast.abstract_local(uid)).into_info(info),
+        tcs,
+    ))
+}
+
+/// Check the body of a clause that is never reached, which binds `binding` at `param_ty`.
+fn check_dead_clause(
+    mut tcs: TCS,
+    binding: &Ident,
+    uid: UID,
+    body: &Abs,
+    param_ty: Val,
+    clause_ty: &Val,
+) -> TCM {
+    let info = merge_info(binding, body);
+    tcs.local_gamma.push(param_ty.into_info(binding.loc));
+    tcs.local_env
+        .push(Val::postulate(uid).into_info(binding.loc));
+    let (_, mut tcs) = tcs.check(body, clause_ty).map_err(|e| e.wrap(info))?;
+    tcs.pop_local();
+    Ok(tcs)
+}
+
+/// Check a dependent record type, each field type is checked against `universe`
+/// with the previous fields in the context.
+/// Also returns the maximum level of the field types.
+fn check_dependent_record(
+    mut tcs: TCS,
+    info: Loc,
+    fields: &[(LabAbs, UID)],
+    universe: &Val,
+) -> TCM<(ValInfo, Level, TCS)> {
+    let mut types = Vec::with_capacity(fields.len());
+    let mut level = Level::default();
+    for (field, uid) in fields {
+        let label = &field.label;
+        if types.iter().any(|(l, _)| *l == label.text) {
+            return Err(TCE::duplicate_field(label.clone()));
+        }
+        let (ty, new_tcs) = (tcs.check(&field.expr, universe)).map_err(|e| e.wrap(info))?;
+        tcs = new_tcs;
+        level = level.max(ty.ast.level());
+        tcs.local_gamma.push(ty.clone());
+        tcs.local_env
+            .push(Val::postulate(*uid).into_info(label.loc));
+        types.push((label.text.clone(), ty.ast));
+    }
+    types.iter().for_each(|_| tcs.pop_local());
+    let record = Val::dependent_record_type(types).into_info(info);
+    Ok((record, level, tcs))
+}
+
+/// Check a record literal against a dependent record type,
+/// the type of a field is instantiated with the values of the previous fields.
+fn check_dependent_fields(mut tcs: TCS, info: Loc, fields: &[LabAbs], ty: &Val) -> ValTCM {
+    let mut nice_fields = Fields::new();
+    let mut ty = ty.clone();
+    while let Val::DepRec(label, field_ty, rest) = ty {
+        let field = (fields.iter())
+            .find(|field| field.label.text == label)
+            .ok_or_else(|| TCE::MissingVariant(Record, label.clone()))?;
+        let (field, new_tcs) = (tcs.check(&field.expr, &field_ty)).map_err(|e| e.wrap(info))?;
+        tcs = new_tcs;
+        ty = rest.instantiate_borrow(&field.ast);
+        nice_fields.insert(label, field.ast);
+    }
+    // The end of the telescope is a non-dependent record type.
+    match ty {
+        Val::RowPoly(Record, field_types) => {
+            let (mut rest_fields, rest_field_types, tcs) = check_fields(tcs, fields, &field_types)?;
+            nice_fields.append(&mut rest_fields);
+            check_fields_no_more(info, nice_fields, rest_field_types, tcs)
+        }
+        ty => Err(TCE::NotRowType(Record, info, ty)),
+    }
+}
+
+/// The type of the field `field` of `record`, whose type is the dependent record type `ty`.
+pub fn dependent_field_type(info: Loc, ty: Val, record: &Val, field: &str) -> TCM<TVal> {
+    let mut ty = ty;
+    while let Val::DepRec(label, field_ty, rest) = ty {
+        if label == field {
+            return Ok(*field_ty);
+        }
+        ty = rest.instantiate(record.clone().project(label));
+    }
+    match ty {
+        Val::RowPoly(Record, mut fields) => {
+            (fields.remove(field)).ok_or_else(|| TCE::MissingVariant(Record, field.to_owned()))
+        }
+        ty => Err(TCE::NotRowType(Record, info, ty)),
+    }
+}
+
+/// Check a row type with an entry labelled by `label`, where `label` is a variable.
+fn check_label_row(
+    tcs: TCS,
+    info: Loc,
+    level: &Level,
+    kind: VarRec,
+    label: &Abs,
+    ty: &Abs,
+    rest: &Option<Box<Abs>>,
+) -> ValTCM {
+    let (label, tcs) = tcs.check(label, &Val::LabelType)?;
+    let (ty, tcs) = (tcs.check(ty, &Val::Type(level.clone()))).map_err(|e| e.wrap(info))?;
+    let (rest, tcs) = match rest {
+        Some(rest) => {
+            let empty = Val::RowKind(level.clone(), kind, Vec::new());
+            let expected_kind = Val::label_kind(label.ast.clone(), empty);
+            let (rest, tcs) = (tcs.check(rest, &expected_kind)).map_err(|e| e.wrap(info))?;
+            (rest.ast, tcs)
+        }
+        None => (Val::RowPoly(kind, Default::default()), tcs),
+    };
+    let row = Val::label_row(kind, label.ast, ty.ast, rest);
+    Ok((row.into_info(info), tcs))
+}
+
+/// Check `@l a`, where `l` is a variable, against a variant type with an entry labelled `l`.
+fn check_label_cons(tcs: TCS, info: Loc, cons: &Abs, a: &Abs, expected_type: &Val) -> ValTCM {
+    let label = match cons {
+        Abs::LabCons(_, label) => label,
+        _ => unreachable!(),
+    };
+    let (label, tcs) = tcs.check(label, &Val::LabelType)?;
+    let ty = label_entry(Variant, expected_type, &label.ast)
+        .ok_or_else(|| TCE::MissingVariant(Variant, label.ast.to_string()))?;
+    let (a, tcs) = tcs.check(a, ty).map_err(|e| e.wrap(info))?;
+    Ok((Val::label_cons(label.ast, a.ast).into_info(info), tcs))
+}
+
+/// The type of the entry labelled `label` in the row type `row`.
+fn label_entry<'a>(kind: VarRec, row: &'a Val, label: &Val) -> Option<&'a Val> {
+    match (row, label) {
+        (Val::RowPoly(row_kind, variants), Val::Label(label)) if *row_kind == kind => {
+            variants.get(label)
+        }
+        (Val::Neut(Neutral::Row(row_kind, variants, more)), _) if *row_kind == kind => {
+            match label {
+                Val::Label(label) if variants.contains_key(label) => variants.get(label),
+                _ => label_entry_neutral(kind, more, label),
+            }
+        }
+        (Val::Neut(neut), _) => label_entry_neutral(kind, neut, label),
+        _ => None,
+    }
+}
+
+fn label_entry_neutral<'a>(kind: VarRec, row: &'a Neutral, label: &Val) -> Option<&'a Val> {
+    match (row, label) {
+        (Neutral::LabRow(row_kind, row_label, ty, _), Val::Neut(label))
+            if *row_kind == kind && same_label(row_label, label) =>
+        {
+            Some(&**ty)
+        }
+        (Neutral::LabRow(row_kind, _, _, rest), _) if *row_kind == kind => {
+            label_entry(kind, rest, label)
+        }
+        _ => None,
+    }
+}
+
+/// Label variables are compared by their unique ids,
+/// the de-bruijn indices attached to them are irrelevant.
+fn same_label(a: &Neutral, b: &Neutral) -> bool {
+    match (a, b) {
+        (Neutral::Axi(a), Neutral::Axi(b)) => a.unique_id() == b.unique_id(),
+        (a, b) => a == b,
+    }
+}
+
+/// The name of a label variable, for error messages.
+fn label_name(label: &Abs) -> String {
+    match label {
+        Abs::Var(ident, ..) => ident.text.clone(),
+        label => label.to_string(),
     }
 }
 
+/// Labels of a closed variant type that are not handled by
+/// a case-split chain ending with `whatever`.
+/// A chain ending with a default clause (or a function) handles them all.
+fn uncovered_labels(mut case_or: &Abs, variants: &Variants) -> Vec<String> {
+    let mut covered = BTreeSet::new();
+    loop {
+        match case_or {
+            Abs::CaseOr(label, _, _, _, or) => {
+                covered.insert(&label.text);
+                case_or = &**or;
+            }
+            Abs::Whatever(..) => break,
+            // The rest of the labels are handled by a function.
+            _ => return Vec::new(),
+        }
+    }
+    (variants.keys())
+        .filter(|label| !covered.contains(label))
+        .cloned()
+        .collect()
+}
+
+/// Elimination forms are checked by inference, since their types may already
+/// be a hidden sigma type.
+fn is_elimination(expr: &Abs) -> bool {
+    use Abs::*;
+    match expr {
+        App(_, f, ..) => !matches!(**f, Cons(..)),
+        Var(..) | Ref(..) | Proj(..) | Fst(..) | Snd(..) | Match(..) => true,
+        _ => false,
+    }
+}
+
+/// Implicit arguments are erased, so the value of an implicit lambda
+/// must not refer to its parameter.
+fn uses_implicit(implicit: UID, body: Val) -> bool {
+    let result = body.try_map_leaves(0, &mut |neut, _| match neut {
+        Neutral::Axi(Axiom::Implicit(uid)) if uid == implicit => Err(()),
+        neut => Ok(Val::Neut(neut)),
+    });
+    result.is_err()
+}
+
+/// Check the definition of a `let` and bring it into scope.
+fn define_local(tcs: TCS, name: &Ident, ty: &Option<Box<Abs>>, def: &Abs) -> TCM {
+    let (def, ty, mut tcs) = match ty {
+        Some(ty) => {
+            let (ty, tcs) = tcs.check(ty, &TYPE_OMEGA)?;
+            let (def, tcs) = tcs.check(def, &ty.ast)?;
+            (def, ty, tcs)
+        }
+        None => {
+            let (ty, tcs) = tcs.infer(def)?;
+            let (def, tcs) = tcs.evaluate(def.clone());
+            (def, ty, tcs)
+        }
+    };
+    tcs.push_definition(ty.ast.into_info(name.loc), def);
+    Ok(tcs)
+}
+
 fn check_fallback(tcs: TCS, expr: &Abs, expected_type: &Val) -> ValTCM {
-    let (inferred, tcs) = tcs.infer(expr)?;
+    let (inferred, mut tcs) = tcs.infer(expr)?;
+    let (mut inferred_ty, loc) = (inferred.ast, inferred.loc);
+    // Insert metas for the implicit arguments, like `Nothing` in `Nothing : Maybe A`.
+    if !matches!(expected_type, Val::Dt(Pi, Plicit::Im, ..)) {
+        while let Val::Dt(Pi, Plicit::Im, _, closure) = inferred_ty {
+            let inserted_meta = tcs.fresh_meta();
+            inferred_ty = closure.instantiate(inserted_meta);
+        }
+    }
     Ok(tcs
-        .subtype(&inferred.ast, expected_type)
-        .map_err(|e| e.wrap(inferred.loc))?
+        .subtype(&inferred_ty, expected_type)
+        .map_err(|e| e.wrap(loc))?
         .evaluate(expr.clone()))
 }
 
+/// The extension of a record literal must lack the fields of the literal,
+/// so the type of the extension is inferred to see the fields it may have.
+fn check_record_ext(
+    tcs: TCS,
+    fields: &[LabAbs],
+    nice_fields: &Fields,
+    more: &Abs,
+    more_type: &Val,
+) -> ValTCM {
+    let (more_ty, tcs) = tcs.infer(more)?;
+    let (more_fields, more_rest) = match &more_ty.ast {
+        Val::RowPoly(Record, more_fields) => (more_fields, None),
+        Val::Neut(Neutral::Row(Record, more_fields, rest)) => (more_fields, Some(&**rest)),
+        Val::Neut(rest) => (&Variants::new(), Some(rest)),
+        ast => return Err(TCE::NotRecVal(more_ty.loc, ast.clone())),
+    };
+    let labels = fields.iter().map(|field| &field.label);
+    for label in labels.filter(|label| nice_fields.contains_key(&label.text)) {
+        if more_fields.contains_key(&label.text) {
+            return Err(TCE::duplicate_field(label.clone()));
+        }
+        if more_rest.is_some_and(|rest| !row_lacks(&tcs, rest, &label.text)) {
+            return Err(TCE::MayContainLabel(Record, label.text.clone()));
+        }
+    }
+    let tcs = tcs
+        .subtype(&more_ty.ast, more_type)
+        .map_err(|e| e.wrap(more_ty.loc))?;
+    Ok(tcs.evaluate(more.clone()))
+}
+
 fn check_fields_no_more(
     info: Loc,
     nice_fields: Fields,
@@ -345,7 +880,7 @@ $$
 fn check_row_polymorphic_type(
     mut tcs: TCS,
     info: Loc,
-    level: Level,
+    level: &Level,
     kind: VarRec,
     variants: &[LabAbs],
     ext: &Option<Box<Abs>>,
@@ -354,7 +889,7 @@ fn check_row_polymorphic_type(
     let mut out_variants = Variants::new();
     for labelled in variants {
         let (val, new_tcs) = tcs
-            .check(&labelled.expr, &Val::Type(level))
+            .check(&labelled.expr, &Val::Type(level.clone()))
             .map_err(|e| e.wrap(info))?;
         tcs = new_tcs;
         let label = &labelled.label.text;
@@ -369,7 +904,7 @@ fn check_row_polymorphic_type(
         None => Ok((Val::RowPoly(kind, out_variants).into_info(info), tcs)),
         Some(ext) => {
             let known_labels = out_variants.keys().chain(labels.iter()).cloned().collect();
-            let expected_kind = Val::RowKind(Default::default(), kind, known_labels);
+            let expected_kind = Val::RowKind(level.clone(), kind, known_labels);
             let (ext, new_tcs) = tcs
                 .check(&**ext, &expected_kind)
                 .map_err(|e| e.wrap(info))?;
@@ -442,14 +977,88 @@ $$
 }{
   \Ginfer{a .n}{\cA}
 }
+\\\\ \space \\\\
+\cfrac{
+  \Ginfer{a}{\record{n : \cA, \ctyLab}} \quad
+  \Gtyck{b}{\cA}{\beta}
+}{
+  \Ginfer{\\{| a\ \textbf{with}\ n = b |\\}}{\record{n : \cA, \ctyLab}}
+} \quad
+\cfrac{
+  \Ginfer{a}{\record{n : \cA, \ctyLab}}
+}{
+  \Ginfer{a \setminus n}{\record{\ctyLab}}
+} \quad
+\cfrac{
+  \Ginfer{a}{\record{n : \cA, \ctyLab}} \quad
+  m \notin \ctyLab
+}{
+  \Ginfer{a [n \Rightarrow m]}{\record{m : \cA, \ctyLab}}
+}
+\\\\ \space \\\\
+\cfrac{
+  \Ginfer{a}{\cA} \quad
+  \infer{\Gamma, \xx : \cA = \eval{a}}{b}{\cB}
+}{
+  \Ginfer{\textbf{let}\ \xx = a\ \textbf{in}\ b}{\cB}
+}
+\\\\ \space \\\\
+\cfrac{
+  \Ginfer{a}{\cA} \quad
+  \tyck{\Gamma, \xx : \cA}{P}{\ty}{\cB} \quad
+  \Gtyck{f}{\piTy{\xx : \cA . \cB}}{\beta}
+}{
+  \Ginfer{\textbf{match}\ a\ \textbf{as}\ \xx\ \textbf{return}\ P\ \textbf{with}\ f}{\cB [\xx := \eval{a}]}
+}
 $$
 */
 fn infer(tcs: TCS, value: &Abs) -> ValTCM {
     use Abs::*;
     let info = value.loc();
     match value {
-        Type(_, level) => Ok((Val::Type(*level + 1).into_info(info), tcs)),
-        RowKind(..) => Ok((Val::Type(From::from(1u32)).into_info(info), tcs)),
+        Type(_, level) => Ok((Val::Type(level.clone() + 1).into_info(info), tcs)),
+        RowKind(..) | LabKind(..) => Ok((Val::Type(From::from(1u32)).into_info(info), tcs)),
+        DepRec(_, fields) => {
+            let (_, level, tcs) = check_dependent_record(tcs, info, fields, &TYPE_OMEGA)?;
+            Ok((Val::Type(level).into_info(info), tcs))
+        }
+        Fix(_, name, uid, body) => {
+            // The level of the body is inferred with the bound type at the lowest level,
+            // then the whole type is checked at that level.
+            let mut tcs = tcs;
+            let lowest = Val::Type(Default::default());
+            tcs.local_gamma.push(lowest.into_info(name.loc));
+            tcs.local_env.push(Val::postulate(*uid).into_info(name.loc));
+            let (ty, mut tcs) = tcs.infer(body).map_err(|e| e.wrap(info))?;
+            tcs.pop_local();
+            match ty.ast {
+                ty @ Val::Type(..) => {
+                    let (_, tcs) = tcs.check(value, &ty)?;
+                    Ok((ty.into_info(info), tcs))
+                }
+                ty => Err(TCE::NotUniverseVal(info, ty)),
+            }
+        }
+        Unfold(_, expr) => {
+            let (ty, tcs) = tcs.infer(expr).map_err(|e| e.wrap(info))?;
+            match ty.ast {
+                ast @ Val::Fix(..) => Ok((ast.unfold().into_info(info), tcs)),
+                ast => Err(TCE::NotFix(ty.loc, ast)),
+            }
+        }
+        LabelType(..) => Ok((Val::Type(Default::default()).into_info(info), tcs)),
+        Label(..) => Ok((Val::LabelType.into_info(info), tcs)),
+        NatType(..) => Ok((Val::Type(Default::default()).into_info(info), tcs)),
+        Nat(..) => Ok((Val::NatType.into_info(info), tcs)),
+        NatOp(_, op, a, b) => {
+            let (_, tcs) = tcs.check(a, &Val::NatType).map_err(|e| e.wrap(info))?;
+            let (_, tcs) = tcs.check(b, &Val::NatType).map_err(|e| e.wrap(info))?;
+            let ty = match op {
+                NatOpKind::Add | NatOpKind::Sub | NatOpKind::Mul => Val::NatType,
+                NatOpKind::Lt | NatOpKind::Le => Val::bool_type(),
+            };
+            Ok((ty.into_info(info), tcs))
+        }
         RowPoly(_, kind, variants, more) => {
             let mut labels = Vec::with_capacity(variants.len());
             let mut tcs = tcs;
@@ -465,18 +1074,17 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
             match more {
                 None => Ok((Val::Type(kind_level).into_info(info), tcs)),
                 Some(more) => {
-                    let expected = Val::RowKind(kind_level, *kind, labels);
+                    let expected = Val::RowKind(kind_level.clone(), *kind, labels);
                     let (_, tcs) = tcs.check(&**more, &expected)?;
                     Ok((Val::Type(kind_level).into_info(info), tcs))
                 }
             }
         }
         Rec(_, fields, ext) => {
-            let (ext, tcs) = ext
-                .as_ref()
-                .map(|abs| tcs.infer(&**abs).map_err(|e| e.wrap(info)))
-                .transpose()?
-                .unwrap_or_default();
+            let (ext, tcs) = match ext {
+                Some(abs) => tcs.infer(abs).map_err(|e| e.wrap(info))?,
+                None => (Val::record_type(Default::default()).into_info(info), tcs),
+            };
             let (mut ext_fields, more) = match ext.ast {
                 Val::RowPoly(Record, fields) => (fields, None),
                 Val::Neut(Neutral::Row(Record, fields, more)) => (fields, Some(*more)),
@@ -487,6 +1095,13 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
                 if ext_fields.contains_key(&field.label.text) {
                     return Err(TCE::duplicate_field(field.label.clone()));
                 }
+                let label = &field.label.text;
+                if more
+                    .as_ref()
+                    .is_some_and(|more| !row_lacks(&tcs, more, label))
+                {
+                    return Err(TCE::MayContainLabel(Record, field.label.text.clone()));
+                }
                 let (inferred, new_tcs) = tcs.infer(&field.expr).map_err(|e| e.wrap(info))?;
                 tcs = new_tcs;
                 ext_fields.insert(field.label.text.clone(), inferred.ast);
@@ -501,14 +1116,14 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
             let local = tcs.local_type(*dbi).ast.clone().attach_dbi(*dbi);
             Ok((local.into_info(info), tcs))
         }
-        Lam(..) => {
+        Lam(_, _, _, plicit, _) => {
             let mut tcs = tcs;
             let param_meta = tcs.fresh_meta();
             let ret_meta = tcs.fresh_meta();
             // let mocked = Val::postulate(*uid);
             // tcs.local_gamma.push(param_meta.clone().into_info(info));
             // tcs.local_env.push(mocked.clone().into_info(info));
-            let pi = Val::pi(Plicit::Ex, param_meta, Closure::plain(ret_meta));
+            let pi = Val::pi(*plicit, param_meta, Closure::plain(ret_meta));
             let (_, tcs) = tcs.check(value, &pi)?;
             // tcs.pop_local();
             Ok((pi.into_info(info), tcs))
@@ -517,7 +1132,12 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
             let (expr, tcs) = tcs.infer(&**expr).map_err(|e| e.wrap(info))?;
             Ok((expr.map_ast(|ast| ast.lift(*levels)), tcs))
         }
-        Ref(_, dbi) => Ok((tcs.glob_type(*dbi).ast.clone().into_info(info), tcs)),
+        Ref(_, dbi) => {
+            let mut tcs = tcs;
+            let ty = tcs.glob_type(*dbi).ast.clone();
+            let ty = tcs.instantiate_levels(ty);
+            Ok((ty.into_info(info), tcs))
+        }
         Pair(_, fst, snd) => {
             let (fst_ty, tcs) = tcs.infer(&**fst).map_err(|e| e.wrap(info))?;
             let (snd_ty, tcs) = tcs.infer(&**snd).map_err(|e| e.wrap(info))?;
@@ -527,11 +1147,21 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
         Fst(_, pair) => {
             let (pair_ty, tcs) = tcs.infer(&**pair).map_err(|e| e.wrap(info))?;
             match pair_ty.ast {
-                Val::Dt(Sigma, Plicit::Ex, param_type, ..) => Ok((param_type.into_info(info), tcs)),
+                Val::Dt(Sigma, _, param_type, ..) => Ok((param_type.into_info(info), tcs)),
                 ast => Err(TCE::NotSigma(pair_ty.loc, ast)),
             }
         }
-        Proj(_, record, field) => {
+        Proj(_, record, field, local) => {
+            let tcs = match local {
+                Some(local) => {
+                    let (local_ty, tcs) = tcs.infer(local)?;
+                    if local_ty.ast == Val::LabelType {
+                        return Err(TCE::LabelVarProjection(field.clone()));
+                    }
+                    tcs
+                }
+                None => tcs,
+            };
             let (record_ty, tcs) = tcs.infer(&**record).map_err(|e| e.wrap(info))?;
             match record_ty.ast {
                 Val::Neut(Neutral::Row(Record, mut fields, ..))
@@ -539,13 +1169,26 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
                     .remove(&field.text)
                     .map(|ty| (ty.into_info(info), tcs))
                     .ok_or_else(|| TCE::MissingVariant(Record, field.text.clone())),
+                ast @ Val::DepRec(..) => {
+                    let (record, tcs) = tcs.evaluate(*record.clone());
+                    let ty = dependent_field_type(info, ast, &record.ast, &field.text)?;
+                    Ok((ty.into_info(info), tcs))
+                }
                 ast => Err(TCE::NotRowType(Record, record_ty.loc, ast)),
             }
         }
+        LabProj(_, record, label) => {
+            let (label, tcs) = tcs.check(label, &Val::LabelType)?;
+            let (record_ty, tcs) = tcs.infer(record).map_err(|e| e.wrap(info))?;
+            match label_entry(Record, &record_ty.ast, &label.ast) {
+                Some(ty) => Ok((ty.clone().into_info(info), tcs)),
+                None => Err(TCE::MissingVariant(Record, label.ast.to_string())),
+            }
+        }
         Snd(_, pair) => {
             let (pair_ty, tcs) = tcs.infer(&**pair).map_err(|e| e.wrap(info))?;
             match pair_ty.ast {
-                Val::Dt(Sigma, Plicit::Ex, _, closure) => {
+                Val::Dt(Sigma, _, _, closure) => {
                     // Since we can infer the type of `pair`, it has to be well-typed
                     let (pair_compiled, tcs) = tcs.evaluate(*pair.clone());
                     let fst = pair_compiled.ast.first();
@@ -554,41 +1197,375 @@ fn infer(tcs: TCS, value: &Abs) -> ValTCM {
                 ast => Err(TCE::NotSigma(pair_ty.loc, ast)),
             }
         }
-        App(_, f, _app_plicit, a) => match &**f {
-            Cons(variant_info) => {
+        App(_, f, app_plicit, a) => match &**f {
+            Cons(variant_info) if *app_plicit == Plicit::Ex => {
                 let (a, tcs) = tcs.infer(a).map_err(|e| e.wrap(info))?;
                 let mut variant = Variants::default();
                 variant.insert(variant_info.text[1..].to_owned(), a.ast);
                 Ok((Val::variant_type(variant).into_info(info), tcs))
             }
-            Whatever(whatever_info) => {
+            LabCons(_, label) if *app_plicit == Plicit::Ex => {
+                let (label, tcs) = tcs.check(label, &Val::LabelType)?;
+                let (a, tcs) = tcs.infer(a).map_err(|e| e.wrap(info))?;
+                let empty = Val::variant_type(Default::default());
+                let variant = Val::label_row(Variant, label.ast, a.ast, empty);
+                Ok((variant.into_info(info), tcs))
+            }
+            Whatever(whatever_info) if *app_plicit == Plicit::Ex => {
                 let empty = Val::Lam(Closure::default());
                 let (_, mut tcs) = tcs.check(a, &empty).map_err(|e| e.wrap(info))?;
                 Ok((tcs.fresh_meta().into_info(*whatever_info), tcs))
             }
             f => {
                 let (f_ty, tcs) = tcs.infer(f).map_err(|e| e.wrap(info))?;
-                check_app_type(tcs, f, info, a, &f_ty.ast)
+                check_app_type(tcs, f, info, *app_plicit, a, &f_ty.ast)
             }
         },
+        Ascription(_, expr, ty) => {
+            let (ty, tcs) = tcs.check(ty, &TYPE_OMEGA).map_err(|e| e.wrap(info))?;
+            let (_, tcs) = tcs.check(expr, &ty.ast).map_err(|e| e.wrap(info))?;
+            Ok((ty.ast.into_info(info), tcs))
+        }
+        Let(_, name, _, ty, def, body) => {
+            let tcs = define_local(tcs, name, ty, def).map_err(|e| e.wrap(info))?;
+            let (body_ty, mut tcs) = tcs.infer(body).map_err(|e| e.wrap(info))?;
+            tcs.pop_local();
+            let body_ty = tcs.exit_definition(body_ty.ast);
+            Ok((body_ty.into_info(info), tcs))
+        }
+        Match(_, scrutinee, binding, uid, motive, cases) => {
+            let (scrutinee_ty, tcs) = tcs.infer(scrutinee).map_err(|e| e.wrap(info))?;
+            let (scrutinee, mut tcs) = tcs.evaluate((**scrutinee).clone());
+            tcs.local_gamma.push(scrutinee_ty.clone());
+            tcs.local_env
+                .push(Val::postulate(*uid).into_info(binding.loc));
+            let (motive, mut tcs) = tcs.check(motive, &TYPE_OMEGA).map_err(|e| e.wrap(info))?;
+            tcs.pop_local();
+            let local_motive = Motive::Local(*uid, &motive.ast);
+            let handled = Default::default();
+            let (_, tcs) = check_case_or(tcs, cases, &scrutinee_ty.ast, &handled, &local_motive)
+                .map_err(|e| e.wrap(info))?;
+            let ret_ty = tcs.exit_definition(motive.ast);
+            let ret_ty = substitute_local(ret_ty, *uid, &scrutinee.ast);
+            Ok((ret_ty.into_info(info), tcs))
+        }
+        Eq(_, a, b) => {
+            let (a_ty, tcs) = tcs.infer(a).map_err(|e| e.wrap(info))?;
+            let (_, tcs) = tcs.check(b, &a_ty.ast).map_err(|e| e.wrap(info))?;
+            Ok((Val::Type(a_ty.ast.level()).into_info(info), tcs))
+        }
+        J(_, proof, (end, end_uid), (eq, eq_uid), motive, refl) => {
+            let (proof_ty, tcs) = tcs.infer(proof).map_err(|e| e.wrap(info))?;
+            let (from, to) = match proof_ty.ast {
+                Val::Id(from, to) => (*from, *to),
+                ty => return Err(TCE::NotId(proof_ty.loc, ty)),
+            };
+            let (proof, mut tcs) = tcs.evaluate((**proof).clone());
+            // The type of the ends is solved by the usages in the motive, if any.
+            let end_ty = tcs.fresh_meta();
+            tcs.local_gamma.push(end_ty.into_info(end.loc));
+            tcs.local_env
+                .push(Val::postulate(*end_uid).into_info(end.loc));
+            let eq_ty = Val::id(from.clone(), Val::postulate(*end_uid));
+            tcs.local_gamma.push(eq_ty.into_info(eq.loc));
+            tcs.local_env
+                .push(Val::postulate(*eq_uid).into_info(eq.loc));
+            let (motive, mut tcs) = tcs.check(motive, &TYPE_OMEGA).map_err(|e| e.wrap(info))?;
+            tcs.pop_local();
+            tcs.pop_local();
+            let motive = tcs.exit_definition(tcs.exit_definition(motive.ast));
+            let motive_at = |end: &Val, eq: &Val| {
+                let motive = substitute_local(motive.clone(), *end_uid, end);
+                substitute_local(motive, *eq_uid, eq)
+            };
+            let refl_ty = motive_at(&from, &Val::Refl);
+            let (_, tcs) = tcs.check(refl, &refl_ty).map_err(|e| e.wrap(info))?;
+            Ok((motive_at(&to, &proof.ast).into_info(info), tcs))
+        }
+        NatElim(_, nat, (m, m_uid), motive, zero, (pred, pred_uid), (result, result_uid), step) => {
+            let (nat, mut tcs) = tcs.check(nat, &Val::NatType).map_err(|e| e.wrap(info))?;
+            tcs.local_gamma.push(Val::NatType.into_info(m.loc));
+            tcs.local_env.push(Val::postulate(*m_uid).into_info(m.loc));
+            let (motive, mut tcs) = tcs.check(motive, &TYPE_OMEGA).map_err(|e| e.wrap(info))?;
+            tcs.pop_local();
+            let motive = tcs.exit_definition(motive.ast);
+            let motive_at = |n: &Val| substitute_local(motive.clone(), *m_uid, n);
+            let zero_ty = motive_at(&Val::Nat(Default::default()));
+            let (_, mut tcs) = tcs.check(zero, &zero_ty).map_err(|e| e.wrap(info))?;
+            // The step is checked like a lambda of the predecessor and the result.
+            let pred_val = Val::postulate(*pred_uid);
+            tcs.local_gamma.push(Val::NatType.into_info(pred.loc));
+            tcs.local_env.push(pred_val.clone().into_info(pred.loc));
+            tcs.local_gamma
+                .push(motive_at(&pred_val).into_info(result.loc));
+            tcs.local_env
+                .push(Val::postulate(*result_uid).into_info(result.loc));
+            let step_ty = motive_at(&pred_val.plus(1u32.into()));
+            let (_, mut tcs) = tcs.check(step, &step_ty).map_err(|e| e.wrap(info))?;
+            tcs.pop_local();
+            tcs.pop_local();
+            Ok((motive_at(&nat.ast).into_info(info), tcs))
+        }
+        Update(_, record, fields) => {
+            let (record_ty, tcs) = tcs.infer(record).map_err(|e| e.wrap(info))?;
+            let (field_types, more) = record_type_fields(record_ty)?;
+            let mut updated = BTreeSet::new();
+            let mut tcs = tcs;
+            for field in fields {
+                let label = &field.label;
+                if !updated.insert(&label.text) {
+                    return Err(TCE::duplicate_field(label.clone()));
+                }
+                let ty = (field_types.get(&label.text))
+                    .ok_or_else(|| TCE::MissingVariant(Record, label.text.clone()))?;
+                let (_, new_tcs) = tcs.check(&field.expr, ty).map_err(|e| e.wrap(info))?;
+                tcs = new_tcs;
+            }
+            Ok((open_record_type(field_types, more).into_info(info), tcs))
+        }
+        Remove(_, record, field) => {
+            let (record_ty, tcs) = tcs.infer(record).map_err(|e| e.wrap(info))?;
+            let (mut field_types, more) = record_type_fields(record_ty)?;
+            field_types
+                .remove(&field.text)
+                .ok_or_else(|| TCE::MissingVariant(Record, field.text.clone()))?;
+            Ok((open_record_type(field_types, more).into_info(info), tcs))
+        }
+        Rename(_, record, from, to) => {
+            let (record_ty, tcs) = tcs.infer(record).map_err(|e| e.wrap(info))?;
+            let (mut field_types, more) = record_type_fields(record_ty)?;
+            let ty = (field_types.remove(&from.text))
+                .ok_or_else(|| TCE::MissingVariant(Record, from.text.clone()))?;
+            // The new name should be absent in the unknown part of the row as well.
+            let lacks = match &more {
+                Some(more) => row_lacks(&tcs, more, &to.text),
+                None => true,
+            };
+            if field_types.contains_key(&to.text) || !lacks {
+                return Err(TCE::duplicate_field(to.clone()));
+            }
+            field_types.insert(to.text.clone(), ty);
+            Ok((open_record_type(field_types, more).into_info(info), tcs))
+        }
+        RowConcat(..) | RowMap(..) | RowDiff(..) => {
+            let (level, tcs) = check_row_operator(tcs, value, None)?;
+            Ok((Val::Type(level).into_info(info), tcs))
+        }
         e => Err(TCE::CannotInfer(info, e.clone())),
     }
 }
 
+/// Known field types and the (optional) unknown rest of a record type.
+fn record_type_fields(record_ty: ValInfo) -> TCM<(Variants, Option<Neutral>)> {
+    match record_ty.ast {
+        Val::RowPoly(Record, fields) => Ok((fields, None)),
+        Val::Neut(Neutral::Row(Record, fields, more)) => Ok((fields, Some(*more))),
+        ast => Err(TCE::NotRowType(Record, record_ty.loc, ast)),
+    }
+}
+
+fn open_record_type(fields: Variants, more: Option<Neutral>) -> TVal {
+    match more {
+        None => Val::record_type(fields),
+        Some(more) => Val::record_type(fields).row_extend(Val::Neut(more)),
+    }
+}
+
+/// Whether the row variable `row` is known to lack the field `label`,
+/// that is, `label` is listed in the row kind of `row`.
+fn row_lacks(tcs: &TCS, row: &Neutral, label: &str) -> bool {
+    let row_kind = match row {
+        Neutral::Axi(axiom) => tcs.axiom_type(axiom).map(|ty| &ty.ast),
+        _ => None,
+    };
+    match row_kind.and_then(row_kind_labels) {
+        Some((_, Record, labels, _)) => labels.iter().any(|l| *l == label),
+        _ => false,
+    }
+}
+
+/// The level, the kind, the labels and the label variables lacked by a row kind.
+fn row_kind_labels(kind: &Val) -> Option<(&Level, VarRec, Vec<&String>, Vec<&Neutral>)> {
+    match kind {
+        Val::RowKind(level, kind, labels) => Some((level, *kind, labels.iter().collect(), vec![])),
+        Val::Neut(Neutral::LabKind(label, kind)) => {
+            let (level, kind, labels, mut vars) = row_kind_labels(kind)?;
+            vars.push(&**label);
+            Some((level, kind, labels, vars))
+        }
+        _ => None,
+    }
+}
+
+/// Check a row operator, the operands are checked against `level` if given.
+/// Returns the level of the resulting row.
+fn check_row_operator(tcs: TCS, expr: &Abs, level: Option<&Level>) -> TCM<(Level, TCS)> {
+    use Abs::*;
+    let info = expr.loc();
+    match expr {
+        RowConcat(_, left, right) => {
+            let (left, left_kind, left_level, tcs) = row_operand(tcs, left, level)?;
+            let (right, right_kind, right_level, tcs) = row_operand(tcs, right, level)?;
+            if left_kind != right_kind {
+                return Err(TCE::NotRowType(left_kind, right.loc, right.ast));
+            }
+            check_disjoint_rows(&tcs, info, left_kind, &left.ast, &right.ast)?;
+            Ok((left_level.max(right_level), tcs))
+        }
+        RowMap(_, function, row) => {
+            let (function_ty, tcs) = tcs.infer(function).map_err(|e| e.wrap(info))?;
+            let (param_ty, ret_ty) = match function_ty.ast {
+                Val::Dt(Pi, Plicit::Ex, param_ty, ret_ty) if matches!(*param_ty, Val::Type(..)) => {
+                    (param_ty, ret_ty)
+                }
+                ty => return Err(TCE::NotPi(function_ty.loc, ty)),
+            };
+            let (row, tcs) = tcs.check(row, &param_ty).map_err(|e| e.wrap(info))?;
+            if row_kind_of(&tcs, &row.ast).is_none() {
+                return Err(TCE::NotRowType(Record, row.loc, row.ast));
+            }
+            match ret_ty.instantiate(Val::fresh_axiom()) {
+                Val::Type(level) => Ok((level, tcs)),
+                ty => Err(TCE::NotUniverseVal(function_ty.loc, ty)),
+            }
+        }
+        RowDiff(_, row, labels) => {
+            let (row, kind, level, tcs) = row_operand(tcs, row, level)?;
+            // The labels should (at least possibly) be present.
+            match (labels.iter()).find(|label| row_type_lacks(&tcs, &row.ast, &label.text)) {
+                Some(label) => Err(TCE::MissingVariant(kind, label.text.clone())),
+                None => Ok((level, tcs)),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Check (or infer the level of) an operand of the row operators, and evaluate it.
+fn row_operand(tcs: TCS, row: &Abs, level: Option<&Level>) -> TCM<(ValInfo, VarRec, Level, TCS)> {
+    let (row, level, tcs) = match level {
+        Some(level) => {
+            let (row, tcs) = tcs.check(row, &Val::Type(level.clone()))?;
+            (row, level.clone(), tcs)
+        }
+        None => {
+            let (ty, tcs) = tcs.infer(row)?;
+            let level = match &ty.ast {
+                Val::Type(level) => level.clone(),
+                kind => match row_kind_labels(kind) {
+                    Some((level, ..)) => level.clone(),
+                    None => return Err(TCE::NotUniverseVal(ty.loc, ty.ast)),
+                },
+            };
+            let (row, tcs) = tcs.evaluate(row.clone());
+            (row, level, tcs)
+        }
+    };
+    match row_kind_of(&tcs, &row.ast) {
+        Some(kind) => Ok((row, kind, level, tcs)),
+        None => Err(TCE::NotRowType(Record, row.loc, row.ast)),
+    }
+}
+
+/// The kind of a row type, or `None` if it's not a row type.
+fn row_kind_of(tcs: &TCS, row: &Val) -> Option<VarRec> {
+    match row {
+        Val::RowPoly(kind, ..) => Some(*kind),
+        Val::Neut(neut) => neutral_row_kind_of(tcs, neut),
+        _ => None,
+    }
+}
+
+fn neutral_row_kind_of(tcs: &TCS, row: &Neutral) -> Option<VarRec> {
+    match row {
+        Neutral::Row(kind, ..) | Neutral::LabRow(kind, ..) => Some(*kind),
+        Neutral::RowMap(_, row) | Neutral::RowDiff(row, _) => neutral_row_kind_of(tcs, row),
+        Neutral::Axi(axiom) => {
+            let (_, kind, ..) = row_kind_labels(&tcs.axiom_type(axiom)?.ast)?;
+            Some(kind)
+        }
+        _ => None,
+    }
+}
+
+/// Whether the row type `row` is known to lack the label `label`.
+fn row_type_lacks(tcs: &TCS, row: &Val, label: &str) -> bool {
+    match row {
+        Val::RowPoly(_, variants) => !variants.contains_key(label),
+        Val::Neut(Neutral::Row(_, variants, more)) => {
+            !variants.contains_key(label) && neutral_row_lacks(tcs, more, label)
+        }
+        Val::Neut(neut) => neutral_row_lacks(tcs, neut, label),
+        _ => false,
+    }
+}
+
+fn neutral_row_lacks(tcs: &TCS, row: &Neutral, label: &str) -> bool {
+    match row {
+        Neutral::Axi(axiom) => (tcs.axiom_type(axiom))
+            .and_then(|ty| row_kind_labels(&ty.ast))
+            .is_some_and(|(_, _, labels, _)| labels.iter().any(|l| *l == label)),
+        Neutral::RowMap(_, row) => neutral_row_lacks(tcs, row, label),
+        Neutral::RowDiff(row, labels) => {
+            labels.iter().any(|l| l == label) || neutral_row_lacks(tcs, row, label)
+        }
+        _ => false,
+    }
+}
+
+/// The known labels of a row type, and whether it has an unknown part.
+fn row_type_labels(row: &Val) -> (Vec<&String>, bool) {
+    match row {
+        Val::RowPoly(_, variants) => (variants.keys().collect(), false),
+        Val::Neut(Neutral::Row(_, variants, _)) => (variants.keys().collect(), true),
+        _ => (vec![], true),
+    }
+}
+
+/// Concatenated rows should not share any label.
+/// When both rows have unknown parts, they may always overlap.
+fn check_disjoint_rows(tcs: &TCS, info: Loc, kind: VarRec, left: &Val, right: &Val) -> TCM<()> {
+    let (left_labels, left_open) = row_type_labels(left);
+    let (right_labels, right_open) = row_type_labels(right);
+    if left_open && right_open {
+        return Err(TCE::OverlappingRows(info, left.clone(), right.clone()));
+    }
+    let check_labels = |labels: Vec<&String>, labels_known: &[&String], other: &Val| {
+        for label in labels {
+            if labels_known.contains(&label) {
+                return Err(TCE::OverlappingVariant(info, label.clone()));
+            } else if !row_type_lacks(tcs, other, label) {
+                return Err(TCE::MayContainLabel(kind, label.clone()));
+            }
+        }
+        Ok(())
+    };
+    check_labels(left_labels.clone(), &right_labels, right)?;
+    check_labels(right_labels, &left_labels, left)
+}
+
 /// Recursive function to insert meta for implicit argument
-fn check_app_type(tcs: TCS, f: &Abs, info: Loc, a: &Abs, pi_ty: &Val) -> ValTCM {
-    match pi_ty {
-        Val::Dt(Pi, Plicit::Ex, param_type, closure) => {
+fn check_app_type(
+    tcs: TCS,
+    f: &Abs,
+    info: Loc,
+    app_plicit: Plicit,
+    a: &Abs,
+    pi_ty: &Val,
+) -> ValTCM {
+    match (app_plicit, pi_ty) {
+        (_, Val::Dt(Pi, plicit, param_type, closure)) if *plicit == app_plicit => {
             let (new_a, tcs) = tcs.check(&a, &*param_type).map_err(|e| e.wrap(info))?;
             Ok((closure.instantiate_cloned(new_a.ast).into_info(info), tcs))
         }
-        Val::Dt(Pi, Plicit::Im, _param_type, closure) => {
+        (Plicit::Ex, Val::Dt(Pi, Plicit::Im, _param_type, closure)) => {
             let mut tcs = tcs;
             let inserted_meta = tcs.fresh_meta();
             let new_closure = closure.instantiate_cloned(inserted_meta);
-            check_app_type(tcs, f, info, a, &new_closure)
+            check_app_type(tcs, f, info, app_plicit, a, &new_closure)
         }
-        other => Err(TCE::NotPi(info, other.clone())),
+        (Plicit::Im, other) => Err(TCE::NotImplicitPi(info, other.clone())),
+        (Plicit::Ex, other) => Err(TCE::NotPi(info, other.clone())),
     }
 }
 
@@ -665,7 +1642,26 @@ $$
 fn subtype(tcs: TCS, sub: &Val, sup: &Val) -> TCM {
     use Val::*;
     match (sub, sup) {
-        (RowKind(sub_l, ..), Type(sup_l)) | (Type(sub_l), Type(sup_l)) if sub_l <= sup_l => Ok(tcs),
+        (RowKind(sub_l, ..), Type(sup_l)) | (Type(sub_l), Type(sup_l)) => {
+            tcs.subtype_level(sub_l, sup_l)
+        }
+        (Neut(Neutral::LabKind(_, sub)), Type(..)) => tcs.subtype(sub, sup),
+        // A row lacking more labels can be used where fewer labels are lacked.
+        (sub, sup) if row_kind_labels(sub).is_some() && row_kind_labels(sup).is_some() => {
+            let (sub_l, sub_kind, sub_labels, sub_vars) = row_kind_labels(sub).unwrap();
+            let (sup_l, sup_kind, sup_labels, sup_vars) = row_kind_labels(sup).unwrap();
+            if sub_kind != sup_kind {
+                return Err(TCE::NotSubtype(sub.clone(), sup.clone()));
+            }
+            if let Some(label) = sup_labels.iter().find(|label| !sub_labels.contains(label)) {
+                return Err(TCE::MayContainLabel(sub_kind, label.to_string()));
+            }
+            let lacks = |var: &&Neutral| sub_vars.iter().any(|sub| same_label(sub, var));
+            match sup_vars.iter().find(|var| !lacks(var)) {
+                Some(var) => Err(TCE::MayContainLabel(sub_kind, var.to_string())),
+                None => tcs.subtype_level(sub_l, sup_l),
+            }
+        }
         (RowPoly(Record, sub_vs), RowPoly(Record, sup_vs)) => {
             tcs.unify_variants(Record, sup_vs, sub_vs)
         }
@@ -683,6 +1679,14 @@ fn subtype(tcs: TCS, sub: &Val, sup: &Val) -> TCM {
             // Return value covariance
             tcs.subtype(&a, &b)
         }
+        (Neut(neut), sup) if tcs.unfold_meta(neut).is_some() => {
+            let solution = tcs.unfold_meta(neut).unwrap();
+            tcs.subtype(&solution, sup)
+        }
+        (sub, Neut(neut)) if tcs.unfold_meta(neut).is_some() => {
+            let solution = tcs.unfold_meta(neut).unwrap();
+            tcs.subtype(sub, &solution)
+        }
         (e, t) => tcs.unify(e, t),
     }
 }
@@ -691,12 +1695,14 @@ fn subtype(tcs: TCS, sub: &Val, sup: &Val) -> TCM {
 impl TCS {
     #[inline]
     pub fn check(self, expr: &Abs, expected_type: &Val) -> ValTCM {
-        check(self, expr, expected_type)
+        let (term, tcs) = check(self, expr, expected_type)?;
+        Ok((term, tcs.locate_constraints(expr.loc())))
     }
 
     #[inline]
     pub fn infer(self, value: &Abs) -> ValTCM {
-        infer(self, value)
+        let (ty, tcs) = infer(self, value)?;
+        Ok((ty, tcs.locate_constraints(value.loc())))
     }
 
     #[inline]
@@ -56,3 +56,28 @@ $$
 $$
 */
 mod expr;
+/**
+Compilation of pattern-matching lambdas to nested case-splits.
+$$
+\lambda\ \texttt{case}\ \\{ \overline{p_i \Rightarrow a_i} \\}
+$$
+*/
+mod pattern;
+/**
+Strict positivity checking of recursive types.
+$$
+\texttt{Fix}\ X.\ A,
+\quad
+X \notin \textsf{FV}(B)\ \text{for every}\ B \rightarrow C\ \text{in}\ A
+$$
+*/
+mod positivity;
+/**
+Size-change termination checking on the call graph of global definitions.
+$$
+f(\overline{x}) \rightarrow g(\overline{a}),
+\quad
+a_j < x_i
+$$
+*/
+mod termination;
@@ -30,13 +30,48 @@ pub enum TCE {
     NotEmpty(Loc, Val),
     NotRecVal(Loc, Val),
     NotUniverseVal(Loc, Val),
+    /// An implicit argument or lambda is given, but the type is not an implicit pi.
+    NotImplicitPi(Loc, TVal),
+    /// A `fold` or `unfold` on something that is not of a recursive type.
+    NotFix(Loc, TVal),
+    /// A `J` on something that is not of an identity type,
+    /// or a `refl` checked against something that is not an identity type.
+    NotId(Loc, TVal),
 
     // == Elaboration ==
     TypeNotInGamma(Loc),
     OverlappingVariant(Loc, String),
     DuplicateField(Loc, String),
     UnexpectedVariant(Loc, String),
+    /// Implicit arguments (like `{A}`) are only allowed in applications.
+    MisplacedImplicit(Loc),
+    /// Implicit arguments are erased, so the value of an implicit lambda
+    /// cannot depend on the parameter.
+    RelevantImplicit(Ident),
     MissingVariant(VarRec, String),
+    /// A data type declaration with indices, like `data Vec (A : Type) : Nat -> Type`.
+    IndexedData(Ident),
+    /// A label variable written as a field name, like `r.l` instead of `r.[l]`.
+    LabelVarProjection(Ident),
+    /// A row whose kind does not lack the label is used where the label
+    /// is expected to be absent, so the extension might overlap.
+    MayContainLabel(VarRec, String),
+    /// The label variable of an extension may be equal to a label
+    /// that the rest of the row has.
+    MayEqualLabel(VarRec, String, String),
+    /// Both rows of a concatenation have unknown parts, which may overlap.
+    OverlappingRows(Loc, Val, Val),
+    /// A case-split chain ending with `whatever`, or a pattern-matching lambda,
+    /// does not handle these labels.
+    MissingClauses(Loc, Vec<String>),
+    /// A clause of a pattern-matching lambda that is never reached,
+    /// reported as a warning.
+    RedundantClause(Loc),
+    /// A clause whose label is not in the closed variant type being split,
+    /// reported as a warning.
+    UnreachableClause(Loc, String),
+    /// A clause whose label is handled by a clause above, reported as a warning.
+    DuplicateClause(Loc, String),
     /// Maximum `DBI` vs. Requested `DBI`
     DbiOverflow(DBI, DBI),
     /// Expected the first level to be smaller than second.
@@ -51,13 +86,41 @@ pub enum TCE {
     /// hide the definition at the second `Loc`.
     ReDefine(Loc, Loc),
 
+    // == Termination ==
+    /// The definition at the `Loc` may not terminate,
+    /// because of the non-decreasing call cycle through the named definitions.
+    NonTerminating(Loc, Vec<String>),
+
+    // == Positivity ==
+    /// The variable of a recursive type occurs in the parameter type of a function,
+    /// or as an argument of a neutral type.
+    NonPositive(Ident),
+
+    // == Modules ==
+    /// No file in the search path corresponds to the module path.
+    ModuleNotFound(Ident),
+    /// The module file cannot be read or parsed.
+    ModuleUnreadable(Ident, String),
+    /// The module is (indirectly) importing itself,
+    /// the modules being imported (from the outermost one) are recorded.
+    CyclicImport(Ident, Vec<String>),
+    /// The imported module exports a name already imported from another module.
+    AmbiguousImport(Ident, String, String),
+
     // == "Meta"s ==
     /// Recursive metas are disallowed.
     MetaRecursion(MI),
-    /// Meta solution should be passed with bound variables only.
-    MetaWithNonVar(Loc),
+    /// Applied metas can only be solved when the arguments are
+    /// distinct bound variables, the `Val` is the offending argument.
+    MetaWithNonVar(MI, Val),
+    /// The anticipated solution of the meta refers to
+    /// a variable (the `Val`) out of its scope.
+    MetaScopeEscape(MI, Val),
     /// Unsolved metas are reported as errors.
     MetaUnsolved(MI),
+    /// A postponed equation is still stuck at the end of the declaration,
+    /// printed with the names of the local variables.
+    UnsolvedConstraint(Loc, String, String),
 }
 
 impl TCE {
@@ -86,6 +149,22 @@ impl Display for TCE {
                 "Expected a pi type expression (function), got: `{}` at {}.",
                 val, id
             ),
+            TCE::NotImplicitPi(id, val) => write!(
+                f,
+                "Expected an implicit pi type expression, got: `{}` at {}.",
+                val, id
+            ),
+            TCE::MisplacedImplicit(id) => write!(
+                f,
+                "Implicit argument at {} is not an argument of an application.",
+                id
+            ),
+            TCE::RelevantImplicit(param) => write!(
+                f,
+                "Implicit parameter `{}` at {} is used in the value of the lambda, \
+                 but implicit arguments are erased.",
+                param.text, param.loc
+            ),
             TCE::CannotUnify(val1, val2) => write!(f, "Cannot unify `{}` with `{}`.", val1, val2),
             TCE::NotSubtype(sub, sup) => {
                 write!(f, "Expected `{}` to be the subtype of `{}`.", sub, sup)
@@ -109,6 +188,16 @@ impl Display for TCE {
                 "Expected a variant type expression, got: `{}` at {}.",
                 val, id
             ),
+            TCE::NotFix(id, val) => write!(
+                f,
+                "Expected a recursive type expression, got: `{}` at {}.",
+                val, id
+            ),
+            TCE::NotId(id, val) => write!(
+                f,
+                "Expected an identity type expression, got: `{}` at {}.",
+                val, id
+            ),
             TCE::NotEmpty(id, val) => write!(
                 f,
                 "Expected an empty type expression, got: `{}` at {}.",
@@ -118,12 +207,74 @@ impl Display for TCE {
                 write!(f, "Missing variant `{}`.", variant)
             }
             TCE::MissingVariant(VarRec::Record, field) => write!(f, "Missing field `{}`.", field),
+            TCE::MayContainLabel(VarRec::Variant, variant) => write!(
+                f,
+                "The row may contain variant `{}`, which should be absent.",
+                variant
+            ),
+            TCE::MayContainLabel(VarRec::Record, field) => write!(
+                f,
+                "The row may contain field `{}`, which should be absent.",
+                field
+            ),
+            TCE::MayEqualLabel(VarRec::Variant, variant, label) => write!(
+                f,
+                "The row may contain variant `{}`, which should be absent, because `{}` may equal `'{}`.",
+                variant, variant, label
+            ),
+            TCE::MayEqualLabel(VarRec::Record, field, label) => write!(
+                f,
+                "The row may contain field `{}`, which should be absent, because `{}` may equal `'{}`.",
+                field, field, label
+            ),
+            TCE::OverlappingRows(id, left, right) => write!(
+                f,
+                "The rows `{}` and `{}` at {} may overlap, so they cannot be concatenated.",
+                left, right, id
+            ),
+            TCE::MissingClauses(id, labels) => {
+                write!(f, "Missing clauses at {} for variant", id)?;
+                if labels.len() > 1 {
+                    f.write_str("s")?;
+                }
+                for (i, label) in labels.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}`{}`", sep, label)?;
+                }
+                f.write_str(".")
+            }
+            TCE::UnreachableClause(id, variant) => write!(
+                f,
+                "Unreachable clause for variant `{}` at {}, which is not in the scrutinee type.",
+                variant, id
+            ),
+            TCE::DuplicateClause(id, variant) => write!(
+                f,
+                "Duplicate clause for variant `{}` at {}, which is handled by a clause above.",
+                variant, id
+            ),
+            TCE::RedundantClause(id) => write!(
+                f,
+                "Redundant clause at {}, which is covered by the clauses above.",
+                id
+            ),
             TCE::OverlappingVariant(id, variant) => {
                 write!(f, "Duplicated variant: `{}` at {}.", variant, id)
             }
             TCE::DuplicateField(id, variant) => {
                 write!(f, "Duplicated field: `{}` at {}.", variant, id)
             }
+            TCE::IndexedData(name) => write!(
+                f,
+                "Data type `{}` at {} has indices, but indexed families are not supported, \
+                 consider making them parameters.",
+                name.text, name.loc
+            ),
+            TCE::LabelVarProjection(label) => write!(
+                f,
+                "`{}` at {} is a label variable, write `.[{}]` to project the field it stands for.",
+                label.text, label.loc, label.text
+            ),
             TCE::UnexpectedVariant(id, variant) => {
                 write!(f, "Unexpected variant: `{}` at {}.", variant, id)
             }
@@ -151,6 +302,46 @@ impl Display for TCE {
                 "The definition at {} will hide the definition at {}.",
                 new, old
             ),
+            TCE::NonTerminating(info, cycle) => {
+                write!(f, "The definition at {} may not terminate, ", info)?;
+                f.write_str("because no argument is structurally decreasing in the calls `")?;
+                f.write_str(&cycle.join(" -> "))?;
+                f.write_str("`.")
+            }
+            TCE::NonPositive(name) => write!(
+                f,
+                "The recursive type `{}` at {} does not occur strictly positively in its definition.",
+                name.text, name.loc
+            ),
+            TCE::ModuleNotFound(path) => write!(
+                f,
+                "Cannot find module `{}` (imported at {}) in the search path.",
+                path.text, path.loc
+            ),
+            TCE::ModuleUnreadable(path, err) => write!(
+                f,
+                "Failed to load module `{}` (imported at {}):\n{}",
+                path.text, path.loc, err
+            ),
+            TCE::CyclicImport(path, importing) => {
+                write!(f, "Module `{}` (imported at {}", path.text, path.loc)?;
+                if let Some(importer) = importing.last() {
+                    write!(f, " in module `{}`", importer)?;
+                }
+                write!(f, ") is importing itself: ")?;
+                let start = (importing.iter())
+                    .position(|module| module == &path.text)
+                    .unwrap_or_default();
+                for module in &importing[start..] {
+                    write!(f, "{} -> ", module)?;
+                }
+                write!(f, "{}.", path.text)
+            }
+            TCE::AmbiguousImport(path, name, module) => write!(
+                f,
+                "Module `{}` (imported at {}) exports `{}`, which is already imported from module `{}`.",
+                path.text, path.loc, name, module
+            ),
             TCE::MetaRecursion(mi) => write!(
                 f,
                 "Failed to solve meta {:?}: \
@@ -158,11 +349,22 @@ impl Display for TCE {
                 mi
             ),
             TCE::MetaUnsolved(mi) => write!(f, "Failed to solve meta {:?}: No solution found.", mi),
-            TCE::MetaWithNonVar(info) => write!(
+            TCE::MetaWithNonVar(mi, val) => write!(
+                f,
+                "Failed to solve meta {:?}: \
+                 argument `{}` is not a distinct bound variable.",
+                mi, val
+            ),
+            TCE::MetaScopeEscape(mi, val) => write!(
+                f,
+                "Failed to solve meta {:?}: \
+                 anticipated solution contains `{}`, which is out of its scope.",
+                mi, val
+            ),
+            TCE::UnsolvedConstraint(info, a, b) => write!(
                 f,
-                "Failed to solve meta at {}: \
-                 anticipated solution contains unexpected non-bound values.",
-                info
+                "Cannot solve the postponed constraint `{}` = `{}` from the expression at {}.",
+                a, b, info
             ),
         }
     }
@@ -1,7 +1,16 @@
-use voile_util::meta::MetaContext;
-use voile_util::uid::{DBI, GI};
+use std::collections::BTreeMap;
+use voile_util::axiom::Axiom;
 
-use crate::syntax::core::{Val, ValInfo};
+use voile_util::level::{Level, LevelVar};
+use voile_util::loc::Loc;
+use voile_util::meta::{MetaContext, MetaSolution, MI};
+use voile_util::uid::{DBI, GI, UID};
+
+use crate::check::pattern::CaseTree;
+use crate::check::termination::CallGraph;
+use crate::syntax::core::{Neutral, Val, ValInfo};
+
+use super::TCE;
 
 /// Typing context.
 pub type Gamma = Vec<ValInfo>;
@@ -19,15 +28,123 @@ pub struct TCS {
     pub local_gamma: Gamma,
     /// Meta variable context. Always global.
     pub meta_context: MetaContext<Val>,
+    /// Level meta variable context. Always global.
+    pub level_meta_context: MetaContext<Level>,
+    /// The local variables that the solutions of the metas may refer to.
+    /// Metas without a scope (like `_` before being checked) may refer to any local.
+    pub meta_scopes: BTreeMap<MI, Vec<UID>>,
+    /// Equations that cannot be solved yet because of unsolved metas,
+    /// retried whenever a meta gets solved.
+    /// The location is the expression being checked when the equation is postponed.
+    pub constraints: Vec<(Val, Val, Option<Loc>)>,
+    /// Names of the local variables, for error messages.
+    pub(crate) local_names: BTreeMap<UID, String>,
+    /// Non-fatal errors, reported as warnings.
+    pub warnings: Vec<TCE>,
+    /// Names of the global definitions with signatures, for error messages.
+    pub(crate) glob_names: BTreeMap<GI, String>,
+    /// Calls between implemented global definitions, for termination checking.
+    pub(crate) call_graph: CallGraph,
+    /// Compiled pattern-matching lambdas, by the unique ids of the lambdas.
+    pub(crate) case_trees: BTreeMap<UID, CaseTree>,
 }
 
 impl TCS {
     /// Create a new valid but unsolved meta variable,
     /// used for generating fresh metas during elaboration.
     pub fn fresh_meta(&mut self) -> Val {
+        let mi = MI(self.meta_context.solutions().len());
+        self.scope_meta(mi);
         self.meta_context.fresh_meta(Val::meta)
     }
 
+    /// Record the local variables as the scope of `mi`, unless it already has one.
+    pub fn scope_meta(&mut self, mi: MI) {
+        if !self.meta_scopes.contains_key(&mi) {
+            let scope = self.local_uids();
+            self.meta_scopes.insert(mi, scope);
+        }
+    }
+
+    /// If `neut` is a solved meta (possibly applied),
+    /// the solution applied to the arguments.
+    pub fn unfold_meta(&self, neut: &Neutral) -> Option<Val> {
+        let (mi, args) = match neut {
+            Neutral::Meta(mi) => (*mi, &[][..]),
+            Neutral::App(head, args) => match &**head {
+                Neutral::Meta(mi) => (*mi, &args[..]),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match self.meta_context.solution(mi) {
+            MetaSolution::Solved(solution) => {
+                let apply = |f: Val, arg: &Val| f.apply(arg.clone());
+                Some(args.iter().fold(*solution.clone(), apply))
+            }
+            _ => None,
+        }
+    }
+
+    /// Substitute solved metas in `val`, including those in the solutions.
+    pub fn solved_metas(&self, val: Val) -> Val {
+        let result: Result<_, ()> = val.try_map_leaves(0, &mut |neut, _| {
+            Ok(match self.unfold_meta(&neut) {
+                Some(solution) => self.solved_metas(solution),
+                None => match neut {
+                    Neutral::App(head, args) => {
+                        let args = args.into_iter().map(|a| self.solved_metas(a));
+                        Val::app(*head, args.collect())
+                    }
+                    neut => Val::Neut(neut),
+                },
+            })
+        });
+        result.unwrap()
+    }
+
+    /// Create a new unsolved level meta variable.
+    pub fn fresh_level_meta(&mut self) -> Level {
+        let meta = |mi| Level::var(LevelVar::Meta(mi));
+        self.level_meta_context.fresh_meta(meta)
+    }
+
+    /// Substitute solved level metas in `level`.
+    pub fn solved_level(&self, level: Level) -> Level {
+        level.subst(&mut |var| match var {
+            LevelVar::Meta(mi) => match self.level_meta_context.solution(*mi) {
+                MetaSolution::Solved(solution) => Some(self.solved_level(*solution.clone())),
+                _ => None,
+            },
+            LevelVar::Bound(..) => None,
+        })
+    }
+
+    /// Substitute level metas in `val`, where unsolved ones are unconstrained
+    /// (so we pick the lowest level for them).
+    pub fn inline_levels(&self, val: Val) -> Val {
+        val.map_level(&mut |level| {
+            self.solved_level(level).subst(&mut |var| match var {
+                LevelVar::Meta(..) => Some(Level::default()),
+                LevelVar::Bound(..) => None,
+            })
+        })
+    }
+
+    /// Instantiate the level variables of a level-polymorphic global with fresh metas.
+    pub fn instantiate_levels(&mut self, val: Val) -> Val {
+        let mut metas = BTreeMap::new();
+        val.map_level(&mut |level| {
+            level.subst(&mut |var| match var {
+                LevelVar::Bound(name) => {
+                    let meta = metas.entry(name.clone());
+                    Some(meta.or_insert_with(|| self.fresh_level_meta()).clone())
+                }
+                LevelVar::Meta(..) => None,
+            })
+        })
+    }
+
     pub fn local_type(&self, dbi: DBI) -> &ValInfo {
         &self.local_gamma[self.local_gamma.len() - dbi.0 - 1]
     }
@@ -44,6 +161,17 @@ impl TCS {
         &self.env[index.0]
     }
 
+    /// Type of the local variable postulated as `axiom`, if any.
+    pub fn axiom_type(&self, axiom: &Axiom) -> Option<&ValInfo> {
+        let uid = axiom.unique_id();
+        let is_axiom = |val: &ValInfo| match &val.ast {
+            Val::Neut(Neutral::Axi(a)) => a.unique_id() == uid,
+            _ => false,
+        };
+        let index = self.local_env.iter().position(is_axiom)?;
+        self.local_gamma.get(index)
+    }
+
     pub fn local_is_type(&self, dbi: DBI) -> bool {
         self.local_val(dbi).ast.is_type() || self.local_type(dbi).ast.is_universe()
     }
@@ -60,6 +188,35 @@ impl TCS {
         self.local_env.pop().expect("Unexpected empty local env");
     }
 
+    /// Unique ids of the local variables, local definitions excluded.
+    pub fn local_uids(&self) -> Vec<UID> {
+        let uid = |val: &ValInfo| match &val.ast {
+            Val::Neut(Neutral::Axi(a)) => Some(a.unique_id()),
+            _ => None,
+        };
+        self.local_env.iter().filter_map(uid).collect()
+    }
+
+    /// Local definitions are inlined, but they still take a de-bruijn index
+    /// in the terms produced under them. This moves `val` under a new definition.
+    pub fn enter_definition(&self, val: Val) -> Val {
+        val.shift_generated(&self.local_uids(), |dbi| dbi + 1)
+    }
+
+    /// Inverse of [`enter_definition`](TCS::enter_definition),
+    /// should be invoked after the definition is popped.
+    pub fn exit_definition(&self, val: Val) -> Val {
+        val.shift_generated(&self.local_uids(), |DBI(dbi)| DBI(dbi - 1))
+    }
+
+    /// Introduce a local definition, whose value is inlined into its references.
+    pub fn push_definition(&mut self, ty: ValInfo, def: ValInfo) {
+        let ty = ty.map_ast(|ty| self.enter_definition(ty));
+        let def = def.map_ast(|def| self.enter_definition(def));
+        self.local_gamma.push(ty);
+        self.local_env.push(def);
+    }
+
     pub fn glob_len(&self) -> usize {
         self.gamma.len()
     }
@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use voile_util::loc::*;
+use voile_util::tags::{PiSig::*, Plicit, VarRec::*};
+use voile_util::uid::{next_uid, DBI, UID};
+
+use crate::syntax::abs::{Abs, Pat};
+use crate::syntax::core::{CaseSplit, Closure, Fields, Neutral, TVal, Val};
+
+use super::expr::dependent_field_type;
+use super::monad::{TCE, TCM, TCS};
+
+/// The return type of a case-split, which may depend on the split value.
+pub(super) enum Motive<'a> {
+    /// The closure of the Pi type of the case-split.
+    Closure(&'a Closure),
+    /// A type under the binder of the split value, which is the local (with the `UID`).
+    Local(UID, &'a Val),
+}
+
+impl Motive<'_> {
+    /// The return type of a clause, under the binder of the variant's argument.
+    pub(super) fn clause_type(&self, split: &Val) -> TVal {
+        match self {
+            Motive::Closure(closure) => closure.instantiate_borrow(split),
+            Motive::Local(uid, ty) => substitute_local((*ty).clone(), *uid, split),
+        }
+    }
+
+    pub(super) fn closure(&self) -> Closure {
+        match self {
+            Motive::Closure(closure) => (*closure).clone(),
+            Motive::Local(_, ty) => Closure::plain((*ty).clone()),
+        }
+    }
+}
+
+/// Substitute the local variable `uid` in `val` with `arg`.
+pub(super) fn substitute_local(val: Val, uid: UID, arg: &Val) -> Val {
+    let result: Result<_, ()> = val.try_map_leaves(0, &mut |neut, _| match neut {
+        Neutral::Axi(axiom) if axiom.unique_id() == uid => Ok(arg.clone()),
+        neut => Ok(Val::Neut(neut)),
+    });
+    result.unwrap()
+}
+
+/// A pattern-matching lambda compiled to nested case-splits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseTree {
+    /// The index of the clause, and the values of its pattern variables
+    /// (in the order they're introduced).
+    Leaf(usize, Vec<Path>),
+    /// Split on a value, each clause binds the argument of a constructor.
+    Split(Path, BTreeMap<String, CaseTree>),
+}
+
+/// A sub-value of the argument of a pattern-matching lambda.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Path {
+    /// The argument of the lambda is the binder `0`,
+    /// and each case-split clause binds one more.
+    Binder(usize),
+    Fst(Box<Self>),
+    Snd(Box<Self>),
+    Proj(Box<Self>, String),
+}
+
+impl Path {
+    fn value(&self, binders: &[Val]) -> Val {
+        match self {
+            Path::Binder(level) => binders[*level].clone(),
+            Path::Fst(pair) => pair.value(binders).first(),
+            Path::Snd(pair) => pair.value(binders).second(),
+            Path::Proj(rec, field) => rec.value(binders).project(field.clone()),
+        }
+    }
+
+    fn binder(&self) -> usize {
+        match self {
+            Path::Binder(level) => *level,
+            Path::Fst(inner) | Path::Snd(inner) | Path::Proj(inner, _) => inner.binder(),
+        }
+    }
+
+    fn fst(&self) -> Self {
+        Path::Fst(Box::new(self.clone()))
+    }
+
+    fn snd(&self) -> Self {
+        Path::Snd(Box::new(self.clone()))
+    }
+
+    fn proj(&self, field: &str) -> Self {
+        Path::Proj(Box::new(self.clone()), field.to_owned())
+    }
+}
+
+/// What the splits above a clause know about a binder.
+#[derive(Debug, Clone)]
+enum Known {
+    Unknown,
+    Pair(Box<Self>, Box<Self>),
+    Rec(BTreeMap<String, Self>),
+    /// Constructed with the label, the argument is the binder.
+    Cons(String, usize),
+}
+
+impl Known {
+    fn refine(&self, val: Val, binders: &[Val]) -> Val {
+        match self {
+            Known::Unknown => val,
+            Known::Pair(a, b) => {
+                let first = a.refine(val.clone().first(), binders);
+                Val::pair(first, b.refine(val.second(), binders))
+            }
+            Known::Rec(fields) => {
+                let fields: Fields = (fields.iter())
+                    .map(|(field, known)| {
+                        let value = known.refine(val.clone().project(field.clone()), binders);
+                        (field.clone(), value)
+                    })
+                    .collect();
+                val.update_fields(fields)
+            }
+            Known::Cons(label, level) => Val::cons(label.clone(), binders[*level].clone()),
+        }
+    }
+
+    fn at(&mut self, path: &Path) -> &mut Self {
+        let (parent, step) = match path {
+            Path::Binder(..) => return self,
+            Path::Fst(inner) => (self.at(inner), 0),
+            Path::Snd(inner) => (self.at(inner), 1),
+            Path::Proj(inner, _) => (self.at(inner), 2),
+        };
+        match (step, &*parent) {
+            (0, Known::Unknown) | (1, Known::Unknown) => {
+                *parent = Known::Pair(Box::new(Known::Unknown), Box::new(Known::Unknown))
+            }
+            (2, Known::Unknown) => *parent = Known::Rec(Default::default()),
+            _ => {}
+        }
+        match (path, parent) {
+            (Path::Fst(..), Known::Pair(a, _)) => a,
+            (Path::Snd(..), Known::Pair(_, b)) => b,
+            (Path::Proj(_, field), Known::Rec(fields)) => {
+                fields.entry(field.clone()).or_insert(Known::Unknown)
+            }
+            (path, known) => unreachable!("Cannot refine `{:?}` at `{:?}`.", known, path),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Column {
+    path: Path,
+    ty: TVal,
+}
+
+#[derive(Debug, Clone)]
+struct Row<'a> {
+    clause: usize,
+    /// The patterns of the columns, `None` for wildcards.
+    pats: Vec<Option<&'a Pat>>,
+    /// The bound pattern variables, with their values and types.
+    bound: BTreeMap<UID, (Path, TVal)>,
+}
+
+struct Compiler<'a> {
+    info: Loc,
+    clauses: &'a [(Pat, Abs)],
+    motive: &'a Motive<'a>,
+    /// The binders in scope, with what is known about them.
+    binders: Vec<(UID, Known)>,
+    /// Whether a clause is reachable.
+    used: Vec<bool>,
+    /// Whether missing clauses are allowed, when a redundant clause is checked on its own.
+    partial: bool,
+}
+
+impl<'a> Compiler<'a> {
+    fn binder_values(&self) -> Vec<Val> {
+        let mut values = vec![Val::default(); self.binders.len()];
+        // A binder only knows the binders after it.
+        for (level, (uid, known)) in self.binders.iter().enumerate().rev() {
+            values[level] = known.refine(Val::postulate(*uid), &values);
+        }
+        values
+    }
+
+    fn compile(
+        &mut self,
+        tcs: TCS,
+        columns: Vec<Column>,
+        mut rows: Vec<Row<'a>>,
+    ) -> TCM<(CaseTree, TCS)> {
+        for row in &mut rows {
+            for (pat, column) in row.pats.iter_mut().zip(&columns) {
+                match pat {
+                    Some(Pat::Var(_, uid)) => {
+                        let value = (column.path.clone(), column.ty.clone());
+                        row.bound.insert(*uid, value);
+                        *pat = None;
+                    }
+                    Some(Pat::Wildcard(..)) => *pat = None,
+                    _ => {}
+                }
+            }
+        }
+        let structural =
+            |pat: &Option<&Pat>| matches!(pat, Some(Pat::Pair(..)) | Some(Pat::Rec(..)));
+        if let Some(i) =
+            (0..columns.len()).find(|i| rows.iter().any(|row| structural(&row.pats[*i])))
+        {
+            let (columns, rows) = self.expand(i, columns, rows)?;
+            return self.compile(tcs, columns, rows);
+        }
+        let first = match rows.first() {
+            Some(first) => first,
+            // Only when there's no clause at all.
+            None => {
+                let column = &columns[0];
+                return match &column.ty {
+                    Val::RowPoly(Variant, variants) if variants.is_empty() => Ok((
+                        CaseTree::Split(column.path.clone(), Default::default()),
+                        tcs,
+                    )),
+                    ty => Err(TCE::NotEmpty(self.info, ty.clone())),
+                };
+            }
+        };
+        match first.pats.iter().position(Option::is_some) {
+            None => self.leaf(tcs, first),
+            Some(i) => self.split(tcs, i, columns, rows),
+        }
+    }
+
+    /// Replace the `i`th column, which is matched by pair or record patterns,
+    /// with the columns of its components.
+    fn expand(
+        &self,
+        i: usize,
+        mut columns: Vec<Column>,
+        mut rows: Vec<Row<'a>>,
+    ) -> TCM<(Vec<Column>, Vec<Row<'a>>)> {
+        let Column { path, ty } = columns[i].clone();
+        let ty = ty.unfold();
+        let pat = rows.iter().find_map(|row| row.pats[i]).unwrap();
+        let (new_columns, fields) = match pat {
+            Pat::Pair(..) => match &ty {
+                Val::Dt(Sigma, Plicit::Ex, param_ty, closure) => {
+                    let fst = Column {
+                        path: path.fst(),
+                        ty: (**param_ty).clone(),
+                    };
+                    let fst_value = fst.path.value(&self.binder_values());
+                    let snd_ty = closure.instantiate_borrow(&fst_value);
+                    (
+                        vec![
+                            fst,
+                            Column {
+                                path: path.snd(),
+                                ty: snd_ty,
+                            },
+                        ],
+                        None,
+                    )
+                }
+                ty => return Err(TCE::NotSigma(pat.loc(), ty.clone())),
+            },
+            _ => {
+                let fields: BTreeSet<&String> = (rows.iter())
+                    .filter_map(|row| match row.pats[i] {
+                        Some(Pat::Rec(_, fields)) => Some(fields.iter().map(|f| &f.label.text)),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect();
+                let record = path.value(&self.binder_values());
+                let mut new_columns = Vec::with_capacity(fields.len());
+                for field in &fields {
+                    let ty = match &ty {
+                        Val::Neut(Neutral::Row(Record, field_types, _)) => {
+                            (field_types.get(*field).cloned())
+                                .ok_or_else(|| TCE::MissingVariant(Record, (*field).clone()))?
+                        }
+                        ty => dependent_field_type(pat.loc(), ty.clone(), &record, field)?,
+                    };
+                    new_columns.push(Column {
+                        path: path.proj(field),
+                        ty,
+                    });
+                }
+                (new_columns, Some(fields))
+            }
+        };
+        let width = new_columns.len();
+        for row in &mut rows {
+            let pats = match (row.pats[i], &fields) {
+                (None, _) => vec![None; width],
+                (Some(Pat::Pair(a, b)), None) => vec![Some(&**a), Some(&**b)],
+                (Some(Pat::Rec(_, field_pats)), Some(fields)) => (fields.iter())
+                    .map(|field| {
+                        let pat = field_pats.iter().find(|f| &f.label.text == *field);
+                        pat.map(|f| &f.expr)
+                    })
+                    .collect(),
+                (Some(pat @ Pat::Cons(..)), _) => {
+                    return Err(TCE::NotRowType(Variant, pat.loc(), ty))
+                }
+                (Some(pat @ Pat::Rec(..)), _) => {
+                    return Err(TCE::NotRowType(Record, pat.loc(), ty))
+                }
+                (Some(pat), _) => return Err(TCE::NotSigma(pat.loc(), ty)),
+            };
+            row.pats.splice(i..=i, pats);
+        }
+        columns.splice(i..=i, new_columns);
+        Ok((columns, rows))
+    }
+
+    /// Split on the `i`th column, which is matched by constructor patterns.
+    fn split(
+        &mut self,
+        mut tcs: TCS,
+        i: usize,
+        columns: Vec<Column>,
+        rows: Vec<Row<'a>>,
+    ) -> TCM<(CaseTree, TCS)> {
+        let column = &columns[i];
+        // Values of recursive types are split as their unfoldings.
+        let column_ty = column.ty.clone().unfold();
+        let variants = match &column_ty {
+            Val::RowPoly(Variant, variants) => variants,
+            Val::Neut(Neutral::Row(Variant, ..)) => {
+                return Err(TCE::MissingClauses(self.info, vec!["...".to_owned()]))
+            }
+            ty => {
+                return Err(TCE::NotRowType(
+                    Variant,
+                    rows[0].pats[i].unwrap().loc(),
+                    ty.clone(),
+                ))
+            }
+        };
+        let mut mentioned = Vec::new();
+        let mut has_default = false;
+        for row in &rows {
+            match row.pats[i] {
+                None => has_default = true,
+                Some(Pat::Cons(label, _)) if !variants.contains_key(&label.text) => {
+                    return Err(TCE::UnexpectedVariant(label.loc, label.text.clone()))
+                }
+                Some(Pat::Cons(label, _)) => {
+                    if !mentioned.contains(&&label.text) {
+                        mentioned.push(&label.text);
+                    }
+                }
+                Some(pat) => return Err(TCE::NotSigma(pat.loc(), column.ty.clone())),
+            }
+        }
+        let missing: Vec<String> = (variants.keys())
+            .filter(|label| !has_default && !mentioned.contains(label))
+            .cloned()
+            .collect();
+        if !missing.is_empty() && !self.partial {
+            return Err(TCE::MissingClauses(self.info, missing));
+        }
+        let binder = column.path.binder();
+        let mut branches = BTreeMap::new();
+        for (label, payload_ty) in variants {
+            let sub_rows: Vec<_> = (rows.iter())
+                .filter_map(|row| match row.pats[i] {
+                    None => Some(row.clone()),
+                    Some(Pat::Cons(cons, pat)) if &cons.text == label => {
+                        let mut row = row.clone();
+                        row.pats[i] = Some(&**pat);
+                        Some(row)
+                    }
+                    Some(_) => None,
+                })
+                .collect();
+            if self.partial && sub_rows.is_empty() {
+                continue;
+            }
+            let uid = unsafe { next_uid() };
+            let level = self.binders.len();
+            let known = self.binders[binder].1.clone();
+            self.binders.push((uid, Known::Unknown));
+            *self.binders[binder].1.at(&column.path) = Known::Cons(label.clone(), level);
+
+            let mut sub_columns = columns.clone();
+            sub_columns[i] = Column {
+                path: Path::Binder(level),
+                ty: payload_ty.clone(),
+            };
+            let (tree, new_tcs) = self.compile(tcs, sub_columns, sub_rows)?;
+            tcs = new_tcs;
+            self.binders.pop();
+            self.binders[binder].1 = known;
+            branches.insert(label.clone(), tree);
+        }
+        Ok((CaseTree::Split(column.path.clone(), branches), tcs))
+    }
+
+    /// The first row matches, check the body of its clause.
+    fn leaf(&mut self, mut tcs: TCS, row: &Row) -> TCM<(CaseTree, TCS)> {
+        self.used[row.clause] = true;
+        let (pat, body) = &self.clauses[row.clause];
+        let vars = pat.vars();
+        let binders = self.binder_values();
+        let mut ret_ty = self.motive.clause_type(&binders[0]);
+        let info = merge_info(pat, body);
+        // Pattern variables are local definitions of the sub-values.
+        for uid in &vars {
+            let (path, ty) = &row.bound[uid];
+            let value = path.value(&binders).into_info(info);
+            tcs.push_definition(ty.clone().into_info(info), value);
+            ret_ty = tcs.enter_definition(ret_ty);
+        }
+        let (_, mut tcs) = tcs.check(body, &ret_ty).map_err(|e| e.wrap(info))?;
+        vars.iter().for_each(|_| tcs.pop_local());
+        let paths = vars.iter().map(|uid| row.bound[uid].0.clone()).collect();
+        Ok((CaseTree::Leaf(row.clause, paths), tcs))
+    }
+}
+
+/// Check a pattern-matching lambda against a Pi type from `param_ty`,
+/// the compiled case tree is stored in the state for evaluation.
+pub(super) fn check_pattern_lambda(
+    tcs: TCS,
+    info: Loc,
+    uid: UID,
+    clauses: &[(Pat, Abs)],
+    param_ty: &Val,
+    motive: &Motive,
+) -> TCM {
+    // The binders are not locals, the clauses only see their pattern variables.
+    let root = unsafe { next_uid() };
+    let mut compiler = Compiler {
+        info,
+        clauses,
+        motive,
+        binders: vec![(root, Known::Unknown)],
+        used: vec![false; clauses.len()],
+        partial: false,
+    };
+    let column = Column {
+        path: Path::Binder(0),
+        ty: param_ty.clone(),
+    };
+    let row = |clause: usize| Row {
+        clause,
+        pats: vec![Some(&clauses[clause].0)],
+        bound: Default::default(),
+    };
+    let rows = (0..clauses.len()).map(row).collect();
+    let (tree, mut tcs) = compiler.compile(tcs, vec![column.clone()], rows)?;
+    // Redundant clauses are still checked, each on its own.
+    compiler.partial = true;
+    for (clause, (pat, body)) in clauses.iter().enumerate() {
+        if compiler.used[clause] {
+            continue;
+        }
+        tcs.warnings
+            .push(TCE::RedundantClause(merge_info(pat, body)));
+        let (_, new_tcs) = compiler.compile(tcs, vec![column.clone()], vec![row(clause)])?;
+        tcs = new_tcs;
+    }
+    tcs.case_trees.insert(uid, tree);
+    Ok(tcs)
+}
+
+/// Evaluate a checked pattern-matching lambda by its case tree.
+pub(super) fn evaluate_pattern_lambda(
+    tcs: TCS,
+    tree: &CaseTree,
+    clauses: Vec<(Pat, Abs)>,
+) -> (Val, TCS) {
+    let mut binders = vec![unsafe { next_uid() }];
+    let mut levels = BTreeMap::new();
+    levels.insert(binders[0], 0);
+    let (body, tcs) = evaluate_case_tree(tcs, tree, &clauses, &mut binders, &mut levels);
+    // The binders are postulated during evaluation, now they become bound variables.
+    let lam: Result<_, ()> =
+        Val::closure_lam(body).try_map_leaves(0, &mut |neut, depth| match neut {
+            Neutral::Axi(axiom) if levels.contains_key(&axiom.unique_id()) => {
+                let uid = axiom.unique_id();
+                Ok(Val::var(DBI(depth - levels[&uid] - 1)))
+            }
+            neut => Ok(Val::Neut(neut)),
+        });
+    (lam.unwrap(), tcs)
+}
+
+fn evaluate_case_tree(
+    mut tcs: TCS,
+    tree: &CaseTree,
+    clauses: &[(Pat, Abs)],
+    binders: &mut Vec<UID>,
+    levels: &mut BTreeMap<UID, usize>,
+) -> (Val, TCS) {
+    let binder_values: Vec<Val> = binders.iter().map(|uid| Val::postulate(*uid)).collect();
+    match tree {
+        CaseTree::Leaf(clause, paths) => {
+            let (pat, body) = &clauses[*clause];
+            let info = pat.loc();
+            for path in paths {
+                let value = path.value(&binder_values).into_info(info);
+                tcs.push_definition(Val::fresh_axiom().into_info(info), value);
+            }
+            let (body, mut tcs) = tcs.evaluate(body.clone());
+            let mut body = body.ast;
+            for _ in paths {
+                tcs.pop_local();
+                body = tcs.exit_definition(body);
+            }
+            // The locals outside are now under the binders.
+            let body = body.shift_generated(&tcs.local_uids(), |dbi| dbi + binders.len());
+            (body, tcs)
+        }
+        CaseTree::Split(path, branches) => {
+            let mut split = CaseSplit::new();
+            for (label, tree) in branches {
+                let uid = unsafe { next_uid() };
+                levels.insert(uid, binders.len());
+                binders.push(uid);
+                let (clause, new_tcs) = evaluate_case_tree(tcs, tree, clauses, binders, levels);
+                tcs = new_tcs;
+                binders.pop();
+                split.insert(label.clone(), Closure::plain(clause));
+            }
+            let obj = path.value(&binder_values);
+            (Val::case_tree(split).apply(obj), tcs)
+        }
+    }
+}
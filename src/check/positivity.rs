@@ -0,0 +1,67 @@
+use voile_util::loc::Ident;
+use voile_util::tags::PiSig;
+use voile_util::uid::UID;
+
+use crate::syntax::core::{Closure, Neutral, Val};
+
+use super::monad::{TCE, TCM};
+
+/// Check that the variable `name` (postulated as `uid`) of a recursive type
+/// occurs only strictly positively in `body`, the body of the recursive type.
+pub(super) fn check_positivity(name: &Ident, uid: UID, body: &Val) -> TCM<()> {
+    if positive(uid, body) {
+        Ok(())
+    } else {
+        Err(TCE::NonPositive(name.clone()))
+    }
+}
+
+/// The variable never occurs in the parameter type of a function,
+/// nor as an argument of a neutral term (which may be a type function
+/// using its parameter negatively).
+fn positive(uid: UID, val: &Val) -> bool {
+    match val {
+        Val::Dt(PiSig::Pi, _, param, closure) => {
+            !occurs(uid, param) && positive_closure(uid, closure)
+        }
+        Val::Dt(PiSig::Sigma, _, param, closure) | Val::DepRec(_, param, closure) => {
+            positive(uid, param) && positive_closure(uid, closure)
+        }
+        Val::RowPoly(_, variants) => variants.values().all(|v| positive(uid, v)),
+        Val::Fix(closure) => positive_closure(uid, closure),
+        Val::Neut(neut) => positive_neutral(uid, neut),
+        val => !occurs(uid, val),
+    }
+}
+
+fn positive_neutral(uid: UID, neut: &Neutral) -> bool {
+    use Neutral::*;
+    match neut {
+        Axi(..) | Var(..) | Ref(..) | Meta(..) => true,
+        Lift(_, neut) => positive_neutral(uid, neut),
+        Row(_, variants, rest) => {
+            variants.values().all(|v| positive(uid, v)) && positive_neutral(uid, rest)
+        }
+        LabRow(_, label, ty, rest) => {
+            !occurs(uid, &Val::Neut(*label.clone())) && positive(uid, ty) && positive(uid, rest)
+        }
+        neut => !occurs(uid, &Val::Neut(neut.clone())),
+    }
+}
+
+fn positive_closure(uid: UID, closure: &Closure) -> bool {
+    match closure {
+        Closure::Plain(body) => positive(uid, body),
+        Closure::Tree(split) => split.values().all(|c| positive_closure(uid, c)),
+    }
+}
+
+/// Whether the variable occurs in `val` at all.
+fn occurs(uid: UID, val: &Val) -> bool {
+    let mut found = false;
+    val.clone().map_axiom(&mut |axiom| {
+        found |= axiom.unique_id() == uid;
+        Neutral::Axi(axiom)
+    });
+    found
+}
@@ -0,0 +1,335 @@
+use std::collections::BTreeSet;
+
+use voile_util::axiom::Axiom;
+use voile_util::uid::GI;
+
+use crate::syntax::core::{CaseSplit, Closure, Neutral, Val};
+
+/// How an argument of a call is related to a parameter of the caller.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Size {
+    /// No known relation.
+    #[default]
+    Unknown,
+    /// Equal to (or a projection of) the parameter of the given index.
+    Le(usize),
+    /// Structurally smaller than the parameter of the given index,
+    /// like the payload of a constructor the parameter is split on.
+    Lt(usize),
+}
+
+impl Size {
+    /// Something taken out of a value of this size.
+    fn strict(self) -> Self {
+        match self {
+            Size::Le(i) | Size::Lt(i) => Size::Lt(i),
+            Size::Unknown => Size::Unknown,
+        }
+    }
+
+    /// This size, where the parameters are instantiated with `args`.
+    fn compose(self, args: &[Size]) -> Self {
+        match self {
+            Size::Unknown => Size::Unknown,
+            Size::Le(i) => args.get(i).copied().unwrap_or_default(),
+            Size::Lt(i) => args.get(i).copied().unwrap_or_default().strict(),
+        }
+    }
+}
+
+/// A (possibly composed) call from one global definition to another.
+///
+/// `args[j]` is the relation between the `j`-th argument and the parameters of `from`,
+/// so this is a call matrix in the size-change termination sense,
+/// where each row has at most one non-`Unknown` entry.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Call {
+    pub from: GI,
+    pub to: GI,
+    pub args: Vec<Size>,
+    /// The definitions visited by this call, starting from `from`.
+    pub path: Vec<GI>,
+}
+
+impl Call {
+    fn new(from: GI, to: GI, mut args: Vec<Size>) -> Self {
+        while let Some(Size::Unknown) = args.last() {
+            args.pop();
+        }
+        let path = vec![from];
+        Self {
+            from,
+            to,
+            args,
+            path,
+        }
+    }
+
+    /// Calling `self` and then `next`.
+    fn then(&self, next: &Self) -> Self {
+        debug_assert_eq!(self.to, next.from);
+        let args = next.args.iter().map(|s| s.compose(&self.args)).collect();
+        let mut call = Self::new(self.from, next.to, args);
+        call.path = self.path.iter().chain(&next.path).copied().collect();
+        call
+    }
+
+    fn key(&self) -> (GI, GI, Vec<Size>) {
+        (self.from, self.to, self.args.clone())
+    }
+
+    /// Some parameter is passed to itself, but strictly smaller.
+    fn is_decreasing(&self) -> bool {
+        (self.args.iter().enumerate()).any(|(i, size)| *size == Size::Lt(i))
+    }
+
+    fn is_idempotent(&self) -> bool {
+        self.from == self.to && self.then(self).args == self.args
+    }
+}
+
+/// Calls between global definitions, collected from the checked core.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    calls: Vec<Call>,
+}
+
+impl CallGraph {
+    /// Add the calls in the body of `def`, then run the size-change termination
+    /// check on all the call cycles through `def`.
+    ///
+    /// Returns the definitions involved in a non-decreasing cycle, if any.
+    pub fn add_definition(&mut self, def: GI, body: &Val) -> Result<(), Vec<GI>> {
+        let mut collector = Collector {
+            def,
+            scope: Vec::new(),
+            param_count: 0,
+            calls: Vec::new(),
+        };
+        collector.leading(body);
+        self.calls.append(&mut collector.calls);
+        self.check(def)
+    }
+
+    /// Compute all composed calls from `def` (the closure of the call graph),
+    /// and see if all the idempotent ones going back to `def` are decreasing.
+    fn check(&self, def: GI) -> Result<(), Vec<GI>> {
+        let mut seen = BTreeSet::new();
+        let mut work: Vec<Call> = (self.calls.iter())
+            .filter(|call| call.from == def)
+            .cloned()
+            .collect();
+        while let Some(call) = work.pop() {
+            if !seen.insert(call.key()) {
+                continue;
+            }
+            if call.to == def && call.is_idempotent() && !call.is_decreasing() {
+                let mut path = call.path;
+                path.push(def);
+                return Err(path);
+            }
+            for next in self.calls.iter().filter(|next| next.from == call.to) {
+                work.push(call.then(next));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Traverse a body of a definition, collecting the calls to other global definitions.
+struct Collector {
+    def: GI,
+    /// Sizes of the local variables, the last one is `Var(0)`.
+    scope: Vec<Size>,
+    /// Number of parameters introduced by the leading lambdas so far.
+    param_count: usize,
+    calls: Vec<Call>,
+}
+
+impl Collector {
+    /// Leading lambdas and case-splits introduce parameters.
+    fn leading(&mut self, val: &Val) {
+        match val {
+            Val::Lam(closure) => {
+                let param = Size::Le(self.param_count);
+                self.param_count += 1;
+                self.leading_closure(closure, param);
+                self.param_count -= 1;
+            }
+            Val::Neut(Neutral::OrSplit(split, or)) => {
+                let param = self.param_count;
+                self.param_count += 1;
+                self.leading_split(split, Size::Lt(param));
+                // The default clause binds the parameter itself.
+                if let Val::Lam(default) = &**or {
+                    self.leading_closure(default, Size::Le(param));
+                }
+                self.param_count -= 1;
+                if !matches!(**or, Val::Lam(..)) {
+                    self.val(or);
+                }
+            }
+            val => self.val(val),
+        }
+    }
+
+    fn leading_closure(&mut self, closure: &Closure, arg: Size) {
+        match closure {
+            Closure::Plain(body) => {
+                self.scope.push(arg);
+                self.leading(body);
+                self.scope.pop();
+            }
+            Closure::Tree(split) => self.leading_split(split, arg.strict()),
+        }
+    }
+
+    fn leading_split(&mut self, split: &CaseSplit, payload: Size) {
+        for clause in split.values() {
+            self.leading_closure(clause, payload);
+        }
+    }
+
+    fn closure(&mut self, closure: &Closure, arg: Size) {
+        match closure {
+            Closure::Plain(body) => {
+                self.scope.push(arg);
+                self.val(body);
+                self.scope.pop();
+            }
+            Closure::Tree(split) => self.split(split, arg.strict()),
+        }
+    }
+
+    fn split(&mut self, split: &CaseSplit, payload: Size) {
+        for clause in split.values() {
+            self.closure(clause, payload);
+        }
+    }
+
+    fn val(&mut self, val: &Val) {
+        match val {
+            Val::Type(..)
+            | Val::RowKind(..)
+            | Val::LabelType
+            | Val::Label(..)
+            | Val::Refl
+            | Val::NatType
+            | Val::Nat(..) => {}
+            Val::Lam(closure) | Val::Fix(closure) => self.closure(closure, Size::Unknown),
+            Val::Dt(_, _, param, closure) | Val::DepRec(_, param, closure) => {
+                self.val(param);
+                self.closure(closure, Size::Unknown);
+            }
+            Val::RowPoly(_, variants) => variants.values().for_each(|v| self.val(v)),
+            Val::Rec(fields) => fields.values().for_each(|v| self.val(v)),
+            Val::Cons(_, a) => self.val(a),
+            Val::Pair(a, b) | Val::Id(a, b) => {
+                self.val(a);
+                self.val(b);
+            }
+            Val::Neut(neut) => self.neutral(neut),
+        }
+    }
+
+    fn neutral(&mut self, neut: &Neutral) {
+        use Neutral::*;
+        match neut {
+            App(f, args) => {
+                match callee(f) {
+                    Some(to) => {
+                        let sizes = args.iter().map(|a| self.size(a)).collect();
+                        self.calls.push(Call::new(self.def, to, sizes));
+                    }
+                    None => self.neutral(f),
+                }
+                args.iter().for_each(|a| self.val(a));
+            }
+            Ref(..) | Axi(..) => {
+                if let Some(to) = callee(neut) {
+                    self.calls.push(Call::new(self.def, to, Vec::new()));
+                }
+            }
+            Lift(_, inner) => self.neutral(inner),
+            Var(..) | Meta(..) => {}
+            Fst(p) | Snd(p) | Proj(p, _) | Remove(p, _) => self.neutral(p),
+            Row(_, variants, more) => {
+                variants.values().for_each(|v| self.val(v));
+                self.neutral(more);
+            }
+            Rec(fields, more) => {
+                fields.values().for_each(|v| self.val(v));
+                self.neutral(more);
+            }
+            SplitOn(split, on) => {
+                let payload = self.size_neutral(on).strict();
+                self.split(split, payload);
+                self.neutral(on);
+            }
+            OrSplit(split, or) => {
+                self.split(split, Size::Unknown);
+                self.val(or);
+            }
+            LabProj(rec, label) => {
+                self.val(rec);
+                self.neutral(label);
+            }
+            LabCons(label, a) | LabKind(label, a) => {
+                self.neutral(label);
+                self.val(a);
+            }
+            LabRow(_, label, ty, rest) => {
+                self.neutral(label);
+                self.val(ty);
+                self.val(rest);
+            }
+            RowMap(function, row) => {
+                self.val(function);
+                self.neutral(row);
+            }
+            RowDiff(row, _) => self.neutral(row),
+            J(refl_case, proof) => {
+                self.val(refl_case);
+                self.neutral(proof);
+            }
+            Suc(_, n) => self.neutral(n),
+            NatOp(_, a, b) => {
+                self.val(a);
+                self.val(b);
+            }
+            NatElim(zero, step, n) => {
+                self.val(zero);
+                self.val(step);
+                self.neutral(n);
+            }
+        }
+    }
+
+    fn size(&self, val: &Val) -> Size {
+        match val {
+            Val::Neut(neut) => self.size_neutral(neut),
+            _ => Size::Unknown,
+        }
+    }
+
+    /// Projections are considered as large as the projected value.
+    fn size_neutral(&self, neut: &Neutral) -> Size {
+        use Neutral::*;
+        match neut {
+            Var(dbi) => (self.scope.len().checked_sub(dbi.0 + 1))
+                .map(|i| self.scope[i])
+                .unwrap_or_default(),
+            Fst(p) | Snd(p) | Proj(p, _) | Lift(_, p) => self.size_neutral(p),
+            _ => Size::Unknown,
+        }
+    }
+}
+
+/// The global definition a neutral value refers to.
+fn callee(neut: &Neutral) -> Option<GI> {
+    match neut {
+        Neutral::Ref(gi) | Neutral::Axi(Axiom::Unimplemented(_, gi)) => Some(*gi),
+        Neutral::Lift(_, neut) => callee(neut),
+        _ => None,
+    }
+}
@@ -1,25 +1,362 @@
+use std::mem::take;
+
+use voile_util::axiom::Axiom;
+use voile_util::level::{Level, LevelVar};
+use voile_util::loc::Loc;
 use voile_util::meta::{MetaSolution, MI};
 use voile_util::tags::VarRec;
+use voile_util::uid::{DBI, UID};
 
-use crate::syntax::core::{CaseSplit, Closure, Neutral, TraverseNeutral, Val, Variants};
+use crate::syntax::core::{CaseSplit, Closure, Neutral, Val, Variants};
 
 use super::monad::{TCE, TCM, TCS};
 
-fn check_solution(meta: MI, rhs: Val) -> TCM<()> {
-    rhs.try_fold_neutral((), |(), neut| match neut {
-        Neutral::Meta(mi) if mi == meta => Err(TCE::MetaRecursion(mi)),
-        _ => Ok(()),
+/// The meta at the head of a (possibly applied) neutral value, with the arguments.
+fn flex(neut: &Neutral) -> Option<(MI, &[Val])> {
+    match neut {
+        Neutral::Meta(mi) => Some((*mi, &[])),
+        Neutral::App(head, args) => match &**head {
+            Neutral::Meta(mi) => Some((*mi, args)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Local variables are axioms generated for binders during type-checking.
+fn local_uid(val: &Val) -> Option<UID> {
+    use Axiom::*;
+    match val {
+        Val::Neut(Neutral::Axi(a @ Postulated(..)))
+        | Val::Neut(Neutral::Axi(a @ Generated(..)))
+        | Val::Neut(Neutral::Axi(a @ Implicit(..))) => Some(a.unique_id()),
+        _ => None,
+    }
+}
+
+/// Spine inversion: the arguments of a pattern (in the sense of Miller)
+/// are distinct local variables, which become the parameters of the solution.
+fn invert_spine(meta: MI, spine: &[Val]) -> TCM<Vec<UID>> {
+    let mut params = Vec::with_capacity(spine.len());
+    for arg in spine {
+        match local_uid(arg) {
+            Some(uid) if !params.contains(&uid) => params.push(uid),
+            _ => return Err(TCE::MetaWithNonVar(meta, arg.clone())),
+        }
+    }
+    Ok(params)
+}
+
+fn apply_all(f: Val, args: Vec<Val>) -> Val {
+    args.into_iter().fold(f, Val::apply)
+}
+
+/// A fresh meta with the same scope as `meta`.
+fn fresh_meta_like(tcs: &mut TCS, meta: MI) -> (MI, Val) {
+    let fresh = MI(tcs.meta_context.solutions().len());
+    let val = tcs.meta_context.fresh_meta(Val::meta);
+    if let Some(locals) = tcs.meta_scopes.get(&meta).cloned() {
+        tcs.meta_scopes.insert(fresh, locals);
+    }
+    (fresh, val)
+}
+
+/// What the solution of a meta can refer to.
+struct Scope {
+    meta: MI,
+    /// Parameters of the solution, from the spine.
+    params: Vec<UID>,
+    /// Local variables in scope of the meta, `None` means all of them.
+    locals: Option<Vec<UID>>,
+}
+
+impl Scope {
+    /// The de-bruijn index of the parameter, under `depth` binders.
+    fn param(&self, axiom: &Axiom, depth: usize) -> Option<Val> {
+        let uid = axiom.unique_id();
+        let i = self.params.iter().position(|p| *p == uid)?;
+        Some(Val::var(DBI(self.params.len() - 1 - i + depth)))
+    }
+
+    /// Implicit axioms are placeholders for erased arguments, so they're always fine.
+    fn allows(&self, axiom: &Axiom) -> bool {
+        match (axiom, &self.locals) {
+            (Axiom::Postulated(uid), Some(locals)) | (Axiom::Generated(uid, _), Some(locals)) => {
+                self.params.contains(uid) || locals.contains(uid)
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether an argument of another meta in the solution can be kept.
+    fn keeps(&self, arg: &Val) -> bool {
+        match arg {
+            Val::Neut(Neutral::Axi(axiom)) => self.allows(axiom),
+            _ => true,
+        }
+    }
+}
+
+/// The metas in the solution of another meta cannot refer to
+/// anything out of the scope of that meta.
+fn restrict_scope(tcs: &mut TCS, meta: MI, scope: &Scope) {
+    if let Some(locals) = &scope.locals {
+        let inner = tcs
+            .meta_scopes
+            .entry(meta)
+            .or_insert_with(|| locals.clone());
+        inner.retain(|uid| locals.contains(uid));
+    }
+}
+
+/// Pruning: if `?meta` is applied to variables out of `scope`,
+/// it cannot depend on those arguments.
+/// So `?meta` is solved with a new meta taking only the other arguments.
+fn prune(tcs: &mut TCS, scope: &Scope, meta: MI, args: Vec<Val>) -> (MI, Vec<Val>) {
+    let keep: Vec<bool> = args.iter().map(|arg| scope.keeps(arg)).collect();
+    if keep.iter().all(|keep| *keep) {
+        return (meta, args);
+    }
+    let (pruned, body) = fresh_meta_like(tcs, meta);
+    let len = args.len();
+    let kept_vars = (keep.iter().enumerate())
+        .filter(|(_, keep)| **keep)
+        .map(|(i, _)| Val::var(DBI(len - 1 - i)))
+        .collect();
+    let body = apply_all(body, kept_vars);
+    let solution = args.iter().fold(body, |body, _| Val::closure_lam(body));
+    tcs.meta_context.solve_meta(meta, solution);
+    let args = (args.into_iter().zip(keep))
+        .filter(|(_, keep)| *keep)
+        .map(|(arg, _)| arg)
+        .collect();
+    (pruned, args)
+}
+
+/// Compute the body of the solution of `scope.meta` from the right-hand side.
+/// The parameters become variables, and the metas applied to out-of-scope
+/// variables are pruned. `scope.meta` itself cannot occur (the occurs check).
+fn invert(tcs: &mut TCS, scope: &Scope, rhs: Val, depth: usize) -> TCM<Val> {
+    rhs.try_map_leaves(depth, &mut |neut, depth| {
+        if let Some(solution) = tcs.unfold_meta(&neut) {
+            return invert(tcs, scope, solution, depth);
+        }
+        match neut {
+            Neutral::Axi(axiom) => match scope.param(&axiom, depth) {
+                Some(var) => Ok(var),
+                None if scope.allows(&axiom) => Ok(Val::Neut(Neutral::Axi(axiom))),
+                None => Err(TCE::MetaScopeEscape(
+                    scope.meta,
+                    Val::Neut(Neutral::Axi(axiom)),
+                )),
+            },
+            neut => match flex(&neut) {
+                Some((mi, _)) if mi == scope.meta => Err(TCE::MetaRecursion(mi)),
+                Some((mi, args)) => {
+                    let (mi, args) = prune(tcs, scope, mi, args.to_vec());
+                    restrict_scope(tcs, mi, scope);
+                    let args = (args.into_iter())
+                        .map(|arg| invert(tcs, scope, arg, depth))
+                        .collect::<TCM<_>>()?;
+                    Ok(apply_all(Val::meta(mi), args))
+                }
+                None => Ok(Val::Neut(neut)),
+            },
+        }
     })
 }
 
-/// Solve a meta with a specific value.
-fn solve_with(mut tcs: TCS, meta: MI, solution: Val) -> TCM {
-    // TODO: remove this clone by introducing reference version of `try_fold_neutral`.
-    let anticipated_solution = solution.clone().unimplemented_to_glob();
-    check_solution(meta, solution)?;
-    tcs.meta_context.solve_meta(meta, anticipated_solution);
+/**
+Solve `?meta spine = rhs` by higher-order pattern unification.
+$$
+\newcommand{\Gvdash}[0]{\Gamma \vdash}
+\newcommand{\cA}[0]{\mathcal A}
+\cfrac{
+  x\_1, \dots, x\_n \text{ distinct} \quad
+  \textsf{FV}(\cA) \subseteq \textsf{scope}(?m) \cup \\{x\_1, \dots, x\_n\\} \quad
+  ?m \notin \cA
+}{
+  \Gvdash ?m\ x\_1 \dots x\_n \simeq \cA
+  \quad ?m := \lambda x\_1 \dots x\_n. \cA
+}
+$$
+*/
+fn solve_with(mut tcs: TCS, meta: MI, spine: &[Val], rhs: &Val) -> TCM {
+    let params = invert_spine(meta, spine)?;
+    let locals = tcs.meta_scopes.get(&meta).cloned();
+    let scope = Scope {
+        meta,
+        params,
+        locals,
+    };
+    let rhs = tcs.solved_metas(rhs.clone());
+    let body = invert(&mut tcs, &scope, rhs, 0)?;
+    let solution = spine.iter().fold(body, |body, _| Val::closure_lam(body));
+    tcs.meta_context
+        .solve_meta(meta, solution.unimplemented_to_glob());
+    tcs.retry_constraints()
+}
+
+/// Solve a flex term (an unsolved meta, possibly applied) with `term`.
+/// If `term` is also flex, the one with a pattern spine is solved.
+/// If neither is a pattern, the equation is postponed.
+fn solve_flex(tcs: TCS, flex_neut: &Neutral, term: &Val) -> TCM {
+    let (meta, spine) = flex(flex_neut).unwrap();
+    if invert_spine(meta, spine).is_ok() {
+        return solve_with(tcs, meta, spine, term);
+    }
+    let flex_val = Val::Neut(flex_neut.clone());
+    match term {
+        Val::Neut(other) if flex(other).is_some() => {
+            let (other_meta, other_spine) = flex(other).unwrap();
+            if invert_spine(other_meta, other_spine).is_ok() {
+                return solve_with(tcs, other_meta, other_spine, &flex_val);
+            }
+            Ok(tcs.postpone(flex_val, term.clone()))
+        }
+        _ => Ok(tcs.postpone(flex_val, term.clone())),
+    }
+}
 
-    Ok(tcs)
+/// The meta that prevents `neut` from reducing,
+/// like the meta being split on or being projected.
+fn blocker(neut: &Neutral) -> Option<MI> {
+    use Neutral::*;
+    match neut {
+        Meta(mi) => Some(*mi),
+        App(f, _) => blocker(f),
+        Fst(p) | Snd(p) | Proj(p, _) | Remove(p, _) | Lift(_, p) | SplitOn(_, p) | J(_, p) => {
+            blocker(p)
+        }
+        // Unknown labels prevent the row (or the elimination) from reducing.
+        LabProj(_, l) | LabCons(l, _) | LabRow(_, l, ..) | LabKind(l, _) => blocker(l),
+        // Unknown rows prevent the row operators from reducing.
+        RowMap(_, row) | RowDiff(row, _) => blocker(row),
+        Suc(_, n) | NatElim(_, _, n) => blocker(n),
+        // Unknown operands prevent the primitive from reducing.
+        NatOp(_, a, b) => match (&**a, &**b) {
+            (Val::Neut(a), _) if blocker(a).is_some() => blocker(a),
+            (_, Val::Neut(b)) => blocker(b),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Retry the postponed constraints, until no more metas get solved.
+fn retry_constraints(mut tcs: TCS) -> TCM {
+    loop {
+        if tcs.constraints.is_empty() {
+            return Ok(tcs);
+        }
+        let solved = solved_count(&tcs);
+        let constraints = take(&mut tcs.constraints);
+        for (a, b, loc) in constraints {
+            let postponed = tcs.constraints.len();
+            tcs = tcs.unify(&a, &b)?;
+            // The equations postponed again keep their locations.
+            for (_, _, located) in &mut tcs.constraints[postponed..] {
+                *located = located.or(loc);
+            }
+        }
+        if solved_count(&tcs) == solved {
+            return Ok(tcs);
+        }
+    }
+}
+
+fn solved_count(tcs: &TCS) -> usize {
+    let solutions = tcs.meta_context.solutions().iter();
+    solutions
+        .filter(|s| !matches!(s, MetaSolution::Unsolved))
+        .count()
+}
+
+/// `?meta xs = ?meta ys`: the solution can only depend on the positions
+/// where `xs` and `ys` agree, if they're both patterns.
+/// Otherwise the equation is postponed until `?meta` is solved.
+fn unify_same_meta(mut tcs: TCS, meta: MI, xs: &[Val], ys: &[Val]) -> TCM {
+    let apply = |args: &[Val]| apply_all(Val::meta(meta), args.to_vec());
+    let (x_params, y_params) = match (invert_spine(meta, xs), invert_spine(meta, ys)) {
+        (Ok(x_params), Ok(y_params)) if xs.len() == ys.len() => (x_params, y_params),
+        // Equal arguments are not necessary, like `?m (f a) = ?m (f b)`
+        // when `?m` ignores its argument.
+        _ if xs.len() == ys.len() => return Ok(tcs.postpone(apply(xs), apply(ys))),
+        _ => return Err(TCE::CannotUnify(apply(xs), apply(ys))),
+    };
+    if x_params == y_params {
+        return Ok(tcs);
+    }
+    let len = xs.len();
+    let (_, body) = fresh_meta_like(&mut tcs, meta);
+    let kept_vars = (x_params.iter().zip(&y_params).enumerate())
+        .filter(|(_, (x, y))| x == y)
+        .map(|(i, _)| Val::var(DBI(len - 1 - i)))
+        .collect();
+    let body = apply_all(body, kept_vars);
+    let solution = xs.iter().fold(body, |body, _| Val::closure_lam(body));
+    tcs.meta_context.solve_meta(meta, solution);
+    tcs.retry_constraints()
+}
+
+/// If `meta` is `?m + k` and `level` is `l`, try to solve `?m` with `l - k`.
+fn level_meta_solution(meta: &Level, level: &Level) -> Option<(MI, Level)> {
+    let (mi, offset) = match meta.as_single_var()? {
+        (LevelVar::Meta(mi), offset) => (*mi, offset),
+        (LevelVar::Bound(..), ..) => return None,
+    };
+    let solution = match level {
+        Level::Omega => Level::Omega,
+        Level::Num(n) if *n >= offset => Level::Num(n - offset),
+        Level::Max(n, vars) => {
+            let occurs = vars.contains_key(&LevelVar::Meta(mi));
+            // `n` does not matter if it's smaller than some `v + k`
+            let dominated = *n >= offset || vars.values().any(|k| k >= n);
+            if occurs || !dominated || vars.values().any(|k| *k < offset) {
+                return None;
+            }
+            level.clone() - offset
+        }
+        Level::Num(..) => return None,
+    };
+    Some((mi, solution))
+}
+
+/**
+Solve the level constraint $a = b$, where $a$ and $b$ may contain level metas.
+*/
+fn unify_level(mut tcs: TCS, a: &Level, b: &Level) -> TCM {
+    let a = tcs.solved_level(a.clone());
+    let b = tcs.solved_level(b.clone());
+    if a == b {
+        return Ok(tcs);
+    }
+    match level_meta_solution(&a, &b).or_else(|| level_meta_solution(&b, &a)) {
+        Some((mi, solution)) => {
+            tcs.level_meta_context.solve_meta(mi, solution);
+            Ok(tcs)
+        }
+        None => Err(TCE::CannotUnify(Val::Type(a), Val::Type(b))),
+    }
+}
+
+/**
+Solve the level constraint $a \le b$, where $a$ and $b$ may contain level metas.
+Unsolved metas are solved eagerly by making the constraint an equation,
+which gives the lowest possible level to the metas on the right-hand side.
+*/
+fn subtype_level(mut tcs: TCS, a: &Level, b: &Level) -> TCM {
+    let a = tcs.solved_level(a.clone());
+    let b = tcs.solved_level(b.clone());
+    let solution = level_meta_solution(&b, &a).or_else(|| level_meta_solution(&a, &b));
+    if let Some((mi, solution)) = solution {
+        tcs.level_meta_context.solve_meta(mi, solution);
+        Ok(tcs)
+    } else if a <= b {
+        Ok(tcs)
+    } else {
+        Err(TCE::NotSubtype(Val::Type(a), Val::Type(b)))
+    }
 }
 
 /**
@@ -64,6 +401,106 @@ fn unify_variants(tcs: TCS, kind: VarRec, subset: &Variants, superset: &Variants
     })
 }
 
+/// Flatten the row extensions that are solved metas,
+/// so `{ a | ?r }` with `?r := { b | ?s }` becomes `{ a, b | ?s }`.
+fn row_parts(
+    tcs: &TCS,
+    variants: &Variants,
+    more: Option<&Neutral>,
+) -> (Variants, Option<Neutral>) {
+    let mut variants = variants.clone();
+    let mut more = more.cloned();
+    while let Some(solution) = more.as_ref().and_then(|more| tcs.unfold_meta(more)) {
+        more = match solution {
+            Val::RowPoly(_, mut ext) => {
+                variants.append(&mut ext);
+                None
+            }
+            Val::Neut(Neutral::Row(_, mut ext, ext_more)) => {
+                variants.append(&mut ext);
+                Some(*ext_more)
+            }
+            Val::Neut(neut) => Some(neut),
+            _ => break,
+        };
+    }
+    (variants, more)
+}
+
+/**
+Row unification, in the style of Rémy and Leijen.
+Labels only present in one row are pushed into the extension of the other one,
+and both extensions share a fresh row meta $\rho$ for the rest.
+$$
+\newcommand{\Gvdash}[0]{\Gamma \vdash}
+\newcommand{\clabTy}[0]{\Delta}
+\cfrac{
+  \Gvdash \clabTy\_0 \simeq \clabTy\_1 \text{ on } \clabTy\_0 \cap \clabTy\_1 \quad
+  \Gvdash r\_0 \simeq \\{ \clabTy\_1 \setminus \clabTy\_0 \mid \rho \\} \quad
+  \Gvdash r\_1 \simeq \\{ \clabTy\_0 \setminus \clabTy\_1 \mid \rho \\}
+}{
+  \Gvdash \\{ \clabTy\_0 \mid r\_0 \\} \simeq \\{ \clabTy\_1 \mid r\_1 \\}
+}
+$$
+Closed rows are rows without the extension, where nothing can be pushed into.
+*/
+fn unify_rows(
+    tcs: TCS,
+    kind: VarRec,
+    (a_variants, a_more): (&Variants, Option<&Neutral>),
+    (b_variants, b_more): (&Variants, Option<&Neutral>),
+) -> TCM {
+    let (a_variants, a_more) = row_parts(&tcs, a_variants, a_more);
+    let (b_variants, b_more) = row_parts(&tcs, b_variants, b_more);
+    let only = |this: &Variants, that: &Variants| -> Variants {
+        (this.iter())
+            .filter(|(label, _)| !that.contains_key(*label))
+            .map(|(label, ty)| (label.clone(), ty.clone()))
+            .collect()
+    };
+    let (only_a, only_b) = (
+        only(&a_variants, &b_variants),
+        only(&b_variants, &a_variants),
+    );
+    let mut tcs = (a_variants.iter())
+        .filter_map(|(label, a_ty)| Some((a_ty, b_variants.get(label)?)))
+        .try_fold(tcs, |tcs, (a_ty, b_ty)| tcs.unify(a_ty, b_ty))?;
+    let missing = |only: &Variants| match only.keys().next() {
+        Some(label) => Err(TCE::MissingVariant(kind, label.clone())),
+        None => Ok(()),
+    };
+    match (a_more, b_more) {
+        (None, None) => {
+            missing(&only_a)?;
+            missing(&only_b)?;
+            Ok(tcs)
+        }
+        (Some(a_more), None) => {
+            missing(&only_a)?;
+            tcs.unify(&Val::Neut(a_more), &Val::RowPoly(kind, only_b))
+        }
+        (None, Some(b_more)) => {
+            missing(&only_b)?;
+            tcs.unify(&Val::RowPoly(kind, only_a), &Val::Neut(b_more))
+        }
+        (Some(a_more), Some(b_more)) if only_a.is_empty() && only_b.is_empty() => {
+            tcs.unify(&Val::Neut(a_more), &Val::Neut(b_more))
+        }
+        (Some(a_more), Some(b_more)) if a_more == b_more => {
+            missing(&only_a)?;
+            missing(&only_b)?;
+            Ok(tcs)
+        }
+        (Some(a_more), Some(b_more)) => {
+            let rest = tcs.fresh_meta();
+            let a_ext = Val::RowPoly(kind, only_b).row_extend(rest.clone());
+            let b_ext = Val::RowPoly(kind, only_a).row_extend(rest);
+            tcs.unify(&Val::Neut(a_more), &a_ext)?
+                .unify(&Val::Neut(b_more), &b_ext)
+        }
+    }
+}
+
 /**
 Try to unify two well-typed terms,
 using a conversion check algorithm.
@@ -99,10 +536,12 @@ $$
 */
 fn unify(tcs: TCS, a: &Val, b: &Val) -> TCM {
     // use Axiom::Generated as Gen;
-    use Neutral::{Axi, Meta, Ref};
+    use Neutral::{Axi, Ref, Row, Suc};
     use Val::*;
     match (a, b) {
-        (Type(sub_level), Type(super_level)) if sub_level == super_level => Ok(tcs),
+        (Type(sub_level), Type(super_level)) => tcs.unify_level(sub_level, super_level),
+        (LabelType, LabelType) => Ok(tcs),
+        (Label(a_label), Label(b_label)) if a_label == b_label => Ok(tcs),
         (Neut(Axi(sub)), Neut(Axi(sup))) if sub.unique_id() == sup.unique_id() => Ok(tcs),
         /*
         (Neut(Var(x)), Neut(Var(y))) if x == y => Ok(tcs),
@@ -116,8 +555,13 @@ fn unify(tcs: TCS, a: &Val, b: &Val) -> TCM {
         {
             tcs.unify(input_a, input_b)?.unify_closure(clos_a, clos_b)
         }
-        (Lam(a), Lam(b)) => unify_closure(tcs, a, b),
-        (Cons(_, a), Cons(_, b)) => tcs.unify(&**a, &**b),
+        (DepRec(a_label, a_ty, a_rest), DepRec(b_label, b_ty, b_rest)) if a_label == b_label => {
+            tcs.unify(a_ty, b_ty)?.unify_closure(a_rest, b_rest)
+        }
+        (Lam(a), Lam(b)) | (Fix(a), Fix(b)) => unify_closure(tcs, a, b),
+        (Id(a0, a1), Id(b0, b1)) => tcs.unify(a0, b0)?.unify(a1, b1),
+        (Refl, Refl) => Ok(tcs),
+        (Cons(a_name, a), Cons(b_name, b)) if a_name == b_name => tcs.unify(a, b),
         (Pair(a0, a1), Pair(b0, b1)) => tcs.unify(&**a0, &**b0)?.unify(&**a1, &**b1),
         (RowPoly(a_kind, a_variants), RowPoly(b_kind, b_variants))
             if a_kind == b_kind && a_variants.len() == b_variants.len() =>
@@ -125,10 +569,10 @@ fn unify(tcs: TCS, a: &Val, b: &Val) -> TCM {
             tcs.unify_variants(*a_kind, a_variants, b_variants)
         }
         (RowKind(a_level, a_kind, a_labels), RowKind(b_level, b_kind, b_labels))
-            if a_level == b_level && a_kind == b_kind && a_labels.len() == b_labels.len() =>
+            if a_kind == b_kind && a_labels.len() == b_labels.len() =>
         {
             if a_labels.iter().all(|n| b_labels.contains(n)) {
-                Ok(tcs)
+                tcs.unify_level(a_level, b_level)
             } else {
                 Err(TCE::CannotUnify(a.clone(), b.clone()))
             }
@@ -136,19 +580,71 @@ fn unify(tcs: TCS, a: &Val, b: &Val) -> TCM {
         (Rec(a_fields), Rec(b_fields)) if a_fields.len() == b_fields.len() => {
             tcs.unify_variants(VarRec::Record, a_fields, b_fields)
         }
-        (term, Neut(Meta(mi))) | (Neut(Meta(mi)), term) => match &tcs.meta_context.solution(*mi) {
-            MetaSolution::Unsolved => solve_with(tcs, *mi, term.clone()),
-            MetaSolution::Solved(solution) => {
-                let val = *solution.clone();
-                tcs.unify(&val, term)
+        (NatType, NatType) => Ok(tcs),
+        (Nat(a), Nat(b)) if a == b => Ok(tcs),
+        (Nat(n), Neut(Suc(k, x))) | (Neut(Suc(k, x)), Nat(n)) if n >= k => {
+            tcs.unify(&Nat(n - k), &Neut(*x.clone()))
+        }
+        (Neut(Suc(a_k, a)), Neut(Suc(b_k, b))) => {
+            // The common successors cancel out.
+            let common = a_k.min(b_k);
+            let a = Neut(*a.clone()).plus(a_k - common);
+            tcs.unify(&a, &Neut(*b.clone()).plus(b_k - common))
+        }
+        (RowPoly(a_kind, a_variants), Neut(Row(b_kind, b_variants, b_more)))
+            if a_kind == b_kind =>
+        {
+            let b = (b_variants, Some(&**b_more));
+            unify_rows(tcs, *a_kind, (a_variants, None), b)
+        }
+        (Neut(Row(a_kind, a_variants, a_more)), RowPoly(b_kind, b_variants))
+            if a_kind == b_kind =>
+        {
+            let a = (a_variants, Some(&**a_more));
+            unify_rows(tcs, *a_kind, a, (b_variants, None))
+        }
+        (Neut(Row(a_kind, a_variants, a_more)), Neut(Row(b_kind, b_variants, b_more)))
+            if a_kind == b_kind =>
+        {
+            let a = (a_variants, Some(&**a_more));
+            unify_rows(tcs, *a_kind, a, (b_variants, Some(&**b_more)))
+        }
+        (Neut(a), Neut(b)) if same_unsolved_meta(&tcs, a, b) => {
+            let (meta, xs) = flex(a).unwrap();
+            let (_, ys) = flex(b).unwrap();
+            unify_same_meta(tcs, meta, xs, ys)
+        }
+        (term, Neut(flex_neut)) | (Neut(flex_neut), term) if flex(flex_neut).is_some() => {
+            match tcs.unfold_meta(flex_neut) {
+                Some(val) => tcs.unify(&val, term),
+                None => solve_flex(tcs, flex_neut, term),
             }
-            MetaSolution::Inlined => unreachable!(),
-        },
+        }
+        (Neut(blocked), term) | (term, Neut(blocked)) if blocker(blocked).is_some() => {
+            let val = Val::Neut(blocked.clone());
+            if &val == term {
+                return Ok(tcs);
+            }
+            match tcs.meta_context.solution(blocker(blocked).unwrap()) {
+                MetaSolution::Unsolved => Ok(tcs.postpone(val, term.clone())),
+                _ => {
+                    let val = tcs.solved_metas(val);
+                    tcs.unify(&val, term)
+                }
+            }
+        }
         (Neut(a), Neut(b)) => tcs.unify_neutral(a, b),
         (e, t) => Err(TCE::CannotUnify(e.clone(), t.clone())),
     }
 }
 
+fn same_unsolved_meta(tcs: &TCS, a: &Neutral, b: &Neutral) -> bool {
+    match (flex(a), flex(b)) {
+        (Some((x, _)), Some((y, _))) => x == y && tcs.unfold_meta(a).is_none(),
+        _ => false,
+    }
+}
+
 /**
 Beta-rule in `unify`.
 $$
@@ -250,35 +746,116 @@ fn unify_neutral(tcs: TCS, a: &Neutral, b: &Neutral) -> TCM {
     use Neutral::*;
     match (a, b) {
         (Ref(x), Ref(y)) if x == y => Ok(tcs),
+        (Axi(x), Axi(y)) if x.unique_id() == y.unique_id() => Ok(tcs),
         (Lift(x, a), Lift(y, b)) if x == y => tcs.unify_neutral(&**a, &**b),
         (App(f, a), App(g, b)) if a.len() == b.len() => (a.iter().zip(b.iter()))
             .try_fold(tcs.unify_neutral(&*f, &*g)?, |tcs, (x, y)| tcs.unify(x, y)),
         (Rec(a_fields, a_more), Rec(b_fields, b_more)) if a_fields.len() == b_fields.len() => tcs
             .unify_variants(VarRec::Record, a_fields, b_fields)?
             .unify_neutral(&**a_more, &**b_more),
-        (Row(a_kind, a_fields, a_more), Row(b_kind, b_fields, b_more))
-            if a_kind == b_kind && a_fields.len() == b_fields.len() =>
+        (Snd(a), Snd(b)) | (Fst(a), Fst(b)) => tcs.unify_neutral(&**a, &**b),
+        (Proj(a, lab_a), Proj(b, lab_b)) | (Remove(a, lab_a), Remove(b, lab_b))
+            if lab_a == lab_b =>
         {
-            tcs.unify_variants(*a_kind, a_fields, b_fields)?
-                .unify_neutral(&**a_more, &**b_more)
+            tcs.unify_neutral(a, b)
         }
-        (Snd(a), Snd(b)) | (Fst(a), Fst(b)) => tcs.unify_neutral(&**a, &**b),
-        (Proj(a, lab_a), Proj(b, lab_b)) if lab_a == lab_b => tcs.unify_neutral(&**a, &**b),
-        (SplitOn(split_a, a), SplitOn(split_b, b)) | (OrSplit(split_a, a), OrSplit(split_b, b)) => {
-            tcs.unify_case_split(split_a, split_b)?
-                .unify_neutral(&**a, &**b)
+        (SplitOn(split_a, a), SplitOn(split_b, b)) => {
+            tcs.unify_case_split(split_a, split_b)?.unify_neutral(a, b)
+        }
+        (OrSplit(split_a, a), OrSplit(split_b, b)) => {
+            tcs.unify_case_split(split_a, split_b)?.unify(a, b)
         }
+        (LabProj(a, lab_a), LabProj(b, lab_b))
+        | (LabCons(lab_a, a), LabCons(lab_b, b))
+        | (LabKind(lab_a, a), LabKind(lab_b, b)) => tcs.unify_neutral(lab_a, lab_b)?.unify(a, b),
+        (LabRow(a_kind, lab_a, a_ty, a_rest), LabRow(b_kind, lab_b, b_ty, b_rest))
+            if a_kind == b_kind =>
+        {
+            tcs.unify_neutral(lab_a, lab_b)?
+                .unify(a_ty, b_ty)?
+                .unify(a_rest, b_rest)
+        }
+        (RowMap(f, a), RowMap(g, b)) => tcs.unify(f, g)?.unify_neutral(a, b),
+        (RowDiff(a, lab_a), RowDiff(b, lab_b)) if lab_a == lab_b => tcs.unify_neutral(a, b),
+        (J(a_refl, a), J(b_refl, b)) => tcs.unify_neutral(a, b)?.unify(a_refl, b_refl),
+        (Suc(a_k, a), Suc(b_k, b)) if a_k == b_k => tcs.unify_neutral(a, b),
+        (NatOp(a_op, a0, a1), NatOp(b_op, b0, b1)) if a_op == b_op => {
+            tcs.unify(a0, b0)?.unify(a1, b1)
+        }
+        (NatElim(a_zero, a_step, a), NatElim(b_zero, b_step, b)) => tcs
+            .unify_neutral(a, b)?
+            .unify(a_zero, b_zero)?
+            .unify(a_step, b_step),
         (e, t) => Err(TCE::CannotUnify(Val::Neut(e.clone()), Val::Neut(t.clone()))),
     }
 }
 
 /// So you can do some functional programming based on method call chains.
 impl TCS {
+    /// Postpone the equation `a = b`, until some meta gets solved.
+    pub fn postpone(mut self, a: Val, b: Val) -> Self {
+        self.constraints.push((a, b, None));
+        self
+    }
+
+    /// The equations postponed without a location are from the expression at `loc`.
+    pub fn locate_constraints(mut self, loc: Loc) -> Self {
+        for (_, _, located) in &mut self.constraints {
+            located.get_or_insert(loc);
+        }
+        self
+    }
+
+    /// Print `val` with the local variables replaced by their names.
+    fn show_with_names(&self, val: &Val) -> String {
+        let mut axioms = Vec::new();
+        val.clone().map_axiom(&mut |axiom| {
+            axioms.push(axiom);
+            Neutral::Axi(axiom)
+        });
+        let mut shown = val.to_string();
+        for axiom in axioms {
+            if let Some(name) = self.local_names.get(&axiom.unique_id()) {
+                shown = shown.replace(&axiom.to_string(), name);
+            }
+        }
+        shown
+    }
+
+    #[inline]
+    pub fn retry_constraints(self) -> TCM {
+        retry_constraints(self)
+    }
+
+    /// Retry the postponed constraints for the last time,
+    /// reporting the first one still stuck.
+    pub fn solve_constraints(self) -> TCM {
+        let tcs = self.retry_constraints()?;
+        match tcs.constraints.first() {
+            Some((a, b, loc)) => Err(TCE::UnsolvedConstraint(
+                loc.unwrap_or_default(),
+                tcs.show_with_names(a),
+                tcs.show_with_names(b),
+            )),
+            None => Ok(tcs),
+        }
+    }
+
     #[inline]
     pub fn unify(self, a: &Val, b: &Val) -> TCM {
         unify(self, a, b)
     }
 
+    #[inline]
+    pub fn unify_level(self, a: &Level, b: &Level) -> TCM {
+        unify_level(self, a, b)
+    }
+
+    #[inline]
+    pub fn subtype_level(self, a: &Level, b: &Level) -> TCM {
+        subtype_level(self, a, b)
+    }
+
     #[inline]
     fn unify_neutral(self, a: &Neutral, b: &Neutral) -> TCM {
         unify_neutral(self, a, b)
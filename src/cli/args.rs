@@ -29,6 +29,11 @@ pub struct CliOptions {
     #[structopt(short = "q", long)]
     pub quiet: bool,
 
+    /// Adds a directory to the module search path
+    /// (the directory of the input file is always searched first)
+    #[structopt(short = "I", long = "include", name = "DIR", number_of_values = 1)]
+    pub include: Vec<String>,
+
     /// Evaluates a standalone expression
     #[structopt(short = "e", long, name = "expression")]
     pub evaluate: Option<String>,
@@ -1,8 +1,10 @@
 extern crate voile;
 
+use std::path::PathBuf;
+
 use voile::check::check_decls;
 use voile::check::monad::TCS;
-use voile::syntax::abs::trans_decls_contextual;
+use voile::syntax::abs::{trans_decls_contextual, TransState};
 
 use crate::repl::code_to_abs;
 use minitt_util::repl::ReplEnvType;
@@ -14,6 +16,18 @@ mod util;
 fn main() {
     let args = args::pre();
 
+    let mut search_path = Vec::with_capacity(args.include.len() + 1);
+    search_path.push(util::file_directory(args.file.as_ref()));
+    search_path.extend(args.include.iter().map(PathBuf::from));
+    let trans_state = TransState {
+        search_path,
+        ..Default::default()
+    };
+    let trans_state = match util::file_module(args.file.as_ref()) {
+        Some(name) => trans_state.with_root_module(name),
+        None => trans_state,
+    };
+
     let mut checked = args
         .file
         .clone()
@@ -25,7 +39,7 @@ fn main() {
 
             if !args.parse_only {
                 // Translate to abstract syntax
-                let abs_decls = trans_decls_contextual(Default::default(), decls)
+                let abs_decls = trans_decls_contextual(trans_state.clone(), decls)
                     .map_err(|err| eprintln!("{}", err))
                     .unwrap_or_else(|()| {
                         eprintln!("Nou!");
@@ -36,12 +50,13 @@ fn main() {
                 let mut tcs = TCS::default();
                 tcs.meta_context
                     .expand_with_fresh_meta(abs_decls.meta_count);
-                let checked = check_decls(tcs, abs_decls.decls.clone())
+                let mut checked = check_decls(tcs, abs_decls.decls.clone())
                     .map_err(|err| eprintln!("{}", err))
                     .unwrap_or_else(|()| {
                         eprintln!("Change my mind!");
                         std::process::exit(1)
                     });
+                util::print_warnings(&mut checked);
 
                 if !args.quiet {
                     for (ty, val) in checked.gamma.iter().zip(checked.env.iter()) {
@@ -55,10 +70,10 @@ fn main() {
 
                 (checked, abs_decls)
             } else {
-                Default::default()
+                (Default::default(), trans_state.clone())
             }
         })
-        .unwrap_or_default();
+        .unwrap_or_else(|| (Default::default(), trans_state));
 
     if let Some(abs) = args
         .evaluate
@@ -10,7 +10,7 @@ use voile::syntax::surf::{parse_expr_err_printed, parse_str_err_printed, Decl};
 use voile_util::level::LiftEx;
 use voile_util::meta::MI;
 
-use crate::util::parse_file;
+use crate::util::{parse_file, print_warnings};
 
 type TCS = (TCMS, TransState);
 
@@ -119,6 +119,7 @@ fn expression_thing<T: Display>(
 
 fn update_tcs(tcs: TCS, decls: Vec<Decl>) -> TCS {
     let mut state = tcs.1;
+    let checked_count = state.decls.len();
     state.meta_count = MI(tcs.0.meta_context.solutions().len());
     let state = trans_decls_contextual(state, decls)
         .map_err(|err| eprintln!("{}", err))
@@ -127,9 +128,11 @@ fn update_tcs(tcs: TCS, decls: Vec<Decl>) -> TCS {
     telescope
         .meta_context
         .expand_with_fresh_meta(state.meta_count);
-    let tcs = check_decls(telescope, state.decls.clone())
+    // Only the newly translated declarations need to be checked
+    let mut tcs = check_decls(telescope, state.decls[checked_count..].to_vec())
         .map_err(|err| eprintln!("{}", err))
         .unwrap_or_default();
+    print_warnings(&mut tcs);
     (tcs, state)
 }
 
@@ -1,7 +1,9 @@
+use std::path::{Path, PathBuf};
 use std::str;
 
 use minitt_util::io::read_file;
 
+use voile::check::monad::TCS;
 use voile::syntax::surf::{parse_str_err_printed, Decl};
 
 pub fn parse_file(file_arg: &str) -> Option<Vec<Decl>> {
@@ -12,3 +14,25 @@ pub fn parse_file(file_arg: &str) -> Option<Vec<Decl>> {
     // Parse
     parse_str_err_printed(file_content_utf8).ok()
 }
+
+/// The module name of the file, which is its name without the extension.
+pub fn file_module(file_arg: Option<&String>) -> Option<String> {
+    let stem = Path::new(file_arg?).file_stem()?;
+    Some(stem.to_string_lossy().into_owned())
+}
+
+/// The directory containing the file, where its imports are looked up.
+pub fn file_directory(file_arg: Option<&String>) -> PathBuf {
+    file_arg
+        .and_then(|file| Path::new(file).parent())
+        .map(Path::to_path_buf)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Report (and forget) the warnings produced by type-checking.
+pub fn print_warnings(tcs: &mut TCS) {
+    for warning in tcs.warnings.drain(..) {
+        eprintln!("Warning: {}", warning);
+    }
+}
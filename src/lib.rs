@@ -80,10 +80,34 @@ $$
 &\texttt{getX}&&:\forall A: \T \rarr A \\\\ \space
 &\texttt{getX}&&=\lambda s. (s.\xx) \\\\ \space
 &\texttt{setX}&&:\forall A: \T \rarr A \rarr \T \\\\ \space
-&\texttt{setX}&&= [\text{syntax undecided yet}]
+&\texttt{setX}&&=\lambda s\ a. \\{| s\ \texttt{with}\ \xx = a |\\}
 \end{alignedat}
 $$
 
+The row variable $r$ can be left anonymous, it becomes an implicit parameter:
+
+```text
+val getX : {A : Type} -> Rec { X: A; ... } -> A;
+let getX = \s. s.X;
+```
+
+Labels are first-class as well: they have type `Label`, with literals like `'X`.
+A local variable of type `Label` can be used as a field or a constructor:
+
+```text
+val get : (l : Label) -> {A : Type} -> {r : Rec [l]} -> Rec { l: A; ... = r } -> A;
+let get = \l s. s.l;
+```
+
+Rows can be transformed by type operators, like mapping a type function over them,
+concatenating two disjoint rows or subtracting some labels:
+
+```text
+val partial : Maybe <$> Rec { x: A; y: B; };
+val xyz : Rec { x: A; y: B; } ++ Rec { z: C; };
+val only_x : Rec { x: A; y: B; } -- [y];
+```
+
 Existing row-polymorphism implementation divides into two groups
 according to how they support such generalization,
 either by qualifying records/variants with constraints,
@@ -141,6 +165,51 @@ let not = notTrue (notFalse whatever);
 
 Extensible variant type is also useful for simulating exception-handling.
 
+Recursive types are introduced by `Fix` (or `μ`), where the recursive
+occurrences have to be strictly positive.
+Their values are wrapped with `fold` and unwrapped with `unfold`,
+while case-splits unfold the scrutinee on their own.
+A recursive type extended with more constructors can reuse the clauses
+written for the original constructors via a default clause:
+
+```text
+val Expr : Type;
+let Expr = Fix E. Sum { Lit: Nat; Add: E * E; Double: E; };
+
+val eval : Expr -> Nat;
+let eval = case Double e: add (eval e) (eval e)
+  or case _ rest: evalArith eval rest;
+```
+
+Data type declarations are syntactic sugar for a (parameterized) `Sum` type
+and a function for each constructor, taking the parameters implicitly:
+
+```text
+data Maybe (A : Type) = Nothing | Just A;
+
+val fromMaybe : {A : Type} -> A -> Maybe A -> A;
+let fromMaybe = \a. case Nothing u: a or case Just x: x or whatever;
+```
+
+Identity types `a = b` are proved by `refl`, and eliminated by `J`,
+whose motive binds the other end of the equation and the proof.
+`J` reduces to its last argument when the proof is `refl`:
+
+```text
+val sym : {A : Type} -> {a : A} -> {b : A} -> a = b -> b = a;
+let sym = \{A a b} p. J p as y q return y = a with refl;
+```
+
+`Nat` is a primitive type of natural numbers, with decimal literals and
+the operators `+`, `-` (truncated at zero), `×`, `<` and `<=`.
+They compute on literals, and `natElim` binds the predecessor and
+the result for it in its step:
+
+```text
+val double : Nat -> Nat;
+let double = \n. natElim n as m return Nat with 0 or k r. r + 2;
+```
+
  [rec-calc]: https://dl.acm.org/citation.cfm?id=218572
  [ext-rec]: https://wiki.haskell.org/Extensible_record
  [row-poly]: https://en.wikipedia.org/wiki/Row_polymorphism
@@ -1,11 +1,56 @@
+use num_bigint::BigUint;
 use voile_util::level::Level;
 use voile_util::loc::*;
 use voile_util::meta::MI;
 use voile_util::tags::*;
 use voile_util::uid::*;
 
+use crate::syntax::surf::Pragma;
+
 pub type LabAbs = Labelled<Abs>;
 
+/// Patterns of pattern-matching lambdas, corresponds to
+/// [Pattern](crate::syntax::surf::Pattern).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Pat {
+    /// Pattern variable, with its unique id
+    Var(Ident, UID),
+    Wildcard(Loc),
+    /// Constructor pattern, the label and the pattern of the argument
+    Cons(Ident, Box<Self>),
+    Pair(Box<Self>, Box<Self>),
+    /// Record pattern, the other fields are ignored
+    Rec(Loc, Vec<Labelled<Self>>),
+}
+
+impl Pat {
+    /// The pattern variables from left to right, which is
+    /// the order they are introduced in the body of the clause.
+    pub fn vars(&self) -> Vec<UID> {
+        match self {
+            Pat::Var(_, uid) => vec![*uid],
+            Pat::Wildcard(..) => vec![],
+            Pat::Cons(_, pattern) => pattern.vars(),
+            Pat::Pair(first, second) => {
+                let mut vars = first.vars();
+                vars.append(&mut second.vars());
+                vars
+            }
+            Pat::Rec(_, fields) => fields.iter().flat_map(|f| f.expr.vars()).collect(),
+        }
+    }
+}
+
+impl ToLoc for Pat {
+    fn loc(&self) -> Loc {
+        match self {
+            Pat::Var(ident, ..) | Pat::Cons(ident, ..) => ident.loc,
+            Pat::Wildcard(info) | Pat::Rec(info, ..) => *info,
+            Pat::Pair(first, second) => merge_info(&**first, &**second),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Abs {
     Type(Loc, Level),
@@ -19,28 +64,109 @@ pub enum Abs {
     Lift(Loc, u32, Box<Self>),
     /// Constructor call
     Cons(Ident),
-    /// Record projection
-    Proj(Loc, Box<Self>, Ident),
+    /// Record projection, with the local variable named like the field (if any),
+    /// which must not be a label variable.
+    Proj(Loc, Box<Self>, Ident, Option<Box<Self>>),
     /// Apply or Pipeline in surface
     App(Loc, Box<Self>, Plicit, Box<Self>),
     /// Dependent Type, `(a -> b -> c)` as `Dt(_, DtKind::Pi, _, _, a, Dt(_, DtKind::Pi, _, _, b, c))`
     Dt(Loc, PiSig, UID, Plicit, Box<Self>, Box<Self>),
     /// The first `Loc` is the syntax info of this whole lambda,
-    /// while the second is about its parameter
-    Lam(Loc, Ident, UID, Box<Self>),
+    /// while the second is about its parameter.
+    /// Implicit lambdas are erased after type-checking
+    Lam(Loc, Ident, UID, Plicit, Box<Self>),
+    /// Recursive types, the name of the type itself and its unique id, body
+    Fix(Loc, Ident, UID, Box<Self>),
+    /// Wrapping a value of the unfolded recursive type, erased after type-checking
+    Fold(Loc, Box<Self>),
+    /// Unwrapping a value of a recursive type, erased after type-checking
+    Unfold(Loc, Box<Self>),
+    /// Type ascription, the expression and its type
+    Ascription(Loc, Box<Self>, Box<Self>),
+    /// Local definition, corresponds to [Let](crate::syntax::surf::Expr::Let).
+    /// Name, its unique id, optional type, definition, body.
+    Let(Loc, Ident, UID, Option<Box<Self>>, Box<Self>, Box<Self>),
     Pair(Loc, Box<Self>, Box<Self>),
     Fst(Loc, Box<Self>),
     Snd(Loc, Box<Self>),
     /// Row-polymorphic types, corresponds to [RowPoly](crate::syntax::surf::Expr::RowPoly)
     RowPoly(Loc, VarRec, Vec<LabAbs>, Option<Box<Self>>),
+    /// Dependent record types, each field binds a local variable (with the `UID`)
+    /// in the types of the subsequent fields.
+    DepRec(Loc, Vec<(LabAbs, UID)>),
     /// Record literals
     Rec(Loc, Vec<LabAbs>, Option<Box<Self>>),
+    /// Record update, corresponds to [Update](crate::syntax::surf::Expr::Update)
+    Update(Loc, Box<Self>, Vec<LabAbs>),
+    /// Record field removal
+    Remove(Loc, Box<Self>, Ident),
+    /// Record field renaming, from the first `Ident` to the second
+    Rename(Loc, Box<Self>, Ident, Ident),
+    /// Pattern-matching lambda, the unique id identifies its compiled case tree
+    PatLam(Loc, UID, Vec<(Pat, Abs)>),
     /// Empty type eliminator,
     Whatever(Loc),
     /// Case-split expressions.
     CaseOr(Ident, Ident, UID, Box<Self>, Box<Self>),
+    /// Default clause of a case-split, the binding and its unique id, body.
+    CaseDefault(Loc, Ident, UID, Box<Self>),
+    /// Case-split applied to a scrutinee, corresponds to [Match](crate::syntax::surf::Expr::Match).
+    /// Scrutinee, the binding of the motive and its unique id, motive, case-split.
+    Match(Loc, Box<Self>, Ident, UID, Box<Self>, Box<Self>),
+    /// Identity type, corresponds to [Eq](crate::syntax::surf::Expr::Eq).
+    Eq(Loc, Box<Self>, Box<Self>),
+    /// The proof of reflexivity
+    Refl(Loc),
+    /// Eliminator of identity types, corresponds to [J](crate::syntax::surf::Expr::J).
+    /// Proof, the bindings of the motive and their unique ids, motive, the value for `refl`.
+    J(
+        Loc,
+        Box<Self>,
+        (Ident, UID),
+        (Ident, UID),
+        Box<Self>,
+        Box<Self>,
+    ),
+    /// The type of natural numbers
+    NatType(Loc),
+    /// Natural number literal
+    Nat(Loc, BigUint),
+    /// Primitive operation on natural numbers, like `a + b`
+    NatOp(Loc, NatOp, Box<Self>, Box<Self>),
+    /// Eliminator of natural numbers, corresponds to [NatElim](crate::syntax::surf::Expr::NatElim).
+    /// Natural number, the binding of the motive and its unique id, motive, the value for zero,
+    /// the bindings of the step and their unique ids, step.
+    NatElim(
+        Loc,
+        Box<Self>,
+        (Ident, UID),
+        Box<Self>,
+        Box<Self>,
+        (Ident, UID),
+        (Ident, UID),
+        Box<Self>,
+    ),
     /// Row-polymorphic kinds, corresponds to [RowKind](crate::syntax::surf::Expr::RowKind)
     RowKind(Loc, VarRec, Vec<Ident>),
+    /// The type of labels
+    LabelType(Loc),
+    /// Label literal
+    Label(Ident),
+    /// Record projection, where the label is a local variable
+    LabProj(Loc, Box<Self>, Box<Self>),
+    /// Constructor call, where the label is a local variable
+    LabCons(Loc, Box<Self>),
+    /// Row-polymorphic type with one entry labelled by a local variable,
+    /// the label, the type of the entry and the rest of the row
+    LabRow(Loc, VarRec, Box<Self>, Box<Self>, Option<Box<Self>>),
+    /// Row-polymorphic kind lacking a local label variable, and the other labels
+    LabKind(Loc, Box<Self>, Box<Self>),
+    /// Row concatenation, corresponds to [RowConcat](crate::syntax::surf::Expr::RowConcat)
+    RowConcat(Loc, Box<Self>, Box<Self>),
+    /// Row map, the type function and the row
+    RowMap(Loc, Box<Self>, Box<Self>),
+    /// Row difference, the row and the subtracted labels
+    RowDiff(Loc, Box<Self>, Vec<Ident>),
 }
 
 impl ToLoc for Abs {
@@ -54,15 +180,44 @@ impl ToLoc for Abs {
             | Abs::Snd(info, ..)
             | Abs::RowPoly(info, ..)
             | Abs::Rec(info, ..)
+            | Abs::DepRec(info, ..)
+            | Abs::Update(info, ..)
+            | Abs::Remove(info, ..)
+            | Abs::Rename(info, ..)
             | Abs::Proj(info, ..)
             | Abs::RowKind(info, ..)
+            | Abs::LabelType(info)
+            | Abs::LabProj(info, ..)
+            | Abs::LabCons(info, ..)
+            | Abs::LabRow(info, ..)
+            | Abs::LabKind(info, ..)
+            | Abs::RowConcat(info, ..)
+            | Abs::RowMap(info, ..)
+            | Abs::RowDiff(info, ..)
             | Abs::Lift(info, ..)
             | Abs::Whatever(info)
+            | Abs::CaseDefault(info, ..)
+            | Abs::Let(info, ..)
+            | Abs::Match(info, ..)
+            | Abs::Eq(info, ..)
+            | Abs::Refl(info)
+            | Abs::J(info, ..)
+            | Abs::NatType(info)
+            | Abs::Nat(info, ..)
+            | Abs::NatOp(info, ..)
+            | Abs::NatElim(info, ..)
+            | Abs::PatLam(info, ..)
+            | Abs::Ascription(info, ..)
+            | Abs::Fix(info, ..)
+            | Abs::Fold(info, ..)
+            | Abs::Unfold(info, ..)
             | Abs::Lam(info, ..) => (*info).clone(),
             Abs::CaseOr(ident, _, _, _, last) => merge_info(ident, &**last),
-            Abs::Var(ident, ..) | Abs::Ref(ident, ..) | Abs::Meta(ident, ..) | Abs::Cons(ident) => {
-                ident.loc.clone()
-            }
+            Abs::Var(ident, ..)
+            | Abs::Ref(ident, ..)
+            | Abs::Meta(ident, ..)
+            | Abs::Cons(ident)
+            | Abs::Label(ident) => ident.loc,
         }
     }
 }
@@ -92,12 +247,58 @@ impl Abs {
         Abs::Rec(info, fields, rest.map(Box::new))
     }
 
+    pub fn update(info: Loc, record: Self, fields: Vec<LabAbs>) -> Self {
+        Abs::Update(info, Box::new(record), fields)
+    }
+
+    pub fn remove(info: Loc, record: Self, field: Ident) -> Self {
+        Abs::Remove(info, Box::new(record), field)
+    }
+
+    pub fn rename(info: Loc, record: Self, from: Ident, to: Ident) -> Self {
+        Abs::Rename(info, Box::new(record), from, to)
+    }
+
     pub fn app(info: Loc, function: Self, plicit: Plicit, argument: Self) -> Self {
         Abs::App(info, Box::new(function), plicit, Box::new(argument))
     }
 
-    pub fn proj(info: Loc, record: Self, field: Ident) -> Self {
-        Abs::Proj(info, Box::new(record), field)
+    pub fn proj(info: Loc, record: Self, field: Ident, local: Option<Self>) -> Self {
+        Abs::Proj(info, Box::new(record), field, local.map(Box::new))
+    }
+
+    pub fn label_proj(info: Loc, record: Self, label: Self) -> Self {
+        Abs::LabProj(info, Box::new(record), Box::new(label))
+    }
+
+    pub fn label_cons(info: Loc, label: Self) -> Self {
+        Abs::LabCons(info, Box::new(label))
+    }
+
+    pub fn label_row(info: Loc, kind: VarRec, label: Self, ty: Self, rest: Option<Self>) -> Self {
+        Abs::LabRow(
+            info,
+            kind,
+            Box::new(label),
+            Box::new(ty),
+            rest.map(Box::new),
+        )
+    }
+
+    pub fn label_kind(info: Loc, label: Self, rest: Self) -> Self {
+        Abs::LabKind(info, Box::new(label), Box::new(rest))
+    }
+
+    pub fn row_concat(info: Loc, left: Self, right: Self) -> Self {
+        Abs::RowConcat(info, Box::new(left), Box::new(right))
+    }
+
+    pub fn row_map(info: Loc, function: Self, row: Self) -> Self {
+        Abs::RowMap(info, Box::new(function), Box::new(row))
+    }
+
+    pub fn row_diff(info: Loc, row: Self, labels: Vec<Ident>) -> Self {
+        Abs::RowDiff(info, Box::new(row), labels)
     }
 
     pub fn fst(info: Loc, of: Self) -> Self {
@@ -108,8 +309,24 @@ impl Abs {
         Abs::Snd(info, Box::new(of))
     }
 
-    pub fn lam(whole_info: Loc, param: Ident, name: UID, body: Self) -> Self {
-        Abs::Lam(whole_info, param, name, Box::new(body))
+    pub fn lam(whole_info: Loc, param: Ident, name: UID, plicit: Plicit, body: Self) -> Self {
+        Abs::Lam(whole_info, param, name, plicit, Box::new(body))
+    }
+
+    pub fn ascription(info: Loc, expr: Self, ty: Self) -> Self {
+        Abs::Ascription(info, Box::new(expr), Box::new(ty))
+    }
+
+    pub fn let_in(
+        info: Loc,
+        name: Ident,
+        uid: UID,
+        ty: Option<Self>,
+        def: Self,
+        body: Self,
+    ) -> Self {
+        let ty = ty.map(Box::new);
+        Abs::Let(info, name, uid, ty, Box::new(def), Box::new(body))
     }
 
     pub fn pair(info: Loc, first: Self, second: Self) -> Self {
@@ -120,6 +337,76 @@ impl Abs {
         Abs::CaseOr(label, binding, uid, Box::new(clause), Box::new(or))
     }
 
+    pub fn fix(info: Loc, name: Ident, uid: UID, body: Self) -> Self {
+        Abs::Fix(info, name, uid, Box::new(body))
+    }
+
+    pub fn fold(info: Loc, expr: Self) -> Self {
+        Abs::Fold(info, Box::new(expr))
+    }
+
+    pub fn unfold(info: Loc, expr: Self) -> Self {
+        Abs::Unfold(info, Box::new(expr))
+    }
+
+    pub fn case_default(info: Loc, binding: Ident, uid: UID, body: Self) -> Self {
+        Abs::CaseDefault(info, binding, uid, Box::new(body))
+    }
+
+    pub fn match_on(
+        info: Loc,
+        scrutinee: Self,
+        binding: Ident,
+        uid: UID,
+        motive: Self,
+        cases: Self,
+    ) -> Self {
+        let (scrutinee, motive) = (Box::new(scrutinee), Box::new(motive));
+        Abs::Match(info, scrutinee, binding, uid, motive, Box::new(cases))
+    }
+
+    pub fn eq(info: Loc, a: Self, b: Self) -> Self {
+        Abs::Eq(info, Box::new(a), Box::new(b))
+    }
+
+    pub fn j(
+        info: Loc,
+        proof: Self,
+        end: (Ident, UID),
+        eq: (Ident, UID),
+        motive: Self,
+        refl: Self,
+    ) -> Self {
+        let (proof, motive) = (Box::new(proof), Box::new(motive));
+        Abs::J(info, proof, end, eq, motive, Box::new(refl))
+    }
+
+    pub fn nat_op(info: Loc, op: NatOp, a: Self, b: Self) -> Self {
+        Abs::NatOp(info, op, Box::new(a), Box::new(b))
+    }
+
+    pub fn nat_elim(
+        info: Loc,
+        nat: Self,
+        binding: (Ident, UID),
+        motive: Self,
+        zero: Self,
+        (pred, result): ((Ident, UID), (Ident, UID)),
+        step: Self,
+    ) -> Self {
+        let (nat, motive, zero) = (Box::new(nat), Box::new(motive), Box::new(zero));
+        Abs::NatElim(
+            info,
+            nat,
+            binding,
+            motive,
+            zero,
+            pred,
+            result,
+            Box::new(step),
+        )
+    }
+
     pub fn lift(info: Loc, lift_count: u32, expr: Self) -> Self {
         Abs::Lift(info, lift_count, Box::new(expr))
     }
@@ -137,12 +424,12 @@ impl Abs {
 /// with abstract syntax.
 #[derive(Debug, Clone)]
 pub enum AbsDecl {
-    /// Signature.
-    Sign(Abs, GI),
+    /// Signature, and the name of the definition.
+    Sign(Abs, GI, Ident),
     /// Function body without a signature.
     Decl(Abs),
-    /// Function body with a signature.
-    Impl(Abs, GI),
+    /// Function body with a signature, and the pragmas attached to it.
+    Impl(Abs, GI, Vec<Pragma>),
 }
 
 impl ToLoc for AbsDecl {
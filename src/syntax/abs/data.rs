@@ -0,0 +1,165 @@
+use voile_util::level::Level;
+use voile_util::loc::*;
+use voile_util::tags::Plicit;
+
+use crate::check::monad::{TCE, TCM};
+use crate::syntax::surf::{DataCons, DataDecl, DeclKind, Expr, LabExpr, NamedDecl, Param};
+
+use super::trans::{trans_named_decl, TransState};
+
+/// Expand a data type declaration, like `data Maybe (A : Type) = Nothing | Just A;`,
+/// into the signatures and the implementations of the type and the constructors:
+///
+/// ```text
+/// val Maybe : (A : Type) -> Type;
+/// let Maybe = \A. Sum { Nothing: Rec {}; Just: A; };
+/// val Nothing : {A : Type} -> Maybe A;
+/// let Nothing = \{A}. @Nothing {| |};
+/// val Just : {A : Type} -> A -> Maybe A;
+/// let Just = \{A} a. @Just a;
+/// ```
+///
+/// The fields of a constructor are packed into a tuple.
+/// Indexed families, whose types have indices like `data Vec (A : Type) : Nat -> Type`,
+/// are rejected since the constructors cannot refine the indices.
+pub(super) fn trans_data(tcs: TransState, data: DataDecl) -> TCM<TransState> {
+    let DataDecl {
+        name,
+        params,
+        ty,
+        conses,
+    } = data;
+    let universe = match ty {
+        Some(Expr::Pi(..)) => return Err(TCE::IndexedData(name)),
+        Some(universe) => universe,
+        None => Expr::Type(name.loc, Level::Num(0)),
+    };
+    let binders: Vec<(Ident, Plicit)> = (params.iter())
+        .flat_map(|param| param.names.iter().map(move |n| (n.clone(), param.plicit)))
+        .collect();
+
+    let variants = (conses.iter())
+        .map(|cons| LabExpr {
+            label: cons.name.clone(),
+            expr: fields_type(cons),
+        })
+        .collect();
+    let sum = Expr::sum(name.loc, variants, None);
+    let signature = pi_or_not(params.clone(), universe);
+    let body = lam_or_not(name.loc, binders.clone(), sum);
+    let mut tcs = trans_data_decl(tcs, &name, signature, body)?;
+
+    let arguments = (binders.iter())
+        .map(|(binder, plicit)| match plicit {
+            Plicit::Ex => Expr::Var(binder.clone()),
+            Plicit::Im => Expr::implicit(binder.loc, Expr::Var(binder.clone())),
+        })
+        .collect::<Vec<_>>();
+    let applied = if arguments.is_empty() {
+        Expr::Var(name.clone())
+    } else {
+        Expr::app(Expr::Var(name.clone()), arguments)
+    };
+    for cons in conses {
+        let implicits = (params.iter()).map(|param| Param {
+            plicit: Plicit::Im,
+            ..param.clone()
+        });
+        let fields = (cons.fields.iter()).map(|field| Param {
+            plicit: Plicit::Ex,
+            names: vec![],
+            ty: field.clone(),
+        });
+        let signature = pi_or_not(implicits.chain(fields).collect(), applied.clone());
+        let vars = field_names(&cons);
+        let cons_binders = (binders.iter())
+            .map(|(binder, _)| (binder.clone(), Plicit::Im))
+            .chain(vars.iter().map(|var| (var.clone(), Plicit::Ex)))
+            .collect();
+        let label = Ident {
+            text: format!("@{}", cons.name.text),
+            loc: cons.name.loc,
+        };
+        let body = Expr::app(Expr::Cons(label), vec![fields_value(cons.name.loc, vars)]);
+        let body = lam_or_not(cons.name.loc, cons_binders, body);
+        tcs = trans_data_decl(tcs, &cons.name, signature, body)?;
+    }
+    Ok(tcs)
+}
+
+fn trans_data_decl(tcs: TransState, name: &Ident, signature: Expr, body: Expr) -> TCM<TransState> {
+    let decl = |body, kind| NamedDecl {
+        name: name.clone(),
+        body,
+        kind,
+        pragmas: vec![],
+    };
+    let tcs = trans_named_decl(tcs, decl(signature, DeclKind::Sign))?;
+    trans_named_decl(tcs, decl(body, DeclKind::Impl))
+}
+
+/// The names of the explicit parameters of the constructor, one for each field.
+fn field_names(cons: &DataCons) -> Vec<Ident> {
+    (0..cons.fields.len())
+        .map(|i| generated_name(cons.name.loc, i))
+        .collect()
+}
+
+fn generated_name(loc: Loc, i: usize) -> Ident {
+    let text = format!("_{}", i);
+    Ident { loc, text }
+}
+
+/// `Rec {}` for no fields, the field itself for one field, and a sigma type for more.
+fn fields_type(cons: &DataCons) -> Expr {
+    let mut fields = cons.fields.clone();
+    match fields.pop() {
+        None => Expr::rec(cons.name.loc, vec![], None),
+        Some(last) => {
+            let params = (fields.into_iter())
+                .map(|field| Param {
+                    plicit: Plicit::Ex,
+                    names: vec![],
+                    ty: field,
+                })
+                .collect::<Vec<_>>();
+            sig_or_not(params, last)
+        }
+    }
+}
+
+/// The value of type [`fields_type`](fields_type), made of the variables.
+fn fields_value(loc: Loc, mut vars: Vec<Ident>) -> Expr {
+    match vars.len() {
+        0 => Expr::record(loc, vec![], None),
+        1 => Expr::Var(vars.remove(0)),
+        _ => {
+            let first = Expr::Var(vars.remove(0));
+            Expr::tup(first, vars.into_iter().map(Expr::Var).collect())
+        }
+    }
+}
+
+fn pi_or_not(params: Vec<Param>, ret: Expr) -> Expr {
+    if params.is_empty() {
+        ret
+    } else {
+        Expr::pi(params, ret)
+    }
+}
+
+fn sig_or_not(params: Vec<Param>, last: Expr) -> Expr {
+    if params.is_empty() {
+        last
+    } else {
+        Expr::sig(params, last)
+    }
+}
+
+fn lam_or_not(loc: Loc, binders: Vec<(Ident, Plicit)>, body: Expr) -> Expr {
+    if binders.is_empty() {
+        body
+    } else {
+        Expr::lam(loc, binders, body)
+    }
+}
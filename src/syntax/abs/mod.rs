@@ -14,5 +14,11 @@ mod trans;
 /// Pretty-print AST.
 mod pretty;
 
+/// Module system: resolving and translating imported modules.
+mod module;
+
+/// Data types: expanding data type declarations to named declarations.
+mod data;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,86 @@
+use std::fs::read_to_string;
+use std::mem::{replace, take};
+use std::path::{Path, PathBuf};
+
+use voile_util::loc::*;
+
+use crate::check::monad::{TCE, TCM};
+use crate::syntax::surf::parse_str;
+
+use super::trans::{trans_decls_contextual, GlobCtx, TransState};
+
+/// Make the globals of the imported module visible, under both
+/// their qualified names (like `Data.Bool.not`) and unqualified names.
+/// Two modules exporting the same unqualified name cannot be both imported.
+///
+/// The module is translated only for the first time it's imported,
+/// so its declarations (appended to `tcs.decls`) are checked only once.
+pub(super) fn trans_import(mut tcs: TransState, path: Ident) -> TCM<TransState> {
+    let exports = match tcs.modules.get(&path.text) {
+        Some(Some(exports)) => exports.clone(),
+        Some(None) => return Err(TCE::CyclicImport(path, tcs.importing)),
+        None => {
+            let (new_tcs, exports) = load_module(tcs, &path)?;
+            tcs = new_tcs;
+            exports
+        }
+    };
+    for (name, gi) in exports {
+        if let Some(defined) = tcs.exports.get(&name) {
+            let defined = &tcs.decls[tcs.signature_indices[defined.0].0];
+            return Err(TCE::ReDefine(path.loc, defined.loc()));
+        }
+        // Importing the same module twice brings the same globals.
+        if let Some(module) = tcs.imported.get(&name) {
+            if tcs.context_mapping.get(&name) != Some(&gi) {
+                return Err(TCE::AmbiguousImport(path, name, module.clone()));
+            }
+        }
+        let qualified = format!("{}.{}", path.text, name);
+        tcs.context_mapping.insert(qualified, gi);
+        tcs.imported.insert(name.clone(), path.text.clone());
+        tcs.context_mapping.insert(name, gi);
+    }
+    Ok(tcs)
+}
+
+fn load_module(mut tcs: TransState, path: &Ident) -> TCM<(TransState, GlobCtx)> {
+    let file = module_file(&tcs.search_path, &path.text)
+        .ok_or_else(|| TCE::ModuleNotFound(path.clone()))?;
+    let code = read_to_string(&file)
+        .map_err(|err| TCE::ModuleUnreadable(path.clone(), err.to_string()))?;
+    let decls = parse_str(&code).map_err(|err| TCE::ModuleUnreadable(path.clone(), err))?;
+
+    tcs.modules.insert(path.text.clone(), None);
+    tcs.importing.push(path.text.clone());
+    // The imported module has its own scope, it cannot see the importer's globals.
+    let importer_mapping = take(&mut tcs.context_mapping);
+    let importer_exports = take(&mut tcs.exports);
+    let importer_imported = take(&mut tcs.imported);
+    let mut tcs = trans_decls_contextual(tcs, decls)?;
+    tcs.context_mapping = importer_mapping;
+    tcs.imported = importer_imported;
+    tcs.importing.pop();
+    let exports = replace(&mut tcs.exports, importer_exports);
+    tcs.modules.insert(path.text.clone(), Some(exports.clone()));
+    Ok((tcs, exports))
+}
+
+impl TransState {
+    /// Register the module of the entry file (named like `Main` for `Main.voile`)
+    /// before translating it, so importing it back is reported as a cycle.
+    pub fn with_root_module(mut self, name: String) -> Self {
+        self.modules.insert(name.clone(), None);
+        self.importing.push(name);
+        self
+    }
+}
+
+/// `Data.Bool` is looked up as `Data/Bool.voile` in each directory of the search path.
+fn module_file(search_path: &[PathBuf], path: &str) -> Option<PathBuf> {
+    let relative: PathBuf = path.split('.').collect();
+    search_path
+        .iter()
+        .map(|dir| dir.join(&relative).with_extension("voile"))
+        .find(|file| Path::is_file(file))
+}
@@ -3,7 +3,7 @@ use std::fmt::{Display, Error, Formatter};
 use voile_util::tags::{PiSig, Plicit};
 use PiSig::*;
 
-use super::{Abs, AbsDecl, LabAbs};
+use super::{Abs, AbsDecl, LabAbs, Pat};
 
 type MonadFmt = Result<(), Error>;
 
@@ -16,25 +16,77 @@ impl Display for Abs {
             Abs::Meta(_, mi) => write!(f, "?{:?}", mi),
             Abs::Cons(name) => write!(f, "@{}", name.text),
             Abs::Lift(_, levels, expr) => write!(f, "(^[{:?}] {})", levels, expr),
-            Abs::App(_, a, _, b) => write!(f, "({} {})", a, b),
+            Abs::App(_, a, Plicit::Ex, b) => write!(f, "({} {})", a, b),
+            Abs::App(_, a, Plicit::Im, b) => write!(f, "({} {{{}}})", a, b),
             Abs::Dt(_, Pi, name, Plicit::Ex, param, ret) => {
                 write!(f, "({:?} : {}) -> {}", name, param, ret)
             }
             Abs::Dt(_, Pi, name, Plicit::Im, param, ret) => {
                 write!(f, "{{{:?} : {}}} -> {}", name, param, ret)
             }
-            Abs::Dt(_, Sigma, name, _, fst, snd) => write!(f, "(<{:?}> : {}) * {}", name, fst, snd),
-            Abs::Lam(_, param, name, body) => write!(f, "(\\{}[{:?}]. {})", param.text, name, body),
+            Abs::Dt(_, Sigma, name, Plicit::Ex, fst, snd) => {
+                write!(f, "(<{:?}> : {}) * {}", name, fst, snd)
+            }
+            Abs::Dt(_, Sigma, name, Plicit::Im, fst, snd) => {
+                write!(f, "{{<{:?}> : {}}} * {}", name, fst, snd)
+            }
+            Abs::Lam(_, param, name, Plicit::Ex, body) => {
+                write!(f, "(\\{}[{:?}]. {})", param.text, name, body)
+            }
+            Abs::Lam(_, param, name, Plicit::Im, body) => {
+                write!(f, "(\\{{{}[{:?}]}}. {})", param.text, name, body)
+            }
+            Abs::Ascription(_, expr, ty) => write!(f, "({} : {})", expr, ty),
+            Abs::Fix(_, name, uid, body) => write!(f, "(Fix {}[{:?}]. {})", name.text, uid, body),
+            Abs::Fold(_, expr) => write!(f, "(fold {})", expr),
+            Abs::Unfold(_, expr) => write!(f, "(unfold {})", expr),
+            Abs::Let(_, name, uid, ty, def, body) => {
+                write!(f, "(let {}[{:?}]", name.text, uid)?;
+                if let Some(ty) = ty {
+                    write!(f, " : {}", ty)?;
+                }
+                write!(f, " = {} in {})", def, body)
+            }
             Abs::Pair(_, a, b) => write!(f, "({}, {})", a, b),
             Abs::Fst(_, p) => write!(f, "({}.1)", p),
             Abs::Snd(_, p) => write!(f, "({}.2)", p),
-            Abs::Proj(_, rec, field) => write!(f, "({}.{})", rec, field.text),
+            Abs::Proj(_, rec, field, _) => write!(f, "({}.{})", rec, field.text),
             Abs::Whatever(..) => f.write_str("whatever"),
+            Abs::PatLam(_, _, clauses) => {
+                f.write_str("(\\case { ")?;
+                for (pattern, body) in clauses {
+                    write!(f, "{} => {}; ", pattern, body)?;
+                }
+                f.write_str("})")
+            }
             Abs::CaseOr(label, binding, _, body, or) => write!(
                 f,
                 "(case {} {}: {} or {})",
                 label.text, binding.text, body, or
             ),
+            Abs::CaseDefault(_, binding, uid, body) => {
+                write!(f, "(case _ {}[{:?}]: {})", binding.text, uid, body)
+            }
+            Abs::Match(_, scrutinee, binding, uid, motive, cases) => write!(
+                f,
+                "(match {} as {}[{:?}] return {} with {})",
+                scrutinee, binding.text, uid, motive, cases
+            ),
+            Abs::Eq(_, a, b) => write!(f, "({} = {})", a, b),
+            Abs::Refl(..) => f.write_str("refl"),
+            Abs::J(_, proof, (end, end_uid), (eq, eq_uid), motive, refl) => write!(
+                f,
+                "(J {} as {}[{:?}] {}[{:?}] return {} with {})",
+                proof, end.text, end_uid, eq.text, eq_uid, motive, refl
+            ),
+            Abs::NatType(..) => f.write_str("Nat"),
+            Abs::Nat(_, n) => write!(f, "{}", n),
+            Abs::NatOp(_, op, a, b) => write!(f, "({} {} {})", a, op, b),
+            Abs::NatElim(_, nat, (m, m_uid), motive, zero, (k, k_uid), (r, r_uid), step) => write!(
+                f,
+                "(natElim {} as {}[{:?}] return {} with {} or {}[{:?}] {}[{:?}]. {})",
+                nat, m.text, m_uid, motive, zero, k.text, k_uid, r.text, r_uid, step
+            ),
             Abs::RowKind(_, kind, labels) => {
                 write!(f, "{} [ ", kind)?;
                 for ident in labels {
@@ -50,6 +102,13 @@ impl Display for Abs {
                     None => write!(f, "}}"),
                 }
             }
+            Abs::DepRec(_, fields) => {
+                f.write_str("DRec { ")?;
+                for (field, uid) in fields {
+                    write!(f, "{}[{:?}]: {}; ", field.label.text, uid, field.expr)?;
+                }
+                f.write_str("}")
+            }
             Abs::Rec(_, fields, rest) => {
                 f.write_str("{|")?;
                 pretty_labels(f, fields, " =")?;
@@ -58,6 +117,34 @@ impl Display for Abs {
                     None => f.write_str("|}"),
                 }
             }
+            Abs::Update(_, rec, fields) => {
+                write!(f, "{{| {} with ", rec)?;
+                pretty_labels(f, fields, " =")?;
+                f.write_str("|}")
+            }
+            Abs::LabelType(..) => f.write_str("Label"),
+            Abs::Label(label) => write!(f, "'{}", label.text),
+            Abs::LabProj(_, rec, label) => write!(f, "({}.{})", rec, label),
+            Abs::LabCons(_, label) => write!(f, "@{}", label),
+            Abs::LabRow(_, kind, label, ty, rest) => {
+                write!(f, "{} {{ {}: {}; ", kind, label, ty)?;
+                match rest {
+                    Some(rest) => write!(f, "... = {} }}", rest),
+                    None => write!(f, "}}"),
+                }
+            }
+            Abs::LabKind(_, label, rest) => write!(f, "({} lacks {})", rest, label),
+            Abs::RowConcat(_, left, right) => write!(f, "({} ++ {})", left, right),
+            Abs::RowMap(_, function, row) => write!(f, "({} <$> {})", function, row),
+            Abs::RowDiff(_, row, labels) => {
+                write!(f, "({} -- [ ", row)?;
+                for ident in labels {
+                    write!(f, "{} ", ident.text)?;
+                }
+                write!(f, "])")
+            }
+            Abs::Remove(_, rec, field) => write!(f, "({} [-{}])", rec, field.text),
+            Abs::Rename(_, rec, from, to) => write!(f, "({} [{} => {}])", rec, from.text, to.text),
         }
     }
 }
@@ -69,12 +156,35 @@ fn pretty_labels(f: &mut Formatter, labels: &[LabAbs], sep: &str) -> MonadFmt {
     Ok(())
 }
 
+impl Display for Pat {
+    fn fmt(&self, f: &mut Formatter) -> MonadFmt {
+        match self {
+            Pat::Var(name, uid) => write!(f, "{}[{:?}]", name.text, uid),
+            Pat::Wildcard(..) => f.write_str("_"),
+            Pat::Cons(label, pattern) => write!(f, "(@{} {})", label.text, pattern),
+            Pat::Pair(first, second) => write!(f, "({}, {})", first, second),
+            Pat::Rec(_, fields) => {
+                f.write_str("{| ")?;
+                for field in fields {
+                    write!(f, "{} = {}; ", field.label.text, field.expr)?;
+                }
+                f.write_str("|}")
+            }
+        }
+    }
+}
+
 impl Display for AbsDecl {
     fn fmt(&self, f: &mut Formatter) -> MonadFmt {
         match self {
-            AbsDecl::Sign(abs, dbi) => write!(f, "[{}] {}", dbi, abs),
+            AbsDecl::Sign(abs, dbi, _) => write!(f, "[{}] {}", dbi, abs),
             AbsDecl::Decl(abs) => write!(f, "_ : {}", abs),
-            AbsDecl::Impl(abs, ty_dbi) => write!(f, "{} : [{}]", abs, ty_dbi),
+            AbsDecl::Impl(abs, ty_dbi, pragmas) => {
+                for pragma in pragmas {
+                    write!(f, "{{-# {:?} #-}} ", pragma)?;
+                }
+                write!(f, "{} : [{}]", abs, ty_dbi)
+            }
         }
     }
 }
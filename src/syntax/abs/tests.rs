@@ -1,11 +1,11 @@
 use voile_util::loc::Ident;
 use voile_util::meta::MI;
-use voile_util::tags::PiSig;
+use voile_util::tags::{PiSig, Plicit};
 use voile_util::uid::{DBI, GI};
 
 use crate::check::monad::TCE;
 use crate::syntax::abs::{trans_expr, Abs};
-use crate::syntax::surf::parse_str_err_printed;
+use crate::syntax::surf::{parse_str_err_printed, Decl, Expr};
 
 use super::{trans_decls, AbsDecl};
 
@@ -23,7 +23,7 @@ fn many_decls() {
     let decl = ctx.pop().unwrap();
     println!("{:?}", decl);
     match decl {
-        AbsDecl::Impl(abs, ty_dbi) => {
+        AbsDecl::Impl(abs, ty_dbi, _) => {
             println!("val {};", ty_dbi);
             println!("let {};", abs);
         }
@@ -33,7 +33,7 @@ fn many_decls() {
     let decl = ctx.pop().unwrap();
     println!("{:?}", decl);
     match decl {
-        AbsDecl::Impl(abs, ty_dbi) => {
+        AbsDecl::Impl(abs, ty_dbi, _) => {
             println!("val {};", ty_dbi);
             println!("let {};", abs);
         }
@@ -43,6 +43,13 @@ fn many_decls() {
     assert!(ctx.is_empty());
 }
 
+fn first_decl_body(code: &str) -> Expr {
+    match parse_str_err_printed(code).unwrap().remove(0) {
+        Decl::Named(decl) => decl.body,
+        e => panic!("`{:?}` is not a `Decl::Named`.", e),
+    }
+}
+
 fn must_be_app(abs: Abs) -> Abs {
     match abs {
         Abs::App(_, _, _, abs) => *abs,
@@ -59,7 +66,7 @@ fn must_be_pi(abs: Abs) -> (Abs, Abs) {
 
 fn must_be_lam(abs: Abs) -> Abs {
     match abs {
-        Abs::Lam(_, _, _, abs) => *abs,
+        Abs::Lam(_, _, _, _, abs) => *abs,
         e => panic!("`{:?}` is not an `Abs::Lam(_, _, _)`.", e),
     }
 }
@@ -73,10 +80,7 @@ fn must_be_local(abs: Abs) -> DBI {
 
 #[test]
 fn trans_pi_env() {
-    let pi_expr = parse_str_err_printed("val t : ((a : Type) -> (b : Type(a)) -> Type(b));")
-        .unwrap()
-        .remove(0)
-        .body;
+    let pi_expr = first_decl_body("val t : ((a : Type) -> (b : Type(a)) -> Type(b));");
     let pi_expr = trans_expr(pi_expr, &[], &mut Default::default(), &Default::default())
         .expect("Parse failed.");
     println!("{}", pi_expr);
@@ -92,7 +96,7 @@ fn trans_pi_env() {
 #[test]
 fn trans_pi_shadowing() {
     let code = "val t : ((a : Type) -> (b : Type(a)) -> (b: Type(b)) -> Type(a));";
-    let pi_expr = parse_str_err_printed(code).unwrap().remove(0).body;
+    let pi_expr = first_decl_body(code);
     let pi_abs = trans_expr(pi_expr, &[], &mut Default::default(), &Default::default()).unwrap();
     println!("{}", pi_abs);
     let (_, bc) = must_be_pi(pi_abs);
@@ -110,7 +114,7 @@ fn trans_pi_shadowing() {
 #[test]
 fn trans_lam() {
     let code = r"let l = \a . \b . \a . b a;";
-    let lam_expr = parse_str_err_printed(code).unwrap().remove(0).body;
+    let lam_expr = first_decl_body(code);
     let lam_abs = trans_expr(lam_expr, &[], &mut Default::default(), &Default::default()).unwrap();
     println!("{}", lam_abs);
     let abs_lam_ba = must_be_lam(lam_abs);
@@ -128,7 +132,7 @@ fn trans_lam() {
 #[test]
 fn trans_multi_param_lam() {
     let code = r"let l = \a b a . b a;";
-    let lam_expr = parse_str_err_printed(code).unwrap().remove(0).body;
+    let lam_expr = first_decl_body(code);
     let lam_abs = trans_expr(lam_expr, &[], &mut Default::default(), &Default::default()).unwrap();
     println!("{}", lam_abs);
     let abs_lam_ba = must_be_lam(lam_abs);
@@ -146,7 +150,7 @@ fn trans_multi_param_lam() {
 #[test]
 fn trans_lam_lookup_failed() {
     let code = r"let l = \a . b;";
-    let lam_expr = parse_str_err_printed(code).unwrap().remove(0).body;
+    let lam_expr = first_decl_body(code);
     let tce = trans_expr(lam_expr, &[], &mut Default::default(), &Default::default()).unwrap_err();
     match tce {
         TCE::LookUpFailed(ident) => assert_eq!(ident.text, "b"),
@@ -157,7 +161,7 @@ fn trans_lam_lookup_failed() {
 #[test]
 fn trans_lam_global() {
     let code = r"let l = \a . b;";
-    let lam_expr = parse_str_err_printed(code).unwrap().remove(0).body;
+    let lam_expr = first_decl_body(code);
     let ident = Ident {
         text: "".to_owned(),
         loc: Default::default(),
@@ -171,10 +175,116 @@ fn trans_lam_global() {
     .unwrap();
     println!("{}", lam_abs);
     match lam_abs {
-        Abs::Lam(_, _, _, global_b) => match *global_b {
+        Abs::Lam(_, _, _, _, global_b) => match *global_b {
             Abs::Ref(_, b_index) => assert_eq!(b_index, GI(0)),
             _ => panic!(),
         },
         _ => panic!(),
     }
 }
+
+#[test]
+fn trans_sigma_projections() {
+    let code = r"let l = \p . p.2.1;";
+    let lam_expr = first_decl_body(code);
+    let lam_abs = trans_expr(lam_expr, &[], &mut Default::default(), &Default::default()).unwrap();
+    println!("{}", lam_abs);
+    match must_be_lam(lam_abs) {
+        Abs::Fst(_, snd) => match *snd {
+            Abs::Snd(_, p) => assert_eq!(DBI(0), must_be_local(*p)),
+            e => panic!("`{:?}` is not an `Abs::Snd`.", e),
+        },
+        e => panic!("`{:?}` is not an `Abs::Fst`.", e),
+    }
+}
+
+#[test]
+fn trans_qualified_name() {
+    let code = r"let l = Data.Bool.not.x;";
+    let expr = first_decl_body(code);
+    let ident = Ident {
+        text: "".to_owned(),
+        loc: Default::default(),
+    };
+    let abs = trans_expr(
+        expr,
+        &[AbsDecl::Decl(Abs::Meta(ident, MI(0)))],
+        &mut MI(1),
+        &[("Data.Bool.not".to_string(), GI(0))]
+            .iter()
+            .cloned()
+            .collect(),
+    )
+    .unwrap();
+    match abs {
+        Abs::Proj(_, not, x, _) => {
+            assert_eq!(x.text, "x");
+            match *not {
+                Abs::Ref(_, index) => assert_eq!(index, GI(0)),
+                e => panic!("`{:?}` is not an `Abs::Ref`.", e),
+            }
+        }
+        e => panic!("`{:?}` is not an `Abs::Proj`.", e),
+    }
+}
+
+#[test]
+fn trans_let_shadowing() {
+    let code = r"let l = \a b. let a = b in a, b;";
+    let lam_expr = first_decl_body(code);
+    let lam_abs = trans_expr(lam_expr, &[], &mut Default::default(), &Default::default()).unwrap();
+    println!("{}", lam_abs);
+    match must_be_lam(must_be_lam(lam_abs)) {
+        Abs::Let(_, _, _, None, def, body) => {
+            assert_eq!(DBI(0), must_be_local(*def));
+            match *body {
+                Abs::Pair(_, a, b) => {
+                    assert_eq!(DBI(0), must_be_local(*a));
+                    assert_eq!(DBI(1), must_be_local(*b));
+                }
+                e => panic!("`{:?}` is not an `Abs::Pair`.", e),
+            }
+        }
+        e => panic!("`{:?}` is not an `Abs::Let`.", e),
+    }
+}
+
+#[test]
+fn trans_implicit_lam_app() {
+    let code = r"let l = \{A} f a. f {A} a;";
+    let lam_expr = first_decl_body(code);
+    let lam_abs = trans_expr(lam_expr, &[], &mut Default::default(), &Default::default()).unwrap();
+    println!("{}", lam_abs);
+    match lam_abs {
+        Abs::Lam(_, _, _, Plicit::Im, body) => match must_be_lam(must_be_lam(*body)) {
+            Abs::App(_, f, Plicit::Ex, a) => {
+                assert_eq!(DBI(0), must_be_local(*a));
+                match *f {
+                    Abs::App(_, f, Plicit::Im, a) => {
+                        assert_eq!(DBI(1), must_be_local(*f));
+                        assert_eq!(DBI(2), must_be_local(*a));
+                    }
+                    e => panic!("`{:?}` is not an implicit `Abs::App`.", e),
+                }
+            }
+            e => panic!("`{:?}` is not an `Abs::App`.", e),
+        },
+        e => panic!("`{:?}` is not an implicit `Abs::Lam`.", e),
+    }
+}
+
+#[test]
+fn trans_misplaced_implicit() {
+    let ident = Ident {
+        text: "A".to_owned(),
+        loc: Default::default(),
+    };
+    // Implicit arguments outside of applications are rejected by the parser,
+    // but they can still be constructed by hand.
+    let expr = Expr::implicit(Default::default(), Expr::Var(ident));
+    let tce = trans_expr(expr, &[], &mut Default::default(), &Default::default()).unwrap_err();
+    match tce {
+        TCE::MisplacedImplicit(..) => {}
+        e => panic!("`{}` is not a `TCE::MisplacedImplicit`.", e),
+    }
+}
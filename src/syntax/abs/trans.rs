@@ -1,17 +1,21 @@
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use voile_util::loc::*;
 use voile_util::meta::MI;
 use voile_util::tags::{PiSig::*, *};
 use voile_util::uid::*;
+use voile_util::vec1::Vec1;
 
 use crate::check::monad::{TCE, TCM};
-use crate::syntax::surf::{Decl, DeclKind, Expr, Param};
+use crate::syntax::surf::{Decl, DeclKind, Expr, NamedDecl, Param, Pattern};
 
 use super::ast::*;
+use super::data::trans_data;
+use super::module::trans_import;
 
 /// Key: global declaration name; Value: global declaration index.
-type GlobCtx = BTreeMap<String, GI>;
+pub(super) type GlobCtx = BTreeMap<String, GI>;
 
 /// Key: local declaration name; Value: de-bruijn indices.
 type LocalCtx = BTreeMap<String, (DBI, Plicit)>;
@@ -35,9 +39,28 @@ pub struct TransState {
     pub context_mapping: GlobCtx,
     pub decl_count: GI,
     pub meta_count: MI,
+    /// Directories where imported modules are looked up.
+    pub search_path: Vec<PathBuf>,
+    /// Key: module path; Value: globals exported by the module,
+    /// or `None` if the module is still being translated.
+    pub modules: BTreeMap<String, Option<GlobCtx>>,
+    /// Globals defined (instead of imported) in the module being translated.
+    pub exports: GlobCtx,
+    /// Key: unqualified name of an imported global; Value: the module it's imported from.
+    pub imported: BTreeMap<String, String>,
+    /// The modules being translated, from the outermost imported one.
+    pub importing: Vec<String>,
 }
 
-fn trans_one_decl(mut tcs: TransState, decl: Decl) -> TCM<TransState> {
+fn trans_one_decl(tcs: TransState, decl: Decl) -> TCM<TransState> {
+    match decl {
+        Decl::Named(decl) => trans_named_decl(tcs, decl),
+        Decl::Import(path) => trans_import(tcs, path),
+        Decl::Data(data) => trans_data(tcs, data),
+    }
+}
+
+pub(super) fn trans_named_decl(mut tcs: TransState, decl: NamedDecl) -> TCM<TransState> {
     let abs = trans_expr(
         decl.body,
         &tcs.decls,
@@ -55,9 +78,15 @@ fn trans_one_decl(mut tcs: TransState, decl: Decl) -> TCM<TransState> {
     } else {
         None
     };
+    // Imported globals cannot be re-defined or implemented
+    let name = decl.name.text.clone();
+    if let Some(imported) = original.filter(|_| !tcs.exports.contains_key(&name)) {
+        return Err(TCE::ReDefine(decl.name.loc, imported.loc()));
+    }
+    tcs.exports.insert(name, dbi);
     let modified = match (decl.kind, original) {
         (DeclKind::Sign, None) => {
-            let abs = AbsDecl::Sign(abs, tcs.decl_count);
+            let abs = AbsDecl::Sign(abs, tcs.decl_count, decl.name.clone());
             tcs.signature_indices.push(DBI(tcs.decls.len()));
             tcs.decl_count += 1;
             abs
@@ -75,7 +104,7 @@ fn trans_one_decl(mut tcs: TransState, decl: Decl) -> TCM<TransState> {
             tcs.signature_indices.push(DBI(tcs.decls.len()));
             AbsDecl::Decl(abs)
         }
-        (DeclKind::Impl, Some(AbsDecl::Sign(_, dbi))) => AbsDecl::Impl(abs, *dbi),
+        (DeclKind::Impl, Some(AbsDecl::Sign(_, dbi, _))) => AbsDecl::Impl(abs, *dbi, decl.pragmas),
     };
     tcs.decls.push(modified);
     Ok(tcs)
@@ -99,6 +128,8 @@ fn trans_expr_inner(
         |Labelled { expr, label }| recursion(expr).map(|expr| Labelled { label, expr });
     match expr {
         Expr::Type(syntax, level) => Ok(Abs::Type(syntax, level)),
+        Expr::LabelType(syntax) => Ok(Abs::LabelType(syntax)),
+        Expr::Label(label) => Ok(Abs::Label(label)),
         Expr::Var(ident) => {
             let name = &ident.text;
             if local_map.contains_key(name) {
@@ -106,13 +137,25 @@ fn trans_expr_inner(
                 Ok(Abs::Var(ident.clone(), local_env[dbi.0], dbi))
             } else if global_map.contains_key(name) {
                 Ok(Abs::Ref(ident.clone(), global_map[name]))
+            } else if name == "Nat" {
+                // Not a keyword, so user programs can still define their own `Nat`.
+                Ok(Abs::NatType(ident.loc))
             } else {
                 Err(TCE::LookUpFailed(ident.clone()))
             }
         }
-        Expr::App(app_vec) => Ok(app_vec.try_map(recursion)?.fold1(|result: Abs, abs: Abs| {
-            Abs::app(merge_info(&result, &abs), result, Plicit::Ex, abs)
-        })),
+        Expr::App(app_vec) => {
+            let app_vec = app_vec.try_map(|e| match e {
+                Expr::Implicit(_, arg) => recursion(*arg).map(|abs| (abs, Plicit::Im)),
+                e => recursion(e).map(|abs| (abs, Plicit::Ex)),
+            })?;
+            let app = app_vec.fold1(|(result, _), (abs, plicit)| {
+                let info = merge_info(&result, &abs);
+                (Abs::app(info, result, plicit, abs), Plicit::Ex)
+            });
+            Ok(app.0)
+        }
+        Expr::Implicit(info, _) => Err(TCE::MisplacedImplicit(info)),
         // I really hope I can reuse the code with `App` here :(
         Expr::Pipe(pipe_vec) => Ok(pipe_vec
             .try_map(recursion)?
@@ -122,22 +165,112 @@ fn trans_expr_inner(
             *meta_count += 1;
             ret
         }
-        Expr::Cons(ident) => Ok(Abs::Cons(ident.clone())),
+        // Not in a parameter type of a pi-type, so there's nothing to bind it.
+        Expr::AnonymousRest(loc) => {
+            let text = "...".to_owned();
+            let ret = Ok(Abs::Meta(Ident { loc, text }, *meta_count));
+            *meta_count += 1;
+            ret
+        }
+        Expr::Cons(ident) => {
+            let label = Ident {
+                text: ident.text[1..].to_owned(),
+                ..ident.clone()
+            };
+            match label_var(&label) {
+                Some(label) => Ok(Abs::label_cons(ident.loc, recursion(label)?)),
+                None => Ok(Abs::Cons(ident)),
+            }
+        }
         // TODO: check uniqueness?
-        Expr::RowKind(info, kind, labels) => Ok(Abs::RowKind(info, kind, labels)),
-        Expr::Proj(expr, projections) => Ok(projections.fold(recursion(*expr)?, |abs, label| {
-            Abs::proj(merge_info(&abs, &label), abs, label)
-        })),
+        Expr::RowKind(info, kind, labels) => {
+            let (vars, labels): (Vec<_>, Vec<_>) =
+                (labels.into_iter()).partition(|label| label_var(label).is_some());
+            let kind = Abs::RowKind(info, kind, labels);
+            vars.into_iter().try_fold(kind, |kind, label| {
+                let label = recursion(label_var(&label).unwrap())?;
+                Ok(Abs::label_kind(info, label, kind))
+            })
+        }
+        Expr::Proj(expr, projections) => {
+            let (expr, projections) = match *expr {
+                Expr::Var(ident) if !local_map.contains_key(&ident.text) => {
+                    qualified_name(global_map, ident, projections)
+                }
+                expr => (expr, projections.into()),
+            };
+            let abs = recursion(expr)?;
+            projections.into_iter().try_fold(abs, |abs, label| {
+                let info = merge_info(&abs, &label);
+                Ok(match label.text.as_str() {
+                    "1" => Abs::fst(info, abs),
+                    "2" => Abs::snd(info, abs),
+                    _ => match label_var(&label) {
+                        Some(label) => Abs::label_proj(info, abs, recursion(label)?),
+                        None if local_map.contains_key(&label.text) => {
+                            let local = recursion(Expr::Var(label.clone()))?;
+                            Abs::proj(info, abs, label, Some(local))
+                        }
+                        None => Abs::proj(info, abs, label, None),
+                    },
+                })
+            })
+        }
         Expr::RowPoly(info, kind, labels, rest) => {
+            let (vars, labels): (Vec<_>, Vec<_>) =
+                (labels.into_iter()).partition(|labelled| label_var(&labelled.label).is_some());
             let labels: Result<_, _> = labels.into_iter().map(map_labels).collect();
-            let rest = rest.map(|e| recursion(*e)).transpose()?;
+            let mut rest = rest.map(|e| recursion(*e)).transpose()?;
+            // Entries labelled by variables become nested extensions.
+            for Labelled { label, expr } in vars.into_iter().rev() {
+                let label = recursion(label_var(&label).unwrap())?;
+                let ty = recursion(expr)?;
+                rest = Some(Abs::label_row(info, kind, label, ty, rest));
+            }
             Ok(Abs::row_polymorphic_type(info, kind, labels?, rest))
         }
+        Expr::DepRec(info, fields) => {
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + fields.len());
+            let mut local_map = local_map.clone();
+            let mut names = Vec::with_capacity(fields.len());
+            let mut out_fields = Vec::with_capacity(fields.len());
+            for Labelled { label, expr } in fields {
+                let expr = trans_expr_inner(expr, meta_count, env, global_map, &local, &local_map)?;
+                introduce_abstractions(
+                    std::slice::from_ref(&label),
+                    &mut local,
+                    &mut local_map,
+                    &mut names,
+                );
+                out_fields.push((Labelled { label, expr }, *names.last().unwrap()));
+            }
+            Ok(Abs::DepRec(info, out_fields))
+        }
         Expr::Rec(info, fields, rest) => {
             let labels: Result<_, _> = fields.into_iter().map(map_labels).collect();
             let rest = rest.map(|e| recursion(*e)).transpose()?;
             Ok(Abs::record(info, labels?, rest))
         }
+        Expr::Update(info, record, fields) => {
+            let fields: Result<_, _> = fields.into_iter().map(map_labels).collect();
+            let record = recursion(*record)?;
+            Ok(Abs::update(info, record, fields?))
+        }
+        Expr::Remove(info, record, field) => Ok(Abs::remove(info, recursion(*record)?, field)),
+        Expr::RowConcat(info, left, right) => {
+            let left = recursion(*left)?;
+            Ok(Abs::row_concat(info, left, recursion(*right)?))
+        }
+        Expr::RowMap(info, function, row) => {
+            let function = recursion(*function)?;
+            Ok(Abs::row_map(info, function, recursion(*row)?))
+        }
+        Expr::RowDiff(info, row, labels) => Ok(Abs::row_diff(info, recursion(*row)?, labels)),
+        Expr::Rename(info, record, from, to) => {
+            Ok(Abs::rename(info, recursion(*record)?, from, to))
+        }
+        // Right-nested, like sigma types: `a, b, c` is `a, (b, c)`
         Expr::Tup(tup_vec) => Ok(tup_vec
             .try_map(recursion)?
             .fold1(|pair, abs| Abs::pair(abs.loc(), pair, abs))),
@@ -155,19 +288,156 @@ fn trans_expr_inner(
             Ok(Abs::case_or(label, binding, names[0], body, or))
         }
         Expr::Whatever(info) => Ok(Abs::Whatever(info)),
+        Expr::CaseDefault(info, binding, body) => {
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 1);
+            let mut local_map = local_map.clone();
+            let mut names = Vec::with_capacity(1);
+            introduce_abstractions(
+                std::slice::from_ref(&binding),
+                &mut local,
+                &mut local_map,
+                &mut names,
+            );
+            let body = trans_expr_inner(*body, meta_count, env, global_map, &local, &local_map)?;
+            Ok(Abs::case_default(info, binding, names[0], body))
+        }
+        Expr::Match(info, scrutinee, binding, motive, cases) => {
+            let scrutinee = recursion(*scrutinee)?;
+            let cases = recursion(*cases)?;
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 1);
+            let mut local_map = local_map.clone();
+            let mut names = Vec::with_capacity(1);
+            introduce_abstractions(
+                std::slice::from_ref(&binding),
+                &mut local,
+                &mut local_map,
+                &mut names,
+            );
+            let motive =
+                trans_expr_inner(*motive, meta_count, env, global_map, &local, &local_map)?;
+            Ok(Abs::match_on(
+                info, scrutinee, binding, names[0], motive, cases,
+            ))
+        }
+        Expr::Eq(info, a, b) => Ok(Abs::eq(info, recursion(*a)?, recursion(*b)?)),
+        Expr::Refl(info) => Ok(Abs::Refl(info)),
+        Expr::J(info, proof, end, eq, motive, refl) => {
+            let proof = recursion(*proof)?;
+            let refl = recursion(*refl)?;
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 2);
+            let mut local_map = local_map.clone();
+            let mut names = Vec::with_capacity(2);
+            let bindings = [end.clone(), eq.clone()];
+            introduce_abstractions(&bindings, &mut local, &mut local_map, &mut names);
+            let motive =
+                trans_expr_inner(*motive, meta_count, env, global_map, &local, &local_map)?;
+            let (end, eq) = ((end, names[0]), (eq, names[1]));
+            Ok(Abs::j(info, proof, end, eq, motive, refl))
+        }
+        Expr::Nat(info, n) => Ok(Abs::Nat(info, n)),
+        Expr::NatOp(info, op, a, b) => Ok(Abs::nat_op(info, op, recursion(*a)?, recursion(*b)?)),
+        Expr::NatElim(info, nat, binding, motive, zero, pred, result, step) => {
+            let nat = recursion(*nat)?;
+            let zero = recursion(*zero)?;
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 1);
+            let mut motive_map = local_map.clone();
+            let mut names = Vec::with_capacity(3);
+            introduce_abstractions(
+                std::slice::from_ref(&binding),
+                &mut local,
+                &mut motive_map,
+                &mut names,
+            );
+            let motive =
+                trans_expr_inner(*motive, meta_count, env, global_map, &local, &motive_map)?;
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 2);
+            let mut local_map = local_map.clone();
+            let bindings = [pred.clone(), result.clone()];
+            introduce_abstractions(&bindings, &mut local, &mut local_map, &mut names);
+            let step = trans_expr_inner(*step, meta_count, env, global_map, &local, &local_map)?;
+            let step_bindings = ((pred, names[1]), (result, names[2]));
+            let binding = (binding, names[0]);
+            Ok(Abs::nat_elim(
+                info,
+                nat,
+                binding,
+                motive,
+                zero,
+                step_bindings,
+                step,
+            ))
+        }
+        Expr::PatLam(info, clauses) => {
+            let mut out_clauses = Vec::with_capacity(clauses.len());
+            for (pattern, body) in clauses {
+                let vars: Vec<Ident> = pattern.vars().into_iter().cloned().collect();
+                let mut local = local_env.to_vec();
+                local.reserve_exact(local.len() + vars.len());
+                let mut local_map = local_map.clone();
+                let mut names = Vec::with_capacity(vars.len());
+                introduce_abstractions(&vars, &mut local, &mut local_map, &mut names);
+                let body = trans_expr_inner(body, meta_count, env, global_map, &local, &local_map)?;
+                let pattern = trans_pattern(pattern, &mut names.into_iter());
+                out_clauses.push((pattern, body));
+            }
+            Ok(Abs::PatLam(info, unsafe { next_uid() }, out_clauses))
+        }
         Expr::Lam(info, params, body) => {
             let mut local = local_env.to_vec();
             local.reserve_exact(local.len() + params.len() + 1);
             let mut local_map = local_map.clone();
             let mut names = Vec::with_capacity(params.len());
+            let (params, plicits): (Vec<_>, Vec<_>) = params.into_iter().unzip();
             introduce_abstractions(&params, &mut local, &mut local_map, &mut names);
             let body = trans_expr_inner(*body, meta_count, env, global_map, &local, &local_map)?;
-            Ok(params.into_iter().rev().fold(body, |lam_abs, param| {
+            let params = params.into_iter().zip(plicits).rev();
+            Ok(params.fold(body, |lam_abs, (param, plicit)| {
                 let pop_empty = "The stack `names` is empty. Please report this as a bug.";
                 let name = names.pop().expect(pop_empty);
-                Abs::lam(info.clone(), param.clone(), name, lam_abs)
+                Abs::lam(info, param, name, plicit, lam_abs)
             }))
         }
+        Expr::Ascription(info, expr, ty) => {
+            let expr = recursion(*expr)?;
+            Ok(Abs::ascription(info, expr, recursion(*ty)?))
+        }
+        Expr::Fix(info, name, body) => {
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 1);
+            let mut local_map = local_map.clone();
+            let mut names = Vec::with_capacity(1);
+            introduce_abstractions(
+                std::slice::from_ref(&name),
+                &mut local,
+                &mut local_map,
+                &mut names,
+            );
+            let body = trans_expr_inner(*body, meta_count, env, global_map, &local, &local_map)?;
+            Ok(Abs::fix(info, name, names[0], body))
+        }
+        Expr::Fold(info, expr) => Ok(Abs::fold(info, recursion(*expr)?)),
+        Expr::Unfold(info, expr) => Ok(Abs::unfold(info, recursion(*expr)?)),
+        Expr::Let(info, name, ty, def, body) => {
+            let ty = ty.map(|ty| recursion(*ty)).transpose()?;
+            let def = recursion(*def)?;
+            let mut local = local_env.to_vec();
+            local.reserve_exact(local.len() + 1);
+            let mut local_map = local_map.clone();
+            let mut names = Vec::with_capacity(1);
+            introduce_abstractions(
+                std::slice::from_ref(&name),
+                &mut local,
+                &mut local_map,
+                &mut names,
+            );
+            let body = trans_expr_inner(*body, meta_count, env, global_map, &local, &local_map)?;
+            Ok(Abs::let_in(info, name, names[0], ty, def, body))
+        }
         Expr::Pi(params, result) => trans_dependent_type(
             meta_count, env, global_map, local_env, local_map, params, *result, Pi,
         ),
@@ -175,6 +445,59 @@ fn trans_expr_inner(
     }
 }
 
+/// The pattern variables take the unique ids in `names`, from left to right.
+fn trans_pattern(pattern: Pattern, names: &mut impl Iterator<Item = UID>) -> Pat {
+    let pop_empty = "The stack `names` is empty. Please report this as a bug.";
+    match pattern {
+        Pattern::Var(ident) => Pat::Var(ident, names.next().expect(pop_empty)),
+        Pattern::Wildcard(info) => Pat::Wildcard(info),
+        Pattern::Cons(label, pattern) => Pat::Cons(label, Box::new(trans_pattern(*pattern, names))),
+        Pattern::Pair(first, second) => {
+            let first = trans_pattern(*first, names);
+            Pat::Pair(Box::new(first), Box::new(trans_pattern(*second, names)))
+        }
+        Pattern::Rec(info, fields) => {
+            let fields = (fields.into_iter())
+                .map(|Labelled { label, expr }| Labelled {
+                    expr: trans_pattern(expr, names),
+                    label,
+                })
+                .collect();
+            Pat::Rec(info, fields)
+        }
+    }
+}
+
+/// A label written `[l]` is a label variable, which is the variable `l` of type `Label`.
+fn label_var(label: &Ident) -> Option<Expr> {
+    let name = label.text.strip_prefix('[')?.strip_suffix(']')?;
+    let text = name.to_owned();
+    Some(Expr::Var(Ident { text, ..*label }))
+}
+
+/// `Foo.Bar.baz` is parsed as projections, but it might be a qualified name
+/// of an imported global. We take the longest prefix that is a global name.
+fn qualified_name(
+    global_map: &GlobCtx,
+    head: Ident,
+    projections: Vec1<Ident>,
+) -> (Expr, Vec<Ident>) {
+    let mut projections: Vec<Ident> = projections.into();
+    let mut name = head.clone();
+    let mut longest = None;
+    for (i, label) in projections.iter().enumerate() {
+        name.text = format!("{}.{}", name.text, label.text);
+        name.loc = merge_info(&name, label);
+        if global_map.contains_key(&name.text) {
+            longest = Some((i, name.clone()));
+        }
+    }
+    match longest {
+        Some((i, name)) => (Expr::Var(name), projections.split_off(i + 1)),
+        None => (Expr::Var(head), projections),
+    }
+}
+
 fn introduce_abstractions(
     params: &[Ident],
     local_env: &mut Vec<UID>,
@@ -182,13 +505,9 @@ fn introduce_abstractions(
     names: &mut Vec<UID>,
 ) {
     for param in params {
-        let shadowing = local_map.get(&param.text).cloned().map(|(dbi, _)| dbi);
+        // A shadowed variable still takes a de-bruijn index, it's just not reachable.
         for (_name, (dbi, _)) in local_map.iter_mut() {
-            let dbi_value = *dbi;
             *dbi += 1;
-            if shadowing == Some(dbi_value) {
-                local_env.remove(dbi_value.0);
-            }
         }
         local_map.insert(param.text.clone(), (Default::default(), Plicit::Ex));
         let new_name = unsafe { next_uid() };
@@ -207,6 +526,10 @@ fn trans_dependent_type(
     result: Expr,
     kind: PiSig,
 ) -> TCM<Abs> {
+    let params = match kind {
+        Pi => params.into_iter().flat_map(bind_anonymous_rows).collect(),
+        Sigma => params,
+    };
     let mut pi_env = local_env.to_vec();
     let mut pi_map = local_map.clone();
     let mut names = Vec::with_capacity(params.len());
@@ -241,6 +564,92 @@ fn trans_dependent_type(
     ))
 }
 
+/// `Rec { x: A; ... } -> B` is `{r : Rec [x]} -> Rec { x: A; ... = r } -> B`,
+/// the implicit row parameters are introduced right before the parameter.
+fn bind_anonymous_rows(param: Param) -> Vec<Param> {
+    let mut params = Vec::new();
+    let ty = name_anonymous_rows(param.ty, &mut params);
+    params.push(Param { ty, ..param });
+    params
+}
+
+/// Replace the anonymous row extensions in `expr` with references to new implicit
+/// parameters, which are pushed to `rows`.
+/// Those under other pi-types are left to the pi-types.
+fn name_anonymous_rows(expr: Expr, rows: &mut Vec<Param>) -> Expr {
+    match expr {
+        Expr::RowPoly(info, kind, labels, rest) => {
+            let labels: Vec<_> = (labels.into_iter())
+                .map(|Labelled { label, expr }| Labelled {
+                    expr: name_anonymous_rows(expr, rows),
+                    label,
+                })
+                .collect();
+            let rest = rest.map(|rest| match *rest {
+                Expr::AnonymousRest(loc) => {
+                    // Not a valid identifier, so it never captures a user variable.
+                    let name = Ident {
+                        loc,
+                        text: format!("...{}", unsafe { next_uid() }),
+                    };
+                    let label_names = labels.iter().map(|l| l.label.clone()).collect();
+                    rows.push(Param {
+                        plicit: Plicit::Im,
+                        names: vec![name.clone()],
+                        ty: Expr::RowKind(loc, kind, label_names),
+                    });
+                    Expr::Var(name)
+                }
+                rest => name_anonymous_rows(rest, rows),
+            });
+            Expr::row_polymorphic_type(info, labels, kind, rest)
+        }
+        Expr::App(app_vec) => Expr::App(Box::new(app_vec.map(|e| name_anonymous_rows(e, rows)))),
+        Expr::Pipe(pipe_vec) => {
+            Expr::Pipe(Box::new(pipe_vec.map(|e| name_anonymous_rows(e, rows))))
+        }
+        Expr::Tup(tup_vec) => Expr::Tup(Box::new(tup_vec.map(|e| name_anonymous_rows(e, rows)))),
+        Expr::Implicit(info, arg) => Expr::implicit(info, name_anonymous_rows(*arg, rows)),
+        Expr::Lift(info, levels, inner) => {
+            Expr::lift(info, levels, name_anonymous_rows(*inner, rows))
+        }
+        Expr::Fix(info, name, body) => Expr::fix(info, name, name_anonymous_rows(*body, rows)),
+        Expr::Proj(expr, projections) => {
+            Expr::Proj(Box::new(name_anonymous_rows(*expr, rows)), projections)
+        }
+        Expr::Ascription(info, expr, ty) => {
+            let expr = name_anonymous_rows(*expr, rows);
+            Expr::ascription(info, expr, name_anonymous_rows(*ty, rows))
+        }
+        Expr::RowConcat(info, left, right) => {
+            let left = name_anonymous_rows(*left, rows);
+            Expr::row_concat(info, left, name_anonymous_rows(*right, rows))
+        }
+        Expr::RowMap(info, function, row) => {
+            let function = name_anonymous_rows(*function, rows);
+            Expr::row_map(info, function, name_anonymous_rows(*row, rows))
+        }
+        Expr::RowDiff(info, row, labels) => {
+            Expr::row_diff(info, name_anonymous_rows(*row, rows), labels)
+        }
+        Expr::Eq(info, a, b) => {
+            let a = name_anonymous_rows(*a, rows);
+            Expr::eq(info, a, name_anonymous_rows(*b, rows))
+        }
+        // The kind of a row only mentions labels, so the row can be bound outside.
+        Expr::Sig(params, result) => {
+            let params = (params.into_iter())
+                .map(|param| Param {
+                    ty: name_anonymous_rows(param.ty, rows),
+                    ..param
+                })
+                .collect();
+            Expr::Sig(params, Box::new(name_anonymous_rows(*result, rows)))
+        }
+        expr => expr,
+    }
+}
+
 fn introduce_telescope(
     meta_count: &mut MI,
     env: &[AbsDecl],
@@ -254,8 +663,8 @@ fn introduce_telescope(
     let param_ty = trans_expr_inner(param.ty, meta_count, env, global_map, &dt_env, &dt_map)?;
     for name in &param.names {
         let param_name = name.text.clone();
-        // These two are actually our assumption. Hope they're correct.
-        assert_eq!(dt_env.len(), dt_map.len());
+        // Anonymous and shadowed parameters still take a de-bruijn index,
+        // so `dt_env` can be longer than `dt_map`.
         // let shadowing = dt_map.get(&param_name).cloned();
         dt_map.iter_mut().for_each(|(_name, (dbi, _))| *dbi += 1);
         /*
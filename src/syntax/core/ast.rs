@@ -1,10 +1,11 @@
 use std::collections::BTreeMap;
 
+use num_bigint::BigUint;
 use voile_util::axiom::Axiom;
 use voile_util::level::Level;
 use voile_util::meta::MI;
-use voile_util::tags::{PiSig, Plicit, VarRec};
-use voile_util::uid::{DBI, GI};
+use voile_util::tags::{NatOp, PiSig, Plicit, VarRec};
+use voile_util::uid::{DBI, GI, UID};
 
 use super::{RedEx, TraverseNeutral};
 
@@ -21,10 +22,19 @@ pub type CaseSplit = BTreeMap<String, Closure>;
 impl Val {
     pub fn apply(self, arg: Val) -> Self {
         match self {
-            Val::Lam(closure) => closure.instantiate(arg),
+            // Stuck until the argument is a constructor call, no binder is removed.
+            Val::Lam(Closure::Tree(split)) if matches!(arg, Val::Neut(..)) => {
+                Closure::Tree(split).instantiate(arg)
+            }
+            Val::Lam(closure) => closure.strengthen().instantiate(arg),
+            // Stuck until the argument is a constructor call, otherwise `or` is lost.
+            Val::Neut(Neutral::OrSplit(split, or)) if matches!(arg, Val::Neut(..)) => {
+                Val::app(Neutral::OrSplit(split, or), vec![arg])
+            }
             Val::Neut(Neutral::OrSplit(split, or)) => Closure::Tree(split)
+                .strengthen()
                 .instantiate_safe(arg)
-                .unwrap_or_else(|e| Val::app(*or, vec![e])),
+                .unwrap_or_else(|e| or.apply(e)),
             Val::Neut(Neutral::App(f, mut a)) => {
                 a.push(arg);
                 Val::app(*f, a)
@@ -34,6 +44,24 @@ impl Val {
         }
     }
 
+    /// Unfold a recursive type once, other values are unchanged.
+    pub fn unfold(self) -> Self {
+        match self {
+            Val::Fix(body) => body.clone().strengthen().instantiate(Val::Fix(body)),
+            e => e,
+        }
+    }
+
+    /// Eliminating a proof of an identity type, the value for `refl` is used
+    /// when the proof is `refl`.
+    pub fn eliminate_id(self, refl_case: Val) -> Self {
+        match self {
+            Val::Refl => refl_case,
+            Val::Neut(proof) => Val::Neut(Neutral::J(Box::new(refl_case), Box::new(proof))),
+            e => panic!("Cannot eliminate `{}`.", e),
+        }
+    }
+
     pub fn first(self) -> Self {
         match self {
             Val::Pair(a, _) => *a,
@@ -55,14 +83,171 @@ impl Val {
             Val::Rec(mut fields) => fields
                 .remove(&field)
                 .expect(&format!("Missing essential field with name `{}`.", field)),
-            Val::Neut(Neutral::Rec(mut fields, ..)) => fields
-                .remove(&field)
-                .expect(&format!("Missing essential field with name `{}`.", field)),
+            Val::Neut(Neutral::Rec(mut fields, more)) => match fields.remove(&field) {
+                Some(value) => value,
+                None => Val::proj(*more, field),
+            },
             Val::Neut(otherwise) => Val::proj(otherwise, field),
             e => panic!("Cannot project on `{}`.", e),
         }
     }
 
+    /// Projecting a field of a record, the label may be a variable.
+    pub fn project_label(self, label: Val) -> Val {
+        match label {
+            Val::Label(field) => self.project(field),
+            Val::Neut(label) => Val::Neut(Neutral::LabProj(Box::new(self), Box::new(label))),
+            e => panic!("Cannot project with label `{}`.", e),
+        }
+    }
+
+    /// Constructor call, the label may be a variable.
+    pub fn label_cons(label: Val, param: Val) -> Val {
+        match label {
+            Val::Label(name) => Val::cons(name, param),
+            Val::Neut(label) => Val::Neut(Neutral::LabCons(Box::new(label), Box::new(param))),
+            e => panic!("Cannot construct with label `{}`.", e),
+        }
+    }
+
+    /// Extending a row with one entry, the label may be a variable.
+    pub fn label_row(kind: VarRec, label: Val, ty: Val, rest: Val) -> Val {
+        match label {
+            Val::Label(name) => {
+                let mut variants = Variants::new();
+                variants.insert(name, ty);
+                Val::RowPoly(kind, variants).row_extend(rest)
+            }
+            Val::Neut(label) => Val::Neut(Neutral::LabRow(
+                kind,
+                Box::new(label),
+                Box::new(ty),
+                Box::new(rest),
+            )),
+            e => panic!("Cannot extend a row with label `{}`.", e),
+        }
+    }
+
+    /// Adding a label to a row kind, the label may be a variable.
+    pub fn label_kind(label: Val, kind: Val) -> Val {
+        match (label, kind) {
+            (Val::Label(name), Val::RowKind(level, kind, mut labels)) => {
+                labels.push(name);
+                Val::RowKind(level, kind, labels)
+            }
+            (label @ Val::Label(..), Val::Neut(Neutral::LabKind(var, kind))) => {
+                let kind = Val::label_kind(label, *kind);
+                Val::Neut(Neutral::LabKind(var, Box::new(kind)))
+            }
+            (Val::Neut(label), kind) => {
+                Val::Neut(Neutral::LabKind(Box::new(label), Box::new(kind)))
+            }
+            (label, kind) => panic!("Cannot add label `{}` to `{}`.", label, kind),
+        }
+    }
+
+    /// Concatenation of two disjoint rows.
+    /// When both rows have unknown parts the result cannot be expressed
+    /// as an extension, but the type-checker rejects such concatenations.
+    pub fn row_concat(self, other: Self) -> Self {
+        use {Neutral::Row, Val::*};
+        match (self, other) {
+            (Neut(Row(kind, variants, more)), other) | (other, Neut(Row(kind, variants, more))) => {
+                RowPoly(kind, variants).row_extend(Neut(*more).row_concat(other))
+            }
+            (row @ RowPoly(..), other) | (other, row @ RowPoly(..)) => row.row_extend(other),
+            (a, b) => panic!("Cannot concatenate `{}` with `{}`.", a, b),
+        }
+    }
+
+    /// Removing some labels from a row.
+    pub fn row_diff(self, labels: &[String]) -> Self {
+        use {Neutral::*, Val::*};
+        match self {
+            RowPoly(kind, mut variants) => {
+                for label in labels {
+                    variants.remove(label);
+                }
+                RowPoly(kind, variants)
+            }
+            Neut(Row(kind, mut variants, more)) => {
+                let labels: Vec<_> = (labels.iter())
+                    .filter(|label| variants.remove(*label).is_none())
+                    .cloned()
+                    .collect();
+                RowPoly(kind, variants).row_extend(Neut(*more).row_diff(&labels))
+            }
+            Neut(otherwise) if labels.is_empty() => Neut(otherwise),
+            Neut(RowDiff(row, mut removed)) => {
+                removed.extend(labels.iter().cloned());
+                removed.sort();
+                removed.dedup();
+                Neut(RowDiff(row, removed))
+            }
+            Neut(otherwise) => {
+                let mut labels = labels.to_vec();
+                labels.sort();
+                labels.dedup();
+                Neut(RowDiff(Box::new(otherwise), labels))
+            }
+            e => panic!("Cannot remove labels from `{}`.", e),
+        }
+    }
+
+    /// Applying a type function to every entry of a row.
+    pub fn row_map(self, function: Val) -> Self {
+        use {Neutral::*, Val::*};
+        let map = |variants: Variants| -> Variants {
+            (variants.into_iter())
+                .map(|(label, ty)| (label, function.clone().apply(ty)))
+                .collect()
+        };
+        match self {
+            RowPoly(kind, variants) => RowPoly(kind, map(variants)),
+            Neut(Row(kind, variants, more)) => {
+                RowPoly(kind, map(variants)).row_extend(Neut(*more).row_map(function))
+            }
+            Neut(LabRow(kind, label, ty, rest)) => {
+                let ty = function.clone().apply(*ty);
+                Val::label_row(kind, Neut(*label), ty, rest.row_map(function))
+            }
+            Neut(otherwise) => Neut(RowMap(Box::new(function), Box::new(otherwise))),
+            e => panic!("Cannot map over `{}`.", e),
+        }
+    }
+
+    /// Removing a field from a record.
+    pub fn remove_field(self, field: &str) -> Self {
+        match self {
+            Val::Rec(mut fields) => {
+                fields.remove(field);
+                Val::Rec(fields)
+            }
+            Val::Neut(Neutral::Rec(mut fields, more)) => match fields.remove(field) {
+                Some(_) => Val::Rec(fields).rec_extend(Val::Neut(*more)),
+                None => Val::Rec(fields).rec_extend(Val::Neut(*more).remove_field(field)),
+            },
+            Val::Neut(otherwise) => Val::remove(otherwise, field.to_owned()),
+            e => panic!("Cannot remove field from `{}`.", e),
+        }
+    }
+
+    /// Replacing the values of some existing fields of a record.
+    pub fn update_fields(self, fields: Fields) -> Self {
+        let record = fields
+            .keys()
+            .fold(self, |rec, field| rec.remove_field(field));
+        Val::Rec(fields).rec_extend(record)
+    }
+
+    /// Giving a field of a record another name.
+    pub fn rename_field(self, from: String, to: String) -> Self {
+        let value = self.clone().project(from.clone());
+        let mut fields = Fields::new();
+        fields.insert(to, value);
+        Val::Rec(fields).rec_extend(self.remove_field(&from))
+    }
+
     /// Extension for records.
     pub fn rec_extend(self, ext: Self) -> Self {
         use Val::*;
@@ -77,7 +262,11 @@ impl Val {
                 Rec(fields).rec_extend(Neut(*ext))
             }
             (Rec(fields), Neut(otherwise)) | (Neut(otherwise), Rec(fields)) => {
-                Self::neutral_record(fields, otherwise)
+                if fields.is_empty() {
+                    Neut(otherwise)
+                } else {
+                    Self::neutral_record(fields, otherwise)
+                }
             }
             (a, b) => panic!("Cannot extend `{}` by `{}`.", a, b),
         }
@@ -85,7 +274,7 @@ impl Val {
 
     /// Extension for case-splits.
     pub fn split_extend(self, ext: Self) -> Self {
-        use {Closure::Tree, Val::*};
+        use {Closure::Plain, Closure::Tree, Val::*};
         match (self, ext) {
             (Lam(Tree(mut split)), Lam(Tree(mut ext))) => {
                 split.append(&mut ext);
@@ -94,46 +283,33 @@ impl Val {
             (Lam(Tree(mut split)), Neut(Neutral::OrSplit(mut more, ext)))
             | (Neut(Neutral::OrSplit(mut more, ext)), Lam(Tree(mut split))) => {
                 split.append(&mut more);
-                Lam(Tree(split)).split_extend(Neut(*ext))
+                Lam(Tree(split)).split_extend(*ext)
             }
             (Neut(otherwise), Lam(Tree(split))) | (Lam(Tree(split)), Neut(otherwise)) => {
-                Val::or_split(split, otherwise)
+                Val::or_split(split, Neut(otherwise))
             }
+            // The default clause handles the other variants.
+            (default @ Lam(Plain(..)), Lam(Tree(split)))
+            | (Lam(Tree(split)), default @ Lam(Plain(..))) => Val::or_split(split, default),
             (a, b) => panic!("Cannot extend `{}` by `{}`.", a, b),
         }
     }
 
     /// Extension for row-polymorphic types.
     pub fn row_extend(self, ext: Self) -> Self {
-        use {Neutral::Row, Val::*, VarRec::*};
+        use {Neutral::Row, Val::*};
         match (self, ext) {
-            (RowPoly(Record, mut fields), RowPoly(Record, mut ext)) => {
-                fields.append(&mut ext);
-                Self::record_type(fields)
-            }
-            (RowPoly(Record, mut fields), Neut(Row(Record, mut ext, more))) => {
-                fields.append(&mut ext);
-                Self::record_type(fields).row_extend(Neut(*more))
-            }
-            (RowPoly(Variant, mut variants), RowPoly(Variant, mut ext)) => {
-                variants.append(&mut ext);
-                Self::variant_type(variants)
-            }
-            (RowPoly(Variant, mut variants), Neut(Row(Variant, mut ext, more))) => {
-                variants.append(&mut ext);
-                Self::variant_type(variants).row_extend(Neut(*more))
-            }
-            (RowPoly(kind, mut variants), RowPoly(_, mut ext)) => {
-                eprintln!("Warning: incorrect row extension!");
+            (RowPoly(kind, mut variants), RowPoly(ext_kind, mut ext)) if kind == ext_kind => {
                 variants.append(&mut ext);
                 RowPoly(kind, variants)
             }
-            (RowPoly(kind, mut variants), Neut(Row(_, mut ext, more))) => {
-                eprintln!("Warning: incorrect row extension!");
+            (RowPoly(kind, mut variants), Neut(Row(ext_kind, mut ext, more)))
+                if kind == ext_kind =>
+            {
                 variants.append(&mut ext);
                 RowPoly(kind, variants).row_extend(Neut(*more))
             }
-            (RowPoly(kind, variants), Neut(otherwise)) => {
+            (RowPoly(kind, variants), Neut(otherwise)) if !matches!(otherwise, Row(..)) => {
                 if variants.is_empty() {
                     Neut(otherwise)
                 } else {
@@ -144,14 +320,13 @@ impl Val {
         }
     }
 
-    pub(crate) fn attach_dbi(self, dbi: DBI) -> Self {
-        self.map_neutral(&mut |neut: Neutral| {
-            Val::Neut(neut.map_axiom(&mut |a| {
-                Neutral::Axi(match a {
-                    Axiom::Postulated(uid) => Axiom::Generated(uid, dbi),
-                    e => e,
-                })
-            }))
+    /// Move the value under `dbi` binders, the postulated values become generated ones,
+    /// and the generated values referring out of this value are shifted.
+    pub(crate) fn attach_dbi(self, DBI(dbi): DBI) -> Self {
+        self.map_axiom_under(&mut |a, depth| match a {
+            Axiom::Postulated(uid) => Axiom::Generated(uid, DBI(dbi + depth)),
+            Axiom::Generated(uid, DBI(i)) if i >= depth => Axiom::Generated(uid, DBI(i + dbi)),
+            a => a,
         })
     }
 
@@ -163,6 +338,26 @@ impl Val {
         })
     }
 
+    /// Turn the generated values of the given local into variables,
+    /// so the value can be the body of a closure binding that local.
+    pub(crate) fn abstract_local(self, local: UID) -> Self {
+        use {Axiom::*, Neutral::*};
+        self.map_axiom(&mut |a| match a {
+            Generated(uid, dbi) if uid == local => Var(dbi),
+            a => Axi(a),
+        })
+    }
+
+    /// Change the de-bruijn indices of the generated values of the given locals.
+    pub(crate) fn shift_generated(self, uids: &[UID], f: impl Fn(DBI) -> DBI) -> Self {
+        self.map_axiom(&mut |a| {
+            Neutral::Axi(match a {
+                Axiom::Generated(uid, dbi) if uids.contains(&uid) => Axiom::Generated(uid, f(dbi)),
+                a => a,
+            })
+        })
+    }
+
     pub fn unimplemented_to_glob(self) -> Self {
         use {Axiom::*, Neutral::*};
         self.map_axiom(&mut |a| match a {
@@ -205,10 +400,34 @@ pub enum Neutral {
     Row(VarRec, Variants, Box<Self>),
     /// Record literal, with extension.
     Rec(Fields, Box<Self>),
+    /// Removing a field from a neutral record.
+    Remove(Box<Self>, String),
     /// Splitting on a neutral term.
     SplitOn(CaseSplit, Box<Self>),
-    /// Splitting with unknown branches.
-    OrSplit(CaseSplit, Box<Self>),
+    /// Splitting with unknown branches (a neutral function),
+    /// or with a default clause (a lambda) for the other variants.
+    OrSplit(CaseSplit, Box<Val>),
+    /// Projecting a field of a record, labelled by a neutral label.
+    LabProj(Box<Val>, Box<Self>),
+    /// Constructor invocation, labelled by a neutral label.
+    LabCons(Box<Self>, Box<Val>),
+    /// Row-polymorphic types with an entry labelled by a neutral label,
+    /// the type of the entry and the rest of the row.
+    LabRow(VarRec, Box<Self>, Box<Val>, Box<Val>),
+    /// Row kind lacking a neutral label, and the labels of the other row kind.
+    LabKind(Box<Self>, Box<Val>),
+    /// Applying a type function to every entry of a neutral row.
+    RowMap(Box<Val>, Box<Self>),
+    /// Removing labels from a neutral row, the labels are sorted.
+    RowDiff(Box<Self>, Vec<String>),
+    /// Eliminating a neutral proof of an identity type, with the value for `refl`.
+    J(Box<Val>, Box<Self>),
+    /// A neutral natural number plus a (non-zero) literal, like `n + 1`.
+    Suc(BigUint, Box<Self>),
+    /// Primitive operation on natural numbers, stuck on a neutral operand.
+    NatOp(NatOp, Box<Val>, Box<Val>),
+    /// Eliminating a neutral natural number, with the value for zero and the step.
+    NatElim(Box<Val>, Box<Val>, Box<Self>),
 }
 
 impl Neutral {
@@ -216,6 +435,10 @@ impl Neutral {
         use Neutral::*;
         let mapper = &mut |n: Neutral| Val::Neut(n.map_axiom(f));
         let map_val = |(k, v): (String, Val)| (k, v.map_neutral(mapper));
+        // The mapped neutral values are still neutral.
+        let map_label = |label: Box<Neutral>, mapper: &mut dyn FnMut(Neutral) -> Val| {
+            Box::new(mapper(*label).into_neutral().unwrap())
+        };
         match self {
             Axi(a) => f(a),
             App(fun, args) => App(
@@ -227,6 +450,7 @@ impl Neutral {
             Fst(p) => Fst(Box::new(p.map_axiom(f))),
             Snd(p) => Snd(Box::new(p.map_axiom(f))),
             Proj(p, s) => Proj(Box::new(p.map_axiom(f)), s),
+            Remove(p, s) => Remove(Box::new(p.map_axiom(f)), s),
             Var(n) => Var(n),
             Ref(n) => Ref(n),
             Meta(n) => Meta(n),
@@ -243,9 +467,45 @@ impl Neutral {
                 Self::map_axiom_split(mapper, split),
                 Box::new(obj.map_axiom(f)),
             ),
-            OrSplit(split, obj) => OrSplit(
+            OrSplit(split, or) => OrSplit(
                 Self::map_axiom_split(mapper, split),
-                Box::new(obj.map_axiom(f)),
+                Box::new(or.map_neutral(mapper)),
+            ),
+            LabProj(rec, label) => {
+                LabProj(Box::new(rec.map_neutral(mapper)), map_label(label, mapper))
+            }
+            LabCons(label, param) => LabCons(
+                map_label(label, mapper),
+                Box::new(param.map_neutral(mapper)),
+            ),
+            LabRow(kind, label, ty, rest) => LabRow(
+                kind,
+                map_label(label, mapper),
+                Box::new(ty.map_neutral(mapper)),
+                Box::new(rest.map_neutral(mapper)),
+            ),
+            LabKind(label, kind) => {
+                LabKind(map_label(label, mapper), Box::new(kind.map_neutral(mapper)))
+            }
+            RowMap(function, row) => RowMap(
+                Box::new(function.map_neutral(mapper)),
+                Box::new(row.map_axiom(f)),
+            ),
+            RowDiff(row, labels) => RowDiff(Box::new(row.map_axiom(f)), labels),
+            J(refl_case, proof) => J(
+                Box::new(refl_case.map_neutral(mapper)),
+                Box::new(proof.map_axiom(f)),
+            ),
+            Suc(k, n) => Suc(k, Box::new(n.map_axiom(f))),
+            NatOp(op, a, b) => NatOp(
+                op,
+                Box::new(a.map_neutral(mapper)),
+                Box::new(b.map_neutral(mapper)),
+            ),
+            NatElim(zero, step, n) => NatElim(
+                Box::new(zero.map_neutral(mapper)),
+                Box::new(step.map_neutral(mapper)),
+                Box::new(n.map_axiom(f)),
             ),
         }
     }
@@ -273,8 +533,26 @@ pub enum Val {
     Dt(PiSig, Plicit, Box<Self>, Closure),
     /// Row-polymorphic type literal.
     RowPoly(VarRec, Variants),
+    /// Dependent record types, with the label and the type of the first field.
+    /// The types of the other fields may refer to the first field,
+    /// and the end of the telescope is a (non-dependent) record type.
+    DepRec(String, Box<Self>, Closure),
+    /// Iso-recursive types, the body may refer to the type itself.
+    Fix(Closure),
+    /// Identity types, like `a = b`.
+    Id(Box<Self>, Box<Self>),
+    /// The proof of reflexivity, the only value of identity types.
+    Refl,
+    /// The type of natural numbers.
+    NatType,
+    /// Natural number literal.
+    Nat(BigUint),
     /// Row kind literals -- subtype of `Type`.
     RowKind(Level, VarRec, Vec<String>),
+    /// The type of labels.
+    LabelType,
+    /// Label literal.
+    Label(String),
     /// Constructor invocation.
     Cons(String, Box<Self>),
     /// Record literal, without extension.
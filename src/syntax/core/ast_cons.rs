@@ -10,10 +10,14 @@ impl Val {
     pub fn is_type(&self) -> bool {
         use Val::*;
         match self {
-            Type(..) | Dt(..) | RowPoly(..) | RowKind(..) | Neut(Neutral::Row(..)) => true,
+            Type(..) | Dt(..) | RowPoly(..) | DepRec(..) | Fix(..) | Id(..) | RowKind(..)
+            | LabelType | NatType => true,
+            Neut(Neutral::Row(..)) | Neut(Neutral::LabRow(..)) | Neut(Neutral::LabKind(..)) => true,
             // In case it's neutral, we use `is_universe` on its type.
             // In case it's a meta, we're supposed to solve it.
-            Lam(..) | Cons(..) | Rec(..) | Pair(..) | Neut(..) => false,
+            Lam(..) | Cons(..) | Rec(..) | Pair(..) | Label(..) | Refl | Nat(..) | Neut(..) => {
+                false
+            }
         }
     }
 
@@ -60,7 +64,7 @@ impl Val {
         Val::Neut(Neutral::SplitOn(split, Box::new(on)))
     }
 
-    pub fn or_split(split: CaseSplit, or: Neutral) -> Self {
+    pub fn or_split(split: CaseSplit, or: Val) -> Self {
         Val::Neut(Neutral::OrSplit(split, Box::new(or)))
     }
 
@@ -73,8 +77,11 @@ impl Val {
     }
 
     pub fn fresh_implicit() -> Self {
-        let axiom = Axiom::Implicit(unsafe { next_uid() });
-        Val::Neut(Neutral::Axi(axiom))
+        Self::implicit(unsafe { next_uid() })
+    }
+
+    pub(crate) fn implicit(uid: UID) -> Self {
+        Val::Neut(Neutral::Axi(Axiom::Implicit(uid)))
     }
 
     pub fn fresh_unimplemented(index: GI) -> Self {
@@ -98,6 +105,10 @@ impl Val {
         Val::Neut(Neutral::Proj(Box::new(record), field))
     }
 
+    pub fn remove(record: Neutral, field: String) -> Self {
+        Val::Neut(Neutral::Remove(Box::new(record), field))
+    }
+
     pub fn closure_dependent_type(kind: PiSig, visib: Plicit, param_ty: TVal, body: TVal) -> TVal {
         Self::dependent_type(kind, visib, param_ty, Closure::plain(body))
     }
@@ -106,6 +117,22 @@ impl Val {
         Val::Dt(kind, plicit, Box::new(param_type), closure)
     }
 
+    /// The fields are in the order of the telescope.
+    pub fn dependent_record_type(fields: Vec<(String, TVal)>) -> TVal {
+        let end = Self::record_type(Default::default());
+        (fields.into_iter().rev()).fold(end, |rest, (label, ty)| {
+            Val::DepRec(label, Box::new(ty), Closure::plain(rest))
+        })
+    }
+
+    pub fn fix(body: TVal) -> TVal {
+        Val::Fix(Closure::plain(body))
+    }
+
+    pub fn id(a: Self, b: Self) -> TVal {
+        Val::Id(Box::new(a), Box::new(b))
+    }
+
     pub fn variant_type(variants: Variants) -> TVal {
         Val::RowPoly(VarRec::Variant, variants)
     }
@@ -1,4 +1,4 @@
-use super::{Closure, Neutral, Val};
+use super::{CaseSplit, Closure, Neutral, Val, Variants};
 use voile_util::level::{
     calc_slice_plus_one_level, calc_tree_map_level, calc_tree_map_plus_one_level, lift_tree_map,
     Level, LevelCalcState, LiftEx,
@@ -10,12 +10,18 @@ impl LiftEx for Val {
     fn lift(self, levels: u32) -> Val {
         match self {
             Val::Type(l) => Val::Type(l + levels),
+            Val::LabelType | Val::Label(..) | Val::Refl | Val::NatType | Val::Nat(..) => self,
             Val::RowKind(l, k, ls) => Val::RowKind(l + levels, k, ls),
             Val::Lam(closure) => Val::Lam(closure.lift(levels)),
+            Val::Fix(closure) => Val::Fix(closure.lift(levels)),
+            Val::Id(a, b) => Val::id(a.lift(levels), b.lift(levels)),
             Val::Dt(kind, plicit, param_type, closure) => {
                 Val::dependent_type(kind, plicit, param_type.lift(levels), closure.lift(levels))
             }
             Val::RowPoly(kind, variants) => Val::RowPoly(kind, lift_tree_map(levels, variants)),
+            Val::DepRec(label, ty, closure) => {
+                Val::DepRec(label, Box::new(ty.lift(levels)), closure.lift(levels))
+            }
             Val::Rec(fields) => Val::Rec(lift_tree_map(levels, fields)),
             Val::Cons(name, e) => Val::cons(name, e.lift(levels)),
             Val::Pair(l, r) => Val::pair(l.lift(levels), r.lift(levels)),
@@ -25,15 +31,18 @@ impl LiftEx for Val {
 
     fn calc_level(&self) -> LevelCalcState {
         match self {
-            Val::Type(level) | Val::RowKind(level, ..) => Some(*level + 1),
+            Val::Type(level) | Val::RowKind(level, ..) => Some(level.clone() + 1),
+            Val::LabelType | Val::Label(..) | Val::Refl | Val::NatType | Val::Nat(..) => {
+                Some(Default::default())
+            }
             Val::RowPoly(_, variants) => calc_tree_map_level(variants),
             Val::Rec(fields) => calc_tree_map_level(fields),
-            Val::Dt(_, _, param_ty, closure) => {
+            Val::Dt(_, _, param_ty, closure) | Val::DepRec(_, param_ty, closure) => {
                 Some(param_ty.calc_level()?.max(closure.calc_level()?))
             }
-            Val::Lam(closure) => closure.calc_level(),
+            Val::Lam(closure) | Val::Fix(closure) => closure.calc_level(),
             Val::Neut(neut) => neut.calc_level(),
-            Val::Pair(l, r) => Some(l.calc_level()?.max(r.calc_level()?)),
+            Val::Pair(l, r) | Val::Id(l, r) => Some(l.calc_level()?.max(r.calc_level()?)),
             Val::Cons(_, e) => e.calc_level(),
         }
     }
@@ -61,10 +70,18 @@ impl LiftEx for Neutral {
             Ref(..) => None,
             Fst(expr) => expr.calc_level(),
             Snd(expr) => expr.calc_level(),
-            Proj(expr, ..) => expr.calc_level(),
+            Proj(expr, ..) | Remove(expr, ..) => expr.calc_level(),
             App(f, args) => calc_slice_plus_one_level(&**f, args),
             Rec(vs, ext) | Row(_, vs, ext) => calc_tree_map_plus_one_level(&**ext, vs),
-            SplitOn(split, on) | OrSplit(split, on) => calc_tree_map_plus_one_level(&**on, split),
+            SplitOn(split, on) => calc_tree_map_plus_one_level(&**on, split),
+            OrSplit(split, or) => calc_tree_map_plus_one_level(&**or, split),
+            LabProj(expr, ..) | LabCons(_, expr) | LabKind(_, expr) => expr.calc_level(),
+            LabRow(_, _, ty, rest) => Some(ty.calc_level()?.max(rest.calc_level()?)),
+            RowMap(function, row) => Some(function.calc_level()?.max(row.calc_level()?)),
+            RowDiff(row, ..) => row.calc_level(),
+            J(refl_case, _) => refl_case.calc_level(),
+            Suc(_, n) => n.calc_level(),
+            NatOp(_, a, b) | NatElim(a, b, _) => Some(a.calc_level()?.max(b.calc_level()?)),
         }
     }
 }
@@ -86,3 +103,117 @@ impl LiftEx for Closure {
         }
     }
 }
+
+/// Universe level substitution, used for level-polymorphism.
+impl Val {
+    /// Apply `f` on every universe level in `self`.
+    pub fn map_level(self, f: &mut impl FnMut(Level) -> Level) -> Self {
+        match self {
+            Val::Type(l) => Val::Type(f(l)),
+            Val::RowKind(l, k, ls) => Val::RowKind(f(l), k, ls),
+            Val::LabelType | Val::Label(..) | Val::Refl | Val::NatType | Val::Nat(..) => self,
+            Val::Lam(closure) => Val::Lam(closure.map_level(f)),
+            Val::Fix(closure) => Val::Fix(closure.map_level(f)),
+            Val::Id(a, b) => {
+                let a = a.map_level(f);
+                Val::id(a, b.map_level(f))
+            }
+            Val::Dt(kind, plicit, param_type, closure) => {
+                let param_type = param_type.map_level(f);
+                Val::dependent_type(kind, plicit, param_type, closure.map_level(f))
+            }
+            Val::RowPoly(kind, variants) => Val::RowPoly(kind, map_level_tree_map(variants, f)),
+            Val::DepRec(label, ty, closure) => {
+                let ty = Box::new(ty.map_level(f));
+                Val::DepRec(label, ty, closure.map_level(f))
+            }
+            Val::Rec(fields) => Val::Rec(map_level_tree_map(fields, f)),
+            Val::Cons(name, e) => Val::cons(name, e.map_level(f)),
+            Val::Pair(l, r) => {
+                let l = l.map_level(f);
+                Val::pair(l, r.map_level(f))
+            }
+            Val::Neut(neut) => Val::Neut(neut.map_level(f)),
+        }
+    }
+}
+
+impl Neutral {
+    pub fn map_level(self, f: &mut impl FnMut(Level) -> Level) -> Self {
+        use super::Neutral::*;
+        match self {
+            Var(..) | Ref(..) | Meta(..) | Axi(..) => self,
+            Lift(n, expr) => Lift(n, Box::new(expr.map_level(f))),
+            App(fun, args) => {
+                let fun = fun.map_level(f);
+                App(
+                    Box::new(fun),
+                    args.into_iter().map(|a| a.map_level(f)).collect(),
+                )
+            }
+            Fst(expr) => Fst(Box::new(expr.map_level(f))),
+            Snd(expr) => Snd(Box::new(expr.map_level(f))),
+            Proj(expr, field) => Proj(Box::new(expr.map_level(f)), field),
+            Remove(expr, field) => Remove(Box::new(expr.map_level(f)), field),
+            Row(kind, variants, ext) => {
+                let variants = map_level_tree_map(variants, f);
+                Row(kind, variants, Box::new(ext.map_level(f)))
+            }
+            Rec(fields, ext) => {
+                let fields = map_level_tree_map(fields, f);
+                Rec(fields, Box::new(ext.map_level(f)))
+            }
+            SplitOn(split, on) => {
+                let split = map_level_split(split, f);
+                SplitOn(split, Box::new(on.map_level(f)))
+            }
+            OrSplit(split, or) => {
+                let split = map_level_split(split, f);
+                OrSplit(split, Box::new(or.map_level(f)))
+            }
+            LabProj(rec, label) => LabProj(Box::new(rec.map_level(f)), label),
+            LabCons(label, a) => LabCons(label, Box::new(a.map_level(f))),
+            LabRow(kind, label, ty, rest) => {
+                let ty = Box::new(ty.map_level(f));
+                LabRow(kind, label, ty, Box::new(rest.map_level(f)))
+            }
+            LabKind(label, kind) => LabKind(label, Box::new(kind.map_level(f))),
+            RowMap(function, row) => {
+                let function = Box::new(function.map_level(f));
+                RowMap(function, Box::new(row.map_level(f)))
+            }
+            RowDiff(row, labels) => RowDiff(Box::new(row.map_level(f)), labels),
+            J(refl_case, proof) => J(Box::new(refl_case.map_level(f)), proof),
+            Suc(k, n) => Suc(k, Box::new(n.map_level(f))),
+            NatOp(op, a, b) => {
+                let a = Box::new(a.map_level(f));
+                NatOp(op, a, Box::new(b.map_level(f)))
+            }
+            NatElim(zero, step, n) => {
+                let zero = Box::new(zero.map_level(f));
+                NatElim(zero, Box::new(step.map_level(f)), n)
+            }
+        }
+    }
+}
+
+impl Closure {
+    pub fn map_level(self, f: &mut impl FnMut(Level) -> Level) -> Self {
+        use super::Closure::*;
+        match self {
+            Plain(body) => Self::plain(body.map_level(f)),
+            Tree(split) => Tree(map_level_split(split, f)),
+        }
+    }
+}
+
+fn map_level_tree_map(map: Variants, f: &mut impl FnMut(Level) -> Level) -> Variants {
+    map.into_iter().map(|(k, v)| (k, v.map_level(f))).collect()
+}
+
+fn map_level_split(split: CaseSplit, f: &mut impl FnMut(Level) -> Level) -> CaseSplit {
+    split
+        .into_iter()
+        .map(|(k, v)| (k, v.map_level(f)))
+        .collect()
+}
@@ -13,6 +13,8 @@ mod ast;
 mod ast_cons;
 /// Implementations for `Level`.
 mod level;
+/// Primitive operations on natural numbers.
+mod nat;
 /// Definition and implementations for `TraverseNeutral`.
 mod neut_iter;
 mod pretty;
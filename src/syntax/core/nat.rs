@@ -0,0 +1,143 @@
+use std::convert::Infallible;
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use voile_util::axiom::Axiom;
+use voile_util::tags::NatOp;
+use voile_util::uid::DBI;
+
+use super::{Closure, Neutral, TVal, Val, Variants};
+
+/// Primitive operations on natural numbers.
+impl Val {
+    /// Adding a literal to a natural number, so the successor of `n` is `n.plus(1)`.
+    pub fn plus(self, k: BigUint) -> Self {
+        match self {
+            n if k.is_zero() => n,
+            Val::Nat(n) => Val::Nat(n + k),
+            Val::Neut(Neutral::Suc(j, n)) => Val::Neut(Neutral::Suc(j + k, n)),
+            Val::Neut(n) => Val::Neut(Neutral::Suc(k, Box::new(n))),
+            e => panic!("Cannot add to `{}`.", e),
+        }
+    }
+
+    /// The result of a primitive operation, it's stuck if the operands are not
+    /// known enough. The literals added to neutral operands are taken into account.
+    pub fn nat_op(op: NatOp, a: Self, b: Self) -> Self {
+        use {NatOp::*, Neutral::Suc, Val::*};
+        match (op, a, b) {
+            (Add, Nat(k), n) | (Add, n, Nat(k)) => n.plus(k),
+            (Add, Neut(Suc(k, a)), b) | (Add, b, Neut(Suc(k, a))) => {
+                Self::nat_op(Add, Neut(*a), b).plus(k)
+            }
+            (Sub, Nat(a), Nat(b)) => Nat(if a > b { a - b } else { Zero::zero() }),
+            (Sub, a, Nat(k)) if k.is_zero() => a,
+            (Sub, Nat(k), _) if k.is_zero() => Nat(k),
+            (Sub, Neut(Suc(k, a)), Nat(j)) if j <= k => Neut(*a).plus(k - j),
+            (Sub, Neut(Suc(k, a)), Nat(j)) => Self::nat_op(Sub, Neut(*a), Nat(j - k)),
+            (Sub, Neut(Suc(k, a)), Neut(Suc(j, b))) if j <= k => {
+                Self::nat_op(Sub, Neut(*a).plus(k - j), Neut(*b))
+            }
+            (Sub, Neut(Suc(k, a)), Neut(Suc(j, b))) => {
+                Self::nat_op(Sub, Neut(*a), Neut(*b).plus(j - k))
+            }
+            (Mul, Nat(a), Nat(b)) => Nat(a * b),
+            (Mul, Nat(k), _) | (Mul, _, Nat(k)) if k.is_zero() => Nat(k),
+            (Mul, Nat(k), n) | (Mul, n, Nat(k)) if k.is_one() => n,
+            (op @ Lt, a, b) | (op @ Le, a, b) => Self::compare(op, a, b),
+            (op, a, b) => Neut(Neutral::NatOp(op, Box::new(a), Box::new(b))),
+        }
+    }
+
+    /// `a < b` or `a <= b`, the literals added to both sides are cancelled.
+    fn compare(op: NatOp, a: Self, b: Self) -> Self {
+        use {Neutral::Suc, Val::*};
+        let strict = op == NatOp::Lt;
+        match (a, b) {
+            (Nat(a), Nat(b)) => Self::bool(if strict { a < b } else { a <= b }),
+            (Nat(k), _) if !strict && k.is_zero() => Self::bool(true),
+            (_, Nat(k)) if strict && k.is_zero() => Self::bool(false),
+            (Neut(Suc(k, _)), Nat(j)) if k > j || strict && k == j => Self::bool(false),
+            (Neut(Suc(k, a)), Nat(j)) => Self::compare(op, Neut(*a), Nat(j - k)),
+            (Nat(j), Neut(Suc(k, _))) if j < k || !strict && j == k => Self::bool(true),
+            (Nat(j), Neut(Suc(k, b))) => Self::compare(op, Nat(j - k), Neut(*b)),
+            (Neut(Suc(k, a)), Neut(Suc(j, b))) if j <= k => {
+                Self::compare(op, Neut(*a).plus(k - j), Neut(*b))
+            }
+            (Neut(Suc(k, a)), Neut(Suc(j, b))) => Self::compare(op, Neut(*a), Neut(*b).plus(j - k)),
+            (a, b) => Neut(Neutral::NatOp(op, Box::new(a), Box::new(b))),
+        }
+    }
+
+    /// The results of comparisons, of type [`bool_type`](Val::bool_type).
+    pub fn bool(b: bool) -> Self {
+        let label = if b { "True" } else { "False" };
+        Val::cons(label.to_owned(), Val::Rec(Default::default()))
+    }
+
+    /// `Sum { False: Rec {}; True: Rec {}; }`.
+    pub fn bool_type() -> TVal {
+        let unit = || Val::record_type(Default::default());
+        let mut variants = Variants::new();
+        variants.insert("False".to_owned(), unit());
+        variants.insert("True".to_owned(), unit());
+        Val::variant_type(variants)
+    }
+
+    /// Eliminating a natural number, `step` is applied to each predecessor
+    /// and the result for it, from zero (or the neutral part) up.
+    pub fn eliminate_nat(self, zero: Val, step: Val) -> Self {
+        let (base, result, count) = match self {
+            Val::Nat(n) => (Val::Nat(Zero::zero()), zero, n),
+            Val::Neut(Neutral::Suc(k, n)) => {
+                let result = Val::Neut(*n.clone()).eliminate_nat(zero, step.clone());
+                (Val::Neut(*n), result, k)
+            }
+            Val::Neut(n) => {
+                let (zero, step) = (Box::new(zero), Box::new(step));
+                return Val::Neut(Neutral::NatElim(zero, step, Box::new(n)));
+            }
+            e => panic!("Cannot eliminate `{}`.", e),
+        };
+        let mut result = result;
+        let mut i = BigUint::zero();
+        while i < count {
+            let pred = base.clone().plus(i.clone());
+            result = step.clone().apply_step(pred).apply_step(result);
+            i += 1u32;
+        }
+        result
+    }
+
+    /// Apply the step of an elimination, which is reduced under the binders
+    /// of the enclosing lambdas when the number becomes known.
+    /// Unlike [`apply`](Val::apply), the variables referring out of the step
+    /// and the ones in the argument are shifted.
+    fn apply_step(self, arg: Val) -> Self {
+        use {Axiom::Generated, Neutral::*};
+        let body = match self {
+            Val::Lam(Closure::Plain(body)) => *body,
+            step => return step.apply(arg),
+        };
+        map_leaves(body, |neut, depth| match neut {
+            Var(DBI(i)) if i == depth => shift_vars(arg.clone(), depth).attach_dbi(DBI(depth)),
+            Var(DBI(i)) if i > depth => Val::var(DBI(i - 1)),
+            Axi(Generated(uid, DBI(i))) if i > depth => Val::Neut(Axi(Generated(uid, DBI(i - 1)))),
+            neut => Val::Neut(neut),
+        })
+    }
+}
+
+fn map_leaves(val: Val, mut f: impl FnMut(Neutral, usize) -> Val) -> Val {
+    let result: Result<_, Infallible> =
+        val.try_map_leaves(0, &mut |neut, depth| Ok(f(neut, depth)));
+    result.unwrap()
+}
+
+/// Move the variables referring out of `val` under `by` binders.
+fn shift_vars(val: Val, by: usize) -> Val {
+    map_leaves(val, |neut, depth| match neut {
+        Neutral::Var(DBI(i)) if i >= depth => Val::var(DBI(i + by)),
+        neut => Val::Neut(neut),
+    })
+}
@@ -46,6 +46,13 @@ impl TraverseNeutral for Val {
                 param_type.try_map_neutral(f)?,
                 closure.try_map_neutral(f)?,
             )),
+            Val::DepRec(label, ty, closure) => Ok(Self::DepRec(
+                label,
+                Box::new(ty.try_map_neutral(f)?),
+                closure.try_map_neutral(f)?,
+            )),
+            Val::Fix(closure) => closure.try_map_neutral(f).map(Self::Fix),
+            Val::Id(a, b) => Ok(Self::id(a.try_map_neutral(f)?, b.try_map_neutral(f)?)),
             Val::Cons(name, a) => Ok(Self::cons(name, a.try_map_neutral(f)?)),
             e => Ok(e),
         }
@@ -58,7 +65,7 @@ impl TraverseNeutral for Val {
     ) -> Result<R, E> {
         match self {
             Val::Neut(n) => f(init, n),
-            Val::Pair(a, b) => a
+            Val::Pair(a, b) | Val::Id(a, b) => a
                 .try_fold_neutral(init, f)
                 .and_then(|r| b.try_fold_neutral(r, f)),
             Val::RowPoly(_, v) => v
@@ -67,12 +74,18 @@ impl TraverseNeutral for Val {
             Val::Rec(fields) => fields
                 .into_iter()
                 .try_fold(init, |a, (_, v)| v.try_fold_neutral(a, f)),
-            Val::Lam(closure) => closure.try_fold_neutral(init, f),
-            Val::Dt(_, _, param_ty, closure) => closure
+            Val::Lam(closure) | Val::Fix(closure) => closure.try_fold_neutral(init, f),
+            Val::Dt(_, _, param_ty, closure) | Val::DepRec(_, param_ty, closure) => closure
                 .try_fold_neutral(init, f)
                 .and_then(|r| param_ty.try_fold_neutral(r, f)),
             Val::Cons(_, a) => a.try_fold_neutral(init, f),
-            Val::Type(..) | Val::RowKind(..) => Ok(init),
+            Val::Type(..)
+            | Val::RowKind(..)
+            | Val::LabelType
+            | Val::Label(..)
+            | Val::Refl
+            | Val::NatType
+            | Val::Nat(..) => Ok(init),
         }
     }
 }
@@ -33,6 +33,7 @@ impl Display for Neutral {
             Fst(p) => write!(f, "({}.1)", p),
             Snd(p) => write!(f, "({}.2)", p),
             Proj(rec, field) => write!(f, "({}.{})", rec, field),
+            Remove(rec, field) => write!(f, "({} [-{}])", rec, field),
             Lift(levels, p) => write!(f, "(^[{:?}] {})", levels, p),
             Row(kind, variants, ext) => {
                 write!(f, "{} {{", kind)?;
@@ -44,6 +45,16 @@ impl Display for Neutral {
                 write_variants(f, fields, " =")?;
                 write!(f, ", ... = {}|}}", ext)
             }
+            LabProj(rec, label) => write!(f, "({}.{})", rec, label),
+            LabCons(label, a) => write!(f, "(@{} {})", label, a),
+            LabRow(kind, label, ty, rest) => write!(f, "{} {{{}: {} | {}}}", kind, label, ty, rest),
+            LabKind(label, kind) => write!(f, "({} lacks {})", kind, label),
+            RowMap(function, row) => write!(f, "({} <$> {})", function, row),
+            RowDiff(row, labels) => write!(f, "({} -- [{}])", row, labels.join(" ")),
+            J(refl_case, proof) => write!(f, "(J {} with {})", proof, refl_case),
+            Suc(k, n) => write!(f, "({} + {})", n, k),
+            NatOp(op, a, b) => write!(f, "({} {} {})", a, op, b),
+            NatElim(zero, step, n) => write!(f, "(natElim {} with {} or {})", n, zero, step),
         }
     }
 }
@@ -86,12 +97,31 @@ impl Display for Val {
                 }
                 f.write_str("}")
             }
+            Val::LabelType => f.write_str("Label"),
+            Val::Label(label) => write!(f, "'{}", label),
             Val::Lam(clos) => write!(f, "(\\ {})", clos),
+            Val::Fix(clos) => write!(f, "(Fix {})", clos),
+            Val::Id(a, b) => write!(f, "({} = {})", a, b),
+            Val::Refl => f.write_str("refl"),
+            Val::NatType => f.write_str("Nat"),
+            Val::Nat(n) => n.fmt(f),
             Val::RowPoly(kind, variants) => {
                 write!(f, "{} {{", kind)?;
                 write_variants(f, variants, ":")?;
                 f.write_str("}")
             }
+            Val::DepRec(..) => {
+                f.write_str("DRec {")?;
+                let mut rest = self;
+                while let Val::DepRec(label, ty, Closure::Plain(next)) = rest {
+                    write!(f, "{}: {}; ", label, ty)?;
+                    rest = next;
+                }
+                match rest {
+                    Val::RowPoly(_, fields) if fields.is_empty() => f.write_str("}"),
+                    rest => write!(f, "| {}}}", rest),
+                }
+            }
             Val::Rec(fields) => {
                 f.write_str("{|")?;
                 write_variants(f, fields, " =")?;
@@ -99,7 +129,10 @@ impl Display for Val {
             }
             Val::Dt(Pi, Plicit::Ex, param_ty, clos) => write!(f, "({} -> {})", param_ty, clos),
             Val::Dt(Pi, Plicit::Im, param_ty, clos) => write!(f, "({{{}}} -> {})", param_ty, clos),
-            Val::Dt(Sigma, _, param_ty, clos) => write!(f, "({} * {})", param_ty, clos),
+            Val::Dt(Sigma, Plicit::Ex, param_ty, clos) => write!(f, "({} * {})", param_ty, clos),
+            Val::Dt(Sigma, Plicit::Im, param_ty, clos) => {
+                write!(f, "({{{}}} * {})", param_ty, clos)
+            }
             Val::Pair(fst, snd) => write!(f, "({}, {})", fst, snd),
             Val::Neut(neut) => neut.fmt(f),
             Val::Cons(name, a) => write!(f, "(@{} {})", name, a),
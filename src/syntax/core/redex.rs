@@ -1,5 +1,8 @@
+use std::convert::Infallible;
+
+use voile_util::axiom::Axiom;
 use voile_util::level::LiftEx;
-use voile_util::uid::DBI;
+use voile_util::uid::{next_uid, DBI, UID};
 
 use super::{CaseSplit, Closure, Neutral, Val, Variants};
 
@@ -23,12 +26,22 @@ impl RedEx for Val {
             ),
             Val::Neut(neutral_value) => neutral_value.reduce_with_dbi(arg, dbi),
             Val::Lam(closure) => Val::Lam(closure.reduce_with_dbi(arg, dbi + 1)),
+            Val::Fix(closure) => Val::Fix(closure.reduce_with_dbi(arg, dbi + 1)),
+            Val::Id(a, b) => Val::id(
+                a.reduce_with_dbi_borrow(&arg, dbi),
+                b.reduce_with_dbi(arg, dbi),
+            ),
             Val::Dt(kind, param_plicit, param_type, closure) => Val::dependent_type(
                 kind,
                 param_plicit,
                 param_type.reduce_with_dbi_borrow(&arg, dbi),
                 closure.reduce_with_dbi(arg, dbi + 1),
             ),
+            Val::DepRec(label, ty, closure) => Val::DepRec(
+                label,
+                Box::new(ty.reduce_with_dbi_borrow(&arg, dbi)),
+                closure.reduce_with_dbi(arg, dbi + 1),
+            ),
             Val::RowPoly(kind, variants) => {
                 Val::RowPoly(kind, reduce_variants_with_dbi(variants, dbi, &arg))
             }
@@ -36,6 +49,11 @@ impl RedEx for Val {
             Val::Cons(name, a) => Self::cons(name, a.reduce_with_dbi(arg, dbi)),
             Val::Type(n) => Val::Type(n),
             Val::RowKind(l, k, ls) => Val::RowKind(l, k, ls),
+            Val::LabelType => Val::LabelType,
+            Val::Label(label) => Val::Label(label),
+            Val::Refl => Val::Refl,
+            Val::NatType => Val::NatType,
+            Val::Nat(n) => Val::Nat(n),
         }
     }
 
@@ -47,12 +65,22 @@ impl RedEx for Val {
             ),
             Val::Neut(neutral_value) => neutral_value.reduce_with_dbi_borrow(arg, dbi),
             Val::Lam(closure) => Val::Lam(closure.reduce_with_dbi_borrow(arg, dbi + 1)),
+            Val::Fix(closure) => Val::Fix(closure.reduce_with_dbi_borrow(arg, dbi + 1)),
+            Val::Id(a, b) => Val::id(
+                a.reduce_with_dbi_borrow(arg, dbi),
+                b.reduce_with_dbi_borrow(arg, dbi),
+            ),
             Val::Dt(kind, param_plicit, param_type, closure) => Val::dependent_type(
                 kind,
                 param_plicit,
                 param_type.reduce_with_dbi_borrow(arg, dbi),
                 closure.reduce_with_dbi_borrow(arg, dbi + 1),
             ),
+            Val::DepRec(label, ty, closure) => Val::DepRec(
+                label,
+                Box::new(ty.reduce_with_dbi_borrow(arg, dbi)),
+                closure.reduce_with_dbi_borrow(arg, dbi + 1),
+            ),
             Val::RowPoly(kind, variants) => {
                 Val::RowPoly(kind, reduce_variants_with_dbi(variants, dbi, arg))
             }
@@ -60,6 +88,11 @@ impl RedEx for Val {
             Val::Cons(name, a) => Self::cons(name, a.reduce_with_dbi_borrow(arg, dbi)),
             Val::Type(n) => Val::Type(n),
             Val::RowKind(l, k, ls) => Val::RowKind(l, k, ls),
+            Val::LabelType => Val::LabelType,
+            Val::Label(label) => Val::Label(label),
+            Val::Refl => Val::Refl,
+            Val::NatType => Val::NatType,
+            Val::Nat(n) => Val::Nat(n),
         }
     }
 }
@@ -77,18 +110,25 @@ impl RedEx for Neutral {
                 .into_iter()
                 .fold(f.reduce_with_dbi_borrow(&arg, dbi), |f, a| {
                     // Do we need to `reduce` after `apply` again?
-                    f.apply(a.reduce_with_dbi_borrow(&arg, dbi))
+                    f.apply_under(a.reduce_with_dbi_borrow(&arg, dbi), dbi)
                 }),
-            SplitOn(split, obj) => Val::case_tree(split)
-                .apply(obj.reduce_with_dbi_borrow(&arg, dbi))
-                // further reduce because the `split` is not yet reduced
-                .reduce_with_dbi(arg, dbi),
-            OrSplit(split, or) => Val::case_tree(split)
-                .split_extend(or.reduce_with_dbi_borrow(&arg, dbi))
-                .reduce_with_dbi(arg, dbi),
+            SplitOn(split, obj) => match obj.reduce_with_dbi_borrow(&arg, dbi) {
+                // The split is still stuck, so only the clauses are reduced.
+                Val::Neut(obj) => {
+                    Val::split_on(reduce_case_tree_with_dbi(split, dbi + 1, &arg), obj)
+                }
+                // The clauses are under the binder of the constructor's argument.
+                obj => Val::case_tree(reduce_case_tree_with_dbi(split, dbi + 1, &arg))
+                    .apply_under(obj, dbi),
+            },
+            OrSplit(split, or) => {
+                let split = reduce_case_tree_with_dbi(split, dbi + 1, &arg);
+                Val::case_tree(split).split_extend(or.reduce_with_dbi(arg, dbi))
+            }
             Fst(pair) => pair.reduce_with_dbi(arg, dbi).first(),
             Snd(pair) => pair.reduce_with_dbi(arg, dbi).second(),
             Proj(rec, field) => rec.reduce_with_dbi(arg, dbi).project(field),
+            Remove(rec, field) => rec.reduce_with_dbi(arg, dbi).remove_field(&field),
             Lift(levels, neut) => neut.reduce_with_dbi(arg, dbi).lift(levels),
             Row(kind, variants, ext) => {
                 let variants = reduce_variants_with_dbi(variants, dbi, &arg);
@@ -98,7 +138,43 @@ impl RedEx for Neutral {
             Rec(fields, ext) => {
                 let fields = reduce_variants_with_dbi(fields, dbi, &arg);
                 let ext = ext.reduce_with_dbi(arg, dbi);
-                Val::Rec(fields).row_extend(ext)
+                Val::Rec(fields).rec_extend(ext)
+            }
+            LabProj(rec, label) => {
+                let rec = rec.reduce_with_dbi_borrow(&arg, dbi);
+                rec.project_label(label.reduce_with_dbi(arg, dbi))
+            }
+            LabCons(label, a) => {
+                let label = label.reduce_with_dbi_borrow(&arg, dbi);
+                Val::label_cons(label, a.reduce_with_dbi(arg, dbi))
+            }
+            LabRow(kind, label, ty, rest) => {
+                let label = label.reduce_with_dbi_borrow(&arg, dbi);
+                let ty = ty.reduce_with_dbi_borrow(&arg, dbi);
+                Val::label_row(kind, label, ty, rest.reduce_with_dbi(arg, dbi))
+            }
+            LabKind(label, kind) => {
+                let label = label.reduce_with_dbi_borrow(&arg, dbi);
+                Val::label_kind(label, kind.reduce_with_dbi(arg, dbi))
+            }
+            RowMap(function, row) => {
+                let function = function.reduce_with_dbi_borrow(&arg, dbi);
+                row.reduce_with_dbi(arg, dbi).row_map(function)
+            }
+            RowDiff(row, labels) => row.reduce_with_dbi(arg, dbi).row_diff(&labels),
+            J(refl_case, proof) => {
+                let refl_case = refl_case.reduce_with_dbi_borrow(&arg, dbi);
+                proof.reduce_with_dbi(arg, dbi).eliminate_id(refl_case)
+            }
+            Suc(k, n) => n.reduce_with_dbi(arg, dbi).plus(k),
+            NatOp(op, a, b) => {
+                let a = a.reduce_with_dbi_borrow(&arg, dbi);
+                Val::nat_op(op, a, b.reduce_with_dbi(arg, dbi))
+            }
+            NatElim(zero, step, n) => {
+                let zero = zero.reduce_with_dbi_borrow(&arg, dbi);
+                let step = step.reduce_with_dbi_borrow(&arg, dbi);
+                n.reduce_with_dbi(arg, dbi).eliminate_nat(zero, step)
             }
         }
     }
@@ -115,18 +191,25 @@ impl RedEx for Neutral {
                 .into_iter()
                 .fold(f.reduce_with_dbi_borrow(arg, dbi), |f, a| {
                     // Do we need to `reduce` after `apply` again?
-                    f.apply(a.reduce_with_dbi_borrow(arg, dbi))
+                    f.apply_under(a.reduce_with_dbi_borrow(arg, dbi), dbi)
                 }),
-            SplitOn(split, obj) => Val::case_tree(split)
-                .apply(obj.reduce_with_dbi_borrow(&arg, dbi))
-                // further reduce because the `split` is not yet reduced
-                .reduce_with_dbi_borrow(arg, dbi),
-            OrSplit(split, or) => Val::case_tree(split)
-                .split_extend(or.reduce_with_dbi_borrow(arg, dbi))
-                .reduce_with_dbi_borrow(arg, dbi),
+            SplitOn(split, obj) => match obj.reduce_with_dbi_borrow(arg, dbi) {
+                // The split is still stuck, so only the clauses are reduced.
+                Val::Neut(obj) => {
+                    Val::split_on(reduce_case_tree_with_dbi(split, dbi + 1, arg), obj)
+                }
+                // The clauses are under the binder of the constructor's argument.
+                obj => Val::case_tree(reduce_case_tree_with_dbi(split, dbi + 1, arg))
+                    .apply_under(obj, dbi),
+            },
+            OrSplit(split, or) => {
+                let split = reduce_case_tree_with_dbi(split, dbi + 1, arg);
+                Val::case_tree(split).split_extend(or.reduce_with_dbi_borrow(arg, dbi))
+            }
             Fst(pair) => pair.reduce_with_dbi_borrow(arg, dbi).first(),
             Snd(pair) => pair.reduce_with_dbi_borrow(arg, dbi).second(),
             Proj(pair, field) => pair.reduce_with_dbi_borrow(arg, dbi).project(field),
+            Remove(rec, field) => rec.reduce_with_dbi_borrow(arg, dbi).remove_field(&field),
             Lift(levels, neut) => neut.reduce_with_dbi_borrow(arg, dbi).lift(levels),
             Row(kind, variants, ext) => {
                 let variants = reduce_variants_with_dbi(variants, dbi, arg);
@@ -136,7 +219,45 @@ impl RedEx for Neutral {
             Rec(fields, ext) => {
                 let fields = reduce_variants_with_dbi(fields, dbi, arg);
                 let ext = ext.reduce_with_dbi_borrow(&arg, dbi);
-                Val::Rec(fields).row_extend(ext)
+                Val::Rec(fields).rec_extend(ext)
+            }
+            LabProj(rec, label) => {
+                let rec = rec.reduce_with_dbi_borrow(arg, dbi);
+                rec.project_label(label.reduce_with_dbi_borrow(arg, dbi))
+            }
+            LabCons(label, a) => {
+                let label = label.reduce_with_dbi_borrow(arg, dbi);
+                Val::label_cons(label, a.reduce_with_dbi_borrow(arg, dbi))
+            }
+            LabRow(kind, label, ty, rest) => {
+                let label = label.reduce_with_dbi_borrow(arg, dbi);
+                let ty = ty.reduce_with_dbi_borrow(arg, dbi);
+                Val::label_row(kind, label, ty, rest.reduce_with_dbi_borrow(arg, dbi))
+            }
+            LabKind(label, kind) => {
+                let label = label.reduce_with_dbi_borrow(arg, dbi);
+                Val::label_kind(label, kind.reduce_with_dbi_borrow(arg, dbi))
+            }
+            RowMap(function, row) => {
+                let function = function.reduce_with_dbi_borrow(arg, dbi);
+                row.reduce_with_dbi_borrow(arg, dbi).row_map(function)
+            }
+            RowDiff(row, labels) => row.reduce_with_dbi_borrow(arg, dbi).row_diff(&labels),
+            J(refl_case, proof) => {
+                let refl_case = refl_case.reduce_with_dbi_borrow(arg, dbi);
+                proof
+                    .reduce_with_dbi_borrow(arg, dbi)
+                    .eliminate_id(refl_case)
+            }
+            Suc(k, n) => n.reduce_with_dbi_borrow(arg, dbi).plus(k),
+            NatOp(op, a, b) => {
+                let a = a.reduce_with_dbi_borrow(arg, dbi);
+                Val::nat_op(op, a, b.reduce_with_dbi_borrow(arg, dbi))
+            }
+            NatElim(zero, step, n) => {
+                let zero = zero.reduce_with_dbi_borrow(arg, dbi);
+                let step = step.reduce_with_dbi_borrow(arg, dbi);
+                n.reduce_with_dbi_borrow(arg, dbi).eliminate_nat(zero, step)
             }
         }
     }
@@ -173,3 +294,226 @@ fn reduce_case_tree_with_dbi(cases: CaseSplit, dbi: DBI, arg: &Val) -> CaseSplit
         .map(|(name, ty)| (name, ty.reduce_with_dbi_borrow(&arg, dbi)))
         .collect()
 }
+
+/// Rebuild values by mapping their leaves, see [`try_map_leaves`](Val::try_map_leaves).
+impl Val {
+    /// Map the variables, references, metas, axioms and applied metas
+    /// (the leaves of neutral values) with `f`, which also receives the number
+    /// of binders above the leaf (starting from `depth`).
+    /// The eliminations are applied again, so the redexes created by `f` are reduced.
+    ///
+    /// The arguments of applied metas are given to `f` as-is.
+    pub fn try_map_leaves<E>(
+        self,
+        depth: usize,
+        f: &mut impl FnMut(Neutral, usize) -> Result<Val, E>,
+    ) -> Result<Val, E> {
+        match self {
+            Val::Pair(a, b) => Ok(Val::pair(
+                a.try_map_leaves(depth, f)?,
+                b.try_map_leaves(depth, f)?,
+            )),
+            Val::Neut(neut) => neut.try_map_leaves(depth, f),
+            Val::Lam(closure) => closure.try_map_leaves(depth + 1, f).map(Val::Lam),
+            Val::Fix(closure) => closure.try_map_leaves(depth + 1, f).map(Val::Fix),
+            Val::Id(a, b) => Ok(Val::id(
+                a.try_map_leaves(depth, f)?,
+                b.try_map_leaves(depth, f)?,
+            )),
+            Val::Dt(kind, plicit, param_type, closure) => Ok(Val::dependent_type(
+                kind,
+                plicit,
+                param_type.try_map_leaves(depth, f)?,
+                closure.try_map_leaves(depth + 1, f)?,
+            )),
+            Val::DepRec(label, ty, closure) => Ok(Val::DepRec(
+                label,
+                Box::new(ty.try_map_leaves(depth, f)?),
+                closure.try_map_leaves(depth + 1, f)?,
+            )),
+            Val::RowPoly(kind, variants) => {
+                map_variants_leaves(variants, depth, f).map(|v| Val::RowPoly(kind, v))
+            }
+            Val::Rec(fields) => map_variants_leaves(fields, depth, f).map(Val::Rec),
+            Val::Cons(name, a) => Ok(Val::cons(name, a.try_map_leaves(depth, f)?)),
+            Val::Type(..)
+            | Val::RowKind(..)
+            | Val::LabelType
+            | Val::Label(..)
+            | Val::Refl
+            | Val::NatType
+            | Val::Nat(..) => Ok(self),
+        }
+    }
+}
+
+impl Val {
+    /// Map the axioms with `f`, which also receives the number of binders above the axiom.
+    pub(crate) fn map_axiom_under(self, f: &mut impl FnMut(Axiom, usize) -> Axiom) -> Val {
+        let result: Result<_, Infallible> = self.try_map_leaves(0, &mut |neut, depth| match neut {
+            Neutral::Axi(axiom) => Ok(Val::Neut(Neutral::Axi(f(axiom, depth)))),
+            neut => Ok(Val::Neut(neut)),
+        });
+        result.unwrap()
+    }
+
+    /// Apply to `arg`, where both are under `dbi` binders of the value being reduced.
+    /// Beta reduction only shifts the generated values, so the variables bound by
+    /// those binders are turned into generated values while applying.
+    fn apply_under(self, arg: Val, DBI(dbi): DBI) -> Val {
+        if dbi == 0 {
+            return self.apply(arg);
+        }
+        let uids: Vec<UID> = (0..dbi).map(|_| unsafe { next_uid() }).collect();
+        let applied = (self.generate_bound(&uids)).apply(arg.generate_bound(&uids));
+        applied.map_axiom(&mut |a| match a {
+            Axiom::Generated(uid, dbi) if uids.contains(&uid) => Neutral::Var(dbi),
+            a => Neutral::Axi(a),
+        })
+    }
+
+    /// Turn the variables bound by the binders above this value
+    /// (the `i`th one is `uids[i]`) into generated values.
+    fn generate_bound(self, uids: &[UID]) -> Val {
+        let result: Result<_, Infallible> = self.try_map_leaves(0, &mut |neut, depth| match neut {
+            Neutral::Var(DBI(i)) if i >= depth && i < depth + uids.len() => {
+                let generated = Axiom::Generated(uids[i - depth], DBI(i));
+                Ok(Val::Neut(Neutral::Axi(generated)))
+            }
+            neut => Ok(Val::Neut(neut)),
+        });
+        result.unwrap()
+    }
+}
+
+impl Closure {
+    /// Beta reduction removes the binder of the closure,
+    /// so the generated values referring out of the body are shifted down.
+    pub(crate) fn strengthen(self) -> Closure {
+        let result: Result<_, Infallible> = self.try_map_leaves(0, &mut |neut, depth| match neut {
+            Neutral::Axi(Axiom::Generated(uid, DBI(dbi))) if dbi > depth => {
+                Ok(Val::Neut(Neutral::Axi(Axiom::Generated(uid, DBI(dbi - 1)))))
+            }
+            neut => Ok(Val::Neut(neut)),
+        });
+        result.unwrap()
+    }
+}
+
+impl Neutral {
+    fn try_map_leaves<E>(
+        self,
+        depth: usize,
+        f: &mut impl FnMut(Neutral, usize) -> Result<Val, E>,
+    ) -> Result<Val, E> {
+        use Neutral::*;
+        match self {
+            Var(..) | Ref(..) | Meta(..) | Axi(..) => f(self, depth),
+            App(head, args) => match *head {
+                Meta(mi) => f(App(Box::new(Meta(mi)), args), depth),
+                head => args
+                    .into_iter()
+                    .try_fold(head.try_map_leaves(depth, f)?, |head, arg| {
+                        Ok(head.apply(arg.try_map_leaves(depth, f)?))
+                    }),
+            },
+            SplitOn(split, obj) => {
+                let split = map_case_tree_leaves(split, depth + 1, f)?;
+                Ok(Val::case_tree(split).apply(obj.try_map_leaves(depth, f)?))
+            }
+            OrSplit(split, or) => {
+                let split = map_case_tree_leaves(split, depth + 1, f)?;
+                Ok(Val::case_tree(split).split_extend(or.try_map_leaves(depth, f)?))
+            }
+            Fst(pair) => Ok(pair.try_map_leaves(depth, f)?.first()),
+            Snd(pair) => Ok(pair.try_map_leaves(depth, f)?.second()),
+            Proj(rec, field) => Ok(rec.try_map_leaves(depth, f)?.project(field)),
+            Remove(rec, field) => Ok(rec.try_map_leaves(depth, f)?.remove_field(&field)),
+            Lift(levels, neut) => Ok(neut.try_map_leaves(depth, f)?.lift(levels)),
+            Row(kind, variants, ext) => {
+                let variants = map_variants_leaves(variants, depth, f)?;
+                let ext = ext.try_map_leaves(depth, f)?;
+                Ok(Val::RowPoly(kind, variants).row_extend(ext))
+            }
+            Rec(fields, ext) => {
+                let fields = map_variants_leaves(fields, depth, f)?;
+                let ext = ext.try_map_leaves(depth, f)?;
+                Ok(Val::Rec(fields).rec_extend(ext))
+            }
+            LabProj(rec, label) => {
+                let rec = rec.try_map_leaves(depth, f)?;
+                Ok(rec.project_label(label.try_map_leaves(depth, f)?))
+            }
+            LabCons(label, a) => {
+                let label = label.try_map_leaves(depth, f)?;
+                Ok(Val::label_cons(label, a.try_map_leaves(depth, f)?))
+            }
+            LabRow(kind, label, ty, rest) => {
+                let label = label.try_map_leaves(depth, f)?;
+                let ty = ty.try_map_leaves(depth, f)?;
+                Ok(Val::label_row(
+                    kind,
+                    label,
+                    ty,
+                    rest.try_map_leaves(depth, f)?,
+                ))
+            }
+            LabKind(label, kind) => {
+                let label = label.try_map_leaves(depth, f)?;
+                Ok(Val::label_kind(label, kind.try_map_leaves(depth, f)?))
+            }
+            RowMap(function, row) => {
+                let function = function.try_map_leaves(depth, f)?;
+                Ok(row.try_map_leaves(depth, f)?.row_map(function))
+            }
+            RowDiff(row, labels) => Ok(row.try_map_leaves(depth, f)?.row_diff(&labels)),
+            J(refl_case, proof) => {
+                let refl_case = refl_case.try_map_leaves(depth, f)?;
+                Ok(proof.try_map_leaves(depth, f)?.eliminate_id(refl_case))
+            }
+            Suc(k, n) => Ok(n.try_map_leaves(depth, f)?.plus(k)),
+            NatOp(op, a, b) => {
+                let a = a.try_map_leaves(depth, f)?;
+                Ok(Val::nat_op(op, a, b.try_map_leaves(depth, f)?))
+            }
+            NatElim(zero, step, n) => {
+                let zero = zero.try_map_leaves(depth, f)?;
+                let step = step.try_map_leaves(depth, f)?;
+                Ok(n.try_map_leaves(depth, f)?.eliminate_nat(zero, step))
+            }
+        }
+    }
+}
+
+impl Closure {
+    fn try_map_leaves<E>(
+        self,
+        depth: usize,
+        f: &mut impl FnMut(Neutral, usize) -> Result<Val, E>,
+    ) -> Result<Closure, E> {
+        match self {
+            Closure::Plain(body) => body.try_map_leaves(depth, f).map(Closure::plain),
+            Closure::Tree(split) => map_case_tree_leaves(split, depth, f).map(Closure::Tree),
+        }
+    }
+}
+
+fn map_variants_leaves<E>(
+    variants: Variants,
+    depth: usize,
+    f: &mut impl FnMut(Neutral, usize) -> Result<Val, E>,
+) -> Result<Variants, E> {
+    (variants.into_iter())
+        .map(|(name, ty)| Ok((name, ty.try_map_leaves(depth, f)?)))
+        .collect()
+}
+
+fn map_case_tree_leaves<E>(
+    cases: CaseSplit,
+    depth: usize,
+    f: &mut impl FnMut(Neutral, usize) -> Result<Val, E>,
+) -> Result<CaseSplit, E> {
+    (cases.into_iter())
+        .map(|(name, clause)| Ok((name, clause.try_map_leaves(depth, f)?)))
+        .collect()
+}
@@ -90,6 +90,19 @@ fn test_issue_47() {
     );
 }
 
+/// Applications under binders, like `flip const`, keep the variables bound by those binders.
+#[test]
+fn test_app_under_binders() {
+    assert_eq!(
+        from_str("(app (lam (lam (lam (app (app 2 0) 1)))) (lam (lam 1)))"),
+        from_str("(lam (lam 0))")
+    );
+    assert_eq!(
+        from_str("(app (lam (lam (app 1 0))) (lam (lam 1)))"),
+        from_str("(lam (lam 1))")
+    );
+}
+
 #[test]
 fn test_lift() {
     assert_eq!(
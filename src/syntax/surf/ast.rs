@@ -1,6 +1,7 @@
+use num_bigint::BigUint;
 use voile_util::level::Level;
 use voile_util::loc::{Ident, Labelled, Loc};
-use voile_util::tags::{Plicit, VarRec};
+use voile_util::tags::{NatOp, Plicit, VarRec};
 use voile_util::vec1::Vec1;
 
 pub type LabExpr = Labelled<Expr>;
@@ -18,6 +19,44 @@ pub struct Param {
     pub ty: Expr,
 }
 
+/// Surface syntax tree node: Pattern, in the clauses of pattern-matching lambdas.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Pattern {
+    /// Pattern variable.
+    Var(Ident),
+    /// Wildcard pattern, `_`.
+    Wildcard(Loc),
+    /// Constructor pattern, like `@Cons p`.
+    Cons(Ident, Box<Self>),
+    /// Pair pattern, where `p, q, r` is `Pair(p, Pair(q, r))`,
+    /// just like tuple constructors.
+    Pair(Box<Self>, Box<Self>),
+    /// Record pattern, like `{| x = p; y = q; |}`, the other fields are ignored.
+    Rec(Loc, Vec<Labelled<Self>>),
+}
+
+impl Pattern {
+    /// The pattern variables, from left to right.
+    pub fn vars(&self) -> Vec<&Ident> {
+        let mut vars = Vec::new();
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars<'a>(&'a self, vars: &mut Vec<&'a Ident>) {
+        match self {
+            Pattern::Var(ident) => vars.push(ident),
+            Pattern::Wildcard(..) => {}
+            Pattern::Cons(_, pattern) => pattern.collect_vars(vars),
+            Pattern::Pair(first, second) => {
+                first.collect_vars(vars);
+                second.collect_vars(vars);
+            }
+            Pattern::Rec(_, fields) => fields.iter().for_each(|f| f.expr.collect_vars(vars)),
+        }
+    }
+}
+
 /// Surface syntax tree node: Expression.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Expr {
@@ -29,14 +68,21 @@ pub enum Expr {
     Meta(Ident),
     /// Lift an expression many times.
     Lift(Loc, u32, Box<Self>),
-    /// Record projections.
+    /// Record projections, or sigma projections if the label is `1` or `2`.
     Proj(Box<Self>, Vec1<Ident>),
     /// `Type` literal, with levels.
     Type(Loc, Level),
+    /// The type of labels, `Label`.
+    LabelType(Loc),
+    /// Label literal, like `'x` (the text does not include the quote).
+    Label(Ident),
     /// Function application.<br/>
     /// Application operator, where `f a b c` is represented as `App(f, vec![a, b, c])`
     /// instead of `App(App(App(f, a), b), c)`.
     App(Box<Vec1<Self>>),
+    /// Implicit argument, like the `{A}` in `f {A} a`.
+    /// Only valid as an argument of an application.
+    Implicit(Loc, Box<Self>),
     /// Function composition.<br/>
     /// Pipeline operator, where `a |> b |> f` is represented as `Pipe(vec![a, b, f])`
     /// instead of `Pipe(Pipe(Pipe(f, a), b), c)`.
@@ -45,12 +91,36 @@ pub enum Expr {
     /// Comma operator, where `a, b, c` is represented as `Tup(a, vec![b, c])`
     /// instead of `Tup(Tup(a, b), c)`.
     Tup(Box<Vec1<Self>>),
+    /// Anonymous row extension, like the `...` in `Rec { x: A; ... }`.
+    /// Only valid as the extension of a row-polymorphic type.
+    AnonymousRest(Loc),
     /// Row-polymorphic types, either record types or variant types.
     RowPoly(Loc, VarRec, Vec<LabExpr>, Option<Box<Self>>),
+    /// Dependent record types, like `DRec { A: Type; a: A; }`,
+    /// where the field types can refer to the previous fields.
+    DepRec(Loc, Vec<LabExpr>),
     /// Record literals.
     Rec(Loc, Vec<LabExpr>, Option<Box<Self>>),
+    /// Record update, like `{| r with x = e; |}`.
+    Update(Loc, Box<Self>, Vec<LabExpr>),
+    /// Record field removal, like `r [-x]`.
+    Remove(Loc, Box<Self>, Ident),
+    /// Record field renaming, like `r [x => y]`.
+    Rename(Loc, Box<Self>, Ident, Ident),
     /// Row-polymorphic kinds, either record types or variant kinds.
     RowKind(Loc, VarRec, Vec<Ident>),
+    /// Row concatenation, like `Rec {x: A} ++ Rec {y: B}`.
+    RowConcat(Loc, Box<Self>, Box<Self>),
+    /// Row map, like `F <$> Rec {x: A; y: B}`.
+    RowMap(Loc, Box<Self>, Box<Self>),
+    /// Row difference, like `Rec {x: A; y: B} -- [y]`.
+    RowDiff(Loc, Box<Self>, Vec<Ident>),
+    /// Recursive types, like `Fix X. Sum { Nil: Unit; Cons: A * X; }`.
+    Fix(Loc, Ident, Box<Self>),
+    /// Wrapping a value of the unfolded recursive type, like `fold (@Nil unit)`.
+    Fold(Loc, Box<Self>),
+    /// Unwrapping a value of a recursive type, like `unfold xs`.
+    Unfold(Loc, Box<Self>),
     /// Pi-type expression, where `a -> b -> c` is represented as `Pi(vec![a, b], c)`
     /// instead of `Pi(a, Pi(b, c))`.
     /// `a` and `b` here can introduce telescopes.
@@ -64,8 +134,47 @@ pub enum Expr {
     Cases(Ident, Ident, Box<Self>, Box<Self>),
     /// Termination of a case-chain.
     Whatever(Loc),
+    /// Default clause of a case-chain, like `case _ e: b`,
+    /// where `e` is the variant value not handled by the other clauses.
+    CaseDefault(Loc, Ident, Box<Self>),
+    /// Case-chain applied to a scrutinee, with a motive, like `match b as x return P x with f`.
+    /// Scrutinee, binding of the motive, motive, case-chain.
+    Match(Loc, Box<Self>, Ident, Box<Self>, Box<Self>),
+    /// Identity type, like `a = b`.
+    Eq(Loc, Box<Self>, Box<Self>),
+    /// The proof of `a = a`, `refl`.
+    Refl(Loc),
+    /// Eliminator of identity types, like `J p as y q return P with d`.
+    /// Proof, bindings of the motive (the end of the equation and the proof),
+    /// motive, the value for `refl`.
+    J(Loc, Box<Self>, Ident, Ident, Box<Self>, Box<Self>),
+    /// Natural number literal, like `42`.
+    Nat(Loc, BigUint),
+    /// Primitive operation on natural numbers, like `a + b`.
+    NatOp(Loc, NatOp, Box<Self>, Box<Self>),
+    /// Eliminator of natural numbers, like `natElim n as m return P with z or k r. s`.
+    /// Natural number, binding of the motive, motive, the value for zero,
+    /// bindings of the step (the predecessor and the result for it), step.
+    NatElim(
+        Loc,
+        Box<Self>,
+        Ident,
+        Box<Self>,
+        Box<Self>,
+        Ident,
+        Ident,
+        Box<Self>,
+    ),
     /// Anonymous function, aka lambda expression.
-    Lam(Loc, Vec<Ident>, Box<Self>),
+    /// Parameters can be implicit, like the `A` in `\{A} a. a`.
+    Lam(Loc, Vec<(Ident, Plicit)>, Box<Self>),
+    /// Pattern-matching lambda, like `\case { @Nil _ => a; @Cons (x, xs) => b; }`.
+    PatLam(Loc, Vec<(Pattern, Self)>),
+    /// Type ascription, like `(a : A)`.
+    Ascription(Loc, Box<Self>, Box<Self>),
+    /// Local definition, like `let x : A = a in b`.
+    /// Name, optional type, definition, body.
+    Let(Loc, Ident, Option<Box<Self>>, Box<Self>, Box<Self>),
 }
 
 impl Expr {
@@ -73,10 +182,22 @@ impl Expr {
         Expr::Pi(params, Box::new(expr))
     }
 
-    pub fn lam(info: Loc, params: Vec<Ident>, expr: Self) -> Self {
+    pub fn lam(info: Loc, params: Vec<(Ident, Plicit)>, expr: Self) -> Self {
         Expr::Lam(info, params, Box::new(expr))
     }
 
+    pub fn ascription(info: Loc, expr: Self, ty: Self) -> Self {
+        Expr::Ascription(info, Box::new(expr), Box::new(ty))
+    }
+
+    pub fn let_in(info: Loc, name: Ident, ty: Option<Self>, def: Self, body: Self) -> Self {
+        Expr::Let(info, name, ty.map(Box::new), Box::new(def), Box::new(body))
+    }
+
+    pub fn implicit(info: Loc, argument: Self) -> Self {
+        Expr::Implicit(info, Box::new(argument))
+    }
+
     pub fn app(applied: Self, arguments: Vec<Self>) -> Self {
         Expr::App(Box::new(Vec1::new(applied, arguments)))
     }
@@ -110,6 +231,30 @@ impl Expr {
         Self::row_polymorphic_type(info, labels, VarRec::Record, rest)
     }
 
+    pub fn update(info: Loc, record: Self, fields: Vec<LabExpr>) -> Self {
+        Expr::Update(info, Box::new(record), fields)
+    }
+
+    pub fn remove(info: Loc, record: Self, field: Ident) -> Self {
+        Expr::Remove(info, Box::new(record), field)
+    }
+
+    pub fn rename(info: Loc, record: Self, from: Ident, to: Ident) -> Self {
+        Expr::Rename(info, Box::new(record), from, to)
+    }
+
+    pub fn row_concat(info: Loc, left: Self, right: Self) -> Self {
+        Expr::RowConcat(info, Box::new(left), Box::new(right))
+    }
+
+    pub fn row_map(info: Loc, function: Self, row: Self) -> Self {
+        Expr::RowMap(info, Box::new(function), Box::new(row))
+    }
+
+    pub fn row_diff(info: Loc, row: Self, labels: Vec<Ident>) -> Self {
+        Expr::RowDiff(info, Box::new(row), labels)
+    }
+
     pub fn tup(first: Self, rest: Vec<Self>) -> Self {
         Expr::Tup(Box::new(Vec1::new(first, rest)))
     }
@@ -122,9 +267,69 @@ impl Expr {
         Expr::Proj(Box::new(expr), projections)
     }
 
+    pub fn pattern_lambda(info: Loc, clauses: Vec<(Pattern, Self)>) -> Self {
+        Expr::PatLam(info, clauses)
+    }
+
+    pub fn fix(info: Loc, name: Ident, body: Self) -> Self {
+        Expr::Fix(info, name, Box::new(body))
+    }
+
+    pub fn fold(info: Loc, expr: Self) -> Self {
+        Expr::Fold(info, Box::new(expr))
+    }
+
+    pub fn unfold(info: Loc, expr: Self) -> Self {
+        Expr::Unfold(info, Box::new(expr))
+    }
+
+    pub fn case_default(info: Loc, binding: Ident, body: Self) -> Self {
+        Expr::CaseDefault(info, binding, Box::new(body))
+    }
+
     pub fn cases(label: Ident, binding: Ident, body: Self, or: Self) -> Self {
         Expr::Cases(label, binding, Box::new(body), Box::new(or))
     }
+
+    pub fn match_on(info: Loc, scrutinee: Self, binding: Ident, motive: Self, cases: Self) -> Self {
+        let (scrutinee, motive) = (Box::new(scrutinee), Box::new(motive));
+        Expr::Match(info, scrutinee, binding, motive, Box::new(cases))
+    }
+
+    pub fn eq(info: Loc, a: Self, b: Self) -> Self {
+        Expr::Eq(info, Box::new(a), Box::new(b))
+    }
+
+    pub fn j(info: Loc, proof: Self, (end, eq): (Ident, Ident), motive: Self, refl: Self) -> Self {
+        let (proof, motive) = (Box::new(proof), Box::new(motive));
+        Expr::J(info, proof, end, eq, motive, Box::new(refl))
+    }
+
+    pub fn nat_op(info: Loc, op: NatOp, a: Self, b: Self) -> Self {
+        Expr::NatOp(info, op, Box::new(a), Box::new(b))
+    }
+
+    pub fn nat_elim(
+        info: Loc,
+        nat: Self,
+        binding: Ident,
+        motive: Self,
+        zero: Self,
+        (pred, result): (Ident, Ident),
+        step: Self,
+    ) -> Self {
+        let (nat, motive, zero) = (Box::new(nat), Box::new(motive), Box::new(zero));
+        Expr::NatElim(
+            info,
+            nat,
+            binding,
+            motive,
+            zero,
+            pred,
+            result,
+            Box::new(step),
+        )
+    }
 }
 
 /// Indicates that whether a `Decl` is a type signature or an implementation.
@@ -136,13 +341,55 @@ pub enum DeclKind {
     Sign,
 }
 
-/// Surface syntax tree node: Declaration.
+/// Compiler directives attached to an implementation, like `{-# NON_TERMINATING #-}`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Ord, PartialOrd, Hash)]
+pub enum Pragma {
+    /// Skip the termination check, for intentional general recursion.
+    NonTerminating,
+}
+
+/// Surface syntax tree node: Named declaration.
 ///
 /// It can be a type signature, where there's a name and a type expression;
 /// or an implementation, where there's a name and an expression body.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Decl {
+pub struct NamedDecl {
     pub name: Ident,
     pub body: Expr,
     pub kind: DeclKind,
+    /// Always empty for signatures.
+    pub pragmas: Vec<Pragma>,
+}
+
+/// Surface syntax tree node: Data type declaration,
+/// like `data Maybe (A : Type) = Nothing | Just A;`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DataDecl {
+    pub name: Ident,
+    /// Parameters, they're implicit in the types of the constructors.
+    pub params: Vec<Param>,
+    /// The type of the data type applied to the parameters, `Type` if absent.
+    /// The parameters of this (pi-)type would be indices, which are not supported.
+    pub ty: Option<Expr>,
+    pub conses: Vec<DataCons>,
+}
+
+/// Surface syntax tree node: Constructor of a data type, like `Just A`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DataCons {
+    pub name: Ident,
+    /// Types of the fields, which are the explicit parameters of the constructor.
+    pub fields: Vec<Expr>,
+}
+
+/// Surface syntax tree node: Declaration.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Decl {
+    /// Type signature or implementation.
+    Named(NamedDecl),
+    /// Module import, like `import Data.Bool;`.
+    /// The `Ident` is the module path, segments are separated by `.`.
+    Import(Ident),
+    /// Data type declaration, expanded to named declarations.
+    Data(DataDecl),
 }
@@ -1,16 +1,17 @@
+use num_bigint::BigUint;
 use pest::Parser;
 use pest_derive::Parser;
 
-use voile_util::level::Level;
-use voile_util::loc::{Ident, Loc};
+use voile_util::level::{Level, LevelVar};
+use voile_util::loc::{Ident, Labelled, Loc};
 use voile_util::pest_util::end_of_rule;
-use voile_util::tags::{Plicit, VarRec};
+use voile_util::tags::{NatOp, Plicit, VarRec};
 use voile_util::vec1::Vec1;
 
 use crate::syntax::surf::LabExpr;
 
-use super::ast::Param;
-use super::{Decl, DeclKind, Expr};
+use super::ast::{DataCons, DataDecl, Param, Pattern};
+use super::{Decl, DeclKind, Expr, NamedDecl, Pragma};
 
 #[derive(Parser)]
 #[grammar = "syntax/surf/grammar.pest"]
@@ -78,13 +79,27 @@ fn labelled(rules: Tok) -> LabExpr {
 }
 
 fn row_rest(rules: Tok) -> Expr {
+    let info = Loc::from(rules.as_span());
     let mut inner: Tik = rules.into_inner();
+    if inner.peek().is_none() {
+        return Expr::AnonymousRest(info);
+    }
     let expr = next_rule!(inner, expr);
     end_of_rule(&mut inner);
     expr
 }
 
-many_prefix_parser!(row_polymorphic, LabExpr, labelled, row_rest);
+/// The label of an entry is either an identifier or a label variable `[l]`,
+/// which keeps its brackets so the translator can tell them apart.
+fn row_labelled(rules: Tok) -> LabExpr {
+    let mut inner: Tik = rules.into_inner();
+    let label = ident(inner.next().unwrap());
+    let expr = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    LabExpr { expr, label }
+}
+
+many_prefix_parser!(row_polymorphic, LabExpr, row_labelled, row_rest);
 many_prefix_parser!(record_literal, LabExpr, rec_field, row_rest);
 
 fn record(rules: Tok) -> Expr {
@@ -100,6 +115,11 @@ fn variant_record(rules: Tok, kind: VarRec) -> Expr {
     Expr::row_polymorphic_type(info, labels, kind, rest)
 }
 
+fn dependent_record(rules: Tok) -> Expr {
+    let info = Loc::from(rules.as_span());
+    Expr::DepRec(info, rules.into_inner().map(labelled).collect())
+}
+
 fn variant_record_kind(rules: Tok, kind: VarRec) -> Expr {
     let info = Loc::from(rules.as_span());
     let rules = rules.into_inner().next().unwrap();
@@ -112,20 +132,101 @@ fn declaration(rules: Tok) -> Decl {
     let kind = match the_rule.as_rule() {
         Rule::signature => DeclKind::Sign,
         Rule::implementation => DeclKind::Impl,
+        Rule::module_import => return module_import(the_rule),
+        Rule::data_decl => return data_decl(the_rule),
         _ => unreachable!(),
     };
     let mut inner: Tik = the_rule.into_inner();
+    let mut pragmas = Vec::new();
+    while let Some(Rule::pragma) = inner.peek().map(|rule| rule.as_rule()) {
+        pragmas.push(pragma(inner.next().unwrap()));
+    }
     let name = next_ident(&mut inner);
     let body = next_rule!(inner, expr);
     end_of_rule(&mut inner);
-    Decl { kind, name, body }
+    Decl::Named(NamedDecl {
+        kind,
+        name,
+        body,
+        pragmas,
+    })
+}
+
+fn pragma(rules: Tok) -> Pragma {
+    let the_rule: Tok = rules.into_inner().next().unwrap();
+    match the_rule.as_rule() {
+        Rule::non_terminating => Pragma::NonTerminating,
+        e => panic!("Unexpected rule: {:?} with token {}.", e, the_rule.as_str()),
+    }
+}
+
+fn module_import(rules: Tok) -> Decl {
+    let mut inner: Tik = rules.into_inner();
+    let path: Tok = inner.next().unwrap();
+    debug_assert_eq!(path.as_rule(), Rule::module_path);
+    let path = ident(path);
+    end_of_rule(&mut inner);
+    Decl::Import(path)
+}
+
+fn data_decl(rules: Tok) -> Decl {
+    let mut inner: Tik = rules.into_inner();
+    inner.next(); // The keyword
+    let name = next_ident(&mut inner);
+    let mut params = Vec::new();
+    while let Some(Rule::explicit) = inner.peek().map(|rule| rule.as_rule()) {
+        params.push(one_param(inner.next().unwrap(), Plicit::Ex));
+    }
+    let ty = match inner.peek().map(|rule| rule.as_rule()) {
+        Some(Rule::data_type) => Some(data_type(inner.next().unwrap())),
+        _ => None,
+    };
+    let conses = inner.map(data_cons).collect();
+    Decl::Data(DataDecl {
+        name,
+        params,
+        ty,
+        conses,
+    })
+}
+
+fn data_cons(rules: Tok) -> DataCons {
+    let mut inner: Tik = rules.into_inner();
+    let name = next_ident(&mut inner);
+    let fields = inner.map(primary_expr).collect();
+    DataCons { name, fields }
 }
 
 expr_parser!(dollar_expr, comma_expr, app);
 expr_parser!(comma_expr, pipe_expr, tup);
-expr_parser!(pipe_expr, lift_expr, pipe);
+expr_parser!(pipe_expr, cmp_expr, pipe);
 // expr_parser!(lift_expr, app_expr, lift); customized
-expr_parser!(app_expr, primary_expr, app);
+// expr_parser!(app_expr, primary_expr, app); customized
+
+fn app_expr(rules: Tok) -> Expr {
+    let mut exprs: Vec<Expr> = Default::default();
+    for smaller in rules.into_inner() {
+        exprs.push(match smaller.as_rule() {
+            Rule::primary_expr => primary_expr(smaller),
+            Rule::implicit_arg => implicit_arg(smaller),
+            e => panic!("Unexpected rule: {:?} with token {}", e, smaller.as_str()),
+        });
+    }
+    let first = exprs.remove(0);
+    if exprs.is_empty() {
+        first
+    } else {
+        Expr::app(first, exprs)
+    }
+}
+
+fn implicit_arg(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let argument = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::implicit(loc, argument)
+}
 
 fn lift_expr(rules: Tok) -> Expr {
     let mut lift_count = 0u32;
@@ -149,30 +250,126 @@ fn lift_expr(rules: Tok) -> Expr {
     unreachable!()
 }
 
+fn cmp_expr(rules: Tok) -> Expr {
+    nat_op_expr(rules, add_expr)
+}
+
+fn add_expr(rules: Tok) -> Expr {
+    nat_op_expr(rules, mul_expr)
+}
+
+fn mul_expr(rules: Tok) -> Expr {
+    nat_op_expr(rules, row_expr)
+}
+
+/// Primitive operators on natural numbers, they're left-associative.
+fn nat_op_expr(rules: Tok, smaller: fn(Tok) -> Expr) -> Expr {
+    let info = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let mut expr = smaller(inner.next().unwrap());
+    while let Some(operator) = inner.next() {
+        let op = match operator.as_str() {
+            "+" => NatOp::Add,
+            "-" => NatOp::Sub,
+            "<" => NatOp::Lt,
+            "<=" => NatOp::Le,
+            _ => NatOp::Mul,
+        };
+        let operand: Tok = inner.next().unwrap();
+        let info = Loc {
+            end: operand.as_span().end(),
+            ..info
+        };
+        expr = Expr::nat_op(info, op, expr, smaller(operand));
+    }
+    expr
+}
+
+fn row_expr(rules: Tok) -> Expr {
+    let info = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let mut row = next_rule!(inner, lift_expr);
+    for operator in inner {
+        let info = Loc {
+            end: operator.as_span().end(),
+            ..info
+        };
+        let rule = operator.as_rule();
+        let mut inner: Tik = operator.into_inner();
+        row = match rule {
+            Rule::row_concat => Expr::row_concat(info, row, next_rule!(inner, lift_expr)),
+            Rule::row_map => Expr::row_map(info, row, next_rule!(inner, lift_expr)),
+            Rule::row_diff => {
+                let labels = inner.next().unwrap().into_inner().map(ident).collect();
+                Expr::row_diff(info, row, labels)
+            }
+            e => panic!("Unexpected rule: {:?}", e),
+        };
+        end_of_rule(&mut inner);
+    }
+    row
+}
+
 fn proj_expr(rules: Tok) -> Expr {
     let mut projections = None;
+    let info = Loc::from(rules.as_span());
     let mut inner = rules.into_inner();
-    let projected = next_rule!(inner, app_expr);
-    for projection in inner {
-        assert_eq!(projection.as_rule(), Rule::proj_op);
-        let ident = Ident {
-            loc: Loc::from(projection.as_span()),
-            text: projection.as_str()[1..].to_owned(),
+    let mut projected = next_rule!(inner, app_expr);
+    for postfix in inner {
+        let info = Loc {
+            end: postfix.as_span().end(),
+            ..info
         };
-        match projections {
-            None => projections = Some(Vec1::from(ident)),
-            Some(mut some_projections) => {
-                some_projections.push(ident);
-                projections = Some(some_projections);
+        match postfix.as_rule() {
+            Rule::proj_op => {
+                let ident = Ident {
+                    loc: Loc::from(postfix.as_span()),
+                    text: postfix.as_str()[1..].to_owned(),
+                };
+                match projections {
+                    None => projections = Some(Vec1::from(ident)),
+                    Some(mut some_projections) => {
+                        some_projections.push(ident);
+                        projections = Some(some_projections);
+                    }
+                };
             }
-        };
+            Rule::field_removal => {
+                projected = projections_of(projected, projections.take());
+                let mut inner: Tik = postfix.into_inner();
+                let field = next_ident(&mut inner);
+                end_of_rule(&mut inner);
+                projected = Expr::remove(info, projected, field);
+            }
+            Rule::field_renaming => {
+                projected = projections_of(projected, projections.take());
+                let mut inner: Tik = postfix.into_inner();
+                let from = next_ident(&mut inner);
+                let to = next_ident(&mut inner);
+                end_of_rule(&mut inner);
+                projected = Expr::rename(info, projected, from, to);
+            }
+            e => panic!("Unexpected rule: {:?} with token {}", e, postfix.as_str()),
+        }
     }
+    projections_of(projected, projections)
+}
+
+fn projections_of(projected: Expr, projections: Option<Vec1<Ident>>) -> Expr {
     match projections {
         Some(projections) => Expr::proj(projected, projections),
         None => projected,
     }
 }
 
+fn record_update(rules: Tok) -> Expr {
+    let info = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let record = next_rule!(inner, expr);
+    let fields = inner.map(labelled).collect();
+    Expr::update(info, record, fields)
+}
+
 fn expr(rules: Tok) -> Expr {
     let mut inner: Tik = rules.into_inner();
     let expr = next_rule!(inner, sig_expr);
@@ -186,16 +383,32 @@ fn primary_expr(rules: Tok) -> Expr {
     let expr = match the_rule.as_rule() {
         Rule::ident => Expr::Var(ident(the_rule)),
         Rule::cons => Expr::Cons(ident(the_rule)),
+        Rule::label => Expr::Label(label(the_rule)),
+        Rule::label_keyword => Expr::LabelType(From::from(the_rule.as_span())),
+        Rule::refl_keyword => Expr::Refl(From::from(the_rule.as_span())),
+        Rule::nat_lit => Expr::Nat(From::from(the_rule.as_span()), nat_lit(the_rule)),
         Rule::meta => Expr::Meta(ident(the_rule)),
         Rule::no_cases => Expr::Whatever(From::from(the_rule.as_span())),
+        Rule::fix_type => fix_type(the_rule),
+        Rule::fold_expr => fold_expr(the_rule),
+        Rule::default_case => default_case(the_rule),
         Rule::case_expr => case_expr(the_rule),
+        Rule::match_expr => match_expr(the_rule),
+        Rule::j_expr => j_expr(the_rule),
+        Rule::nat_elim_expr => nat_elim_expr(the_rule),
+        Rule::pattern_lambda => pattern_lambda(the_rule),
         Rule::lambda => lambda(the_rule),
+        Rule::let_expr => let_expr(the_rule),
+        Rule::ascription => ascription(the_rule),
         Rule::record => variant_record(the_rule, VarRec::Record),
         Rule::variant => variant_record(the_rule, VarRec::Variant),
+        Rule::dependent_record => dependent_record(the_rule),
         Rule::record_kind => variant_record_kind(the_rule, VarRec::Record),
         Rule::variant_kind => variant_record_kind(the_rule, VarRec::Variant),
+        Rule::record_update => record_update(the_rule),
         Rule::record_literal => record(the_rule),
         Rule::type_keyword => type_keyword(the_rule),
+        Rule::level_type => level_type(the_rule),
         Rule::expr => expr(the_rule),
         e => panic!("Unexpected rule: {:?} with token {}", e, the_rule.as_str()),
     };
@@ -221,7 +434,7 @@ fn param(rules: Tok) -> Param {
             plicit: Plicit::Ex,
             names: Vec::with_capacity(0),
             ty: match rule_type {
-                Rule::dollar_expr => dollar_expr(the_rule),
+                Rule::eq_expr => eq_expr(the_rule),
                 Rule::pi_expr => pi_expr(the_rule),
                 e => panic!("Unexpected rule: {:?} with token {}", e, the_rule.as_str()),
             },
@@ -231,10 +444,10 @@ fn param(rules: Tok) -> Param {
     param
 }
 
-many_prefix_parser!(pi_expr_internal, Param, param, dollar_expr);
+many_prefix_parser!(pi_expr_internal, Param, param, eq_expr);
 many_prefix_parser!(sig_expr_internal, Param, param, pi_expr);
+many_prefix_parser!(data_type_internal, Param, param, dollar_expr);
 many_prefix_parser!(multi_param, Ident, ident, expr);
-many_prefix_parser!(lambda_internal, Ident, ident, expr);
 
 fn pi_expr(rules: Tok) -> Expr {
     let (params, ret) = pi_expr_internal(rules);
@@ -246,6 +459,28 @@ fn pi_expr(rules: Tok) -> Expr {
     }
 }
 
+fn data_type(rules: Tok) -> Expr {
+    let (params, ret) = data_type_internal(rules);
+    let ret = ret.unwrap();
+    if params.is_empty() {
+        ret
+    } else {
+        Expr::pi(params, ret)
+    }
+}
+
+fn eq_expr(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let a = next_rule!(inner, dollar_expr);
+    let expr = match inner.next() {
+        Some(b) => Expr::eq(loc, a, dollar_expr(b)),
+        None => a,
+    };
+    end_of_rule(&mut inner);
+    expr
+}
+
 fn sig_expr(rules: Tok) -> Expr {
     let (params, ret) = sig_expr_internal(rules);
     let ret = ret.unwrap();
@@ -266,11 +501,171 @@ fn case_expr(rules: Tok) -> Expr {
     Expr::cases(label, binding, body, rest)
 }
 
+fn default_case(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    inner.next(); // The wildcard label
+    let binding = next_ident(&mut inner);
+    let body = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::case_default(loc, binding, body)
+}
+
+fn fix_type(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    inner.next(); // The keyword
+    let name = next_ident(&mut inner);
+    let body = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::fix(loc, name, body)
+}
+
+fn fold_expr(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let keyword = inner.next().unwrap();
+    let expr = next_rule!(inner, primary_expr);
+    end_of_rule(&mut inner);
+    match keyword.as_rule() {
+        Rule::fold_keyword => Expr::fold(loc, expr),
+        _ => Expr::unfold(loc, expr),
+    }
+}
+
+fn match_expr(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let scrutinee = next_rule!(inner, expr);
+    let binding = next_ident(&mut inner);
+    let motive = next_rule!(inner, expr);
+    let cases = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::match_on(loc, scrutinee, binding, motive, cases)
+}
+
+fn j_expr(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    inner.next(); // The keyword
+    let proof = next_rule!(inner, expr);
+    let end = next_ident(&mut inner);
+    let eq = next_ident(&mut inner);
+    let motive = next_rule!(inner, expr);
+    let refl = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::j(loc, proof, (end, eq), motive, refl)
+}
+
+fn nat_elim_expr(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    inner.next(); // The keyword
+    let nat = next_rule!(inner, expr);
+    let binding = next_ident(&mut inner);
+    let motive = next_rule!(inner, expr);
+    let zero = next_rule!(inner, expr);
+    let pred = next_ident(&mut inner);
+    let result = next_ident(&mut inner);
+    let step = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::nat_elim(loc, nat, binding, motive, zero, (pred, result), step)
+}
+
 fn lambda(rules: Tok) -> Expr {
     let loc = Loc::from(rules.as_span());
-    let (params, ret) = lambda_internal(rules);
-    let ret = ret.unwrap();
-    Expr::lam(loc, params, ret)
+    let mut params = Vec::new();
+    let mut ret = None;
+    for the_rule in rules.into_inner() {
+        match the_rule.as_rule() {
+            Rule::ident => params.push((ident(the_rule), Plicit::Ex)),
+            Rule::implicit_lambda_param => {
+                let implicits = the_rule.into_inner().map(|i| (ident(i), Plicit::Im));
+                params.extend(implicits)
+            }
+            Rule::expr => ret = Some(expr(the_rule)),
+            e => panic!("Unexpected rule: {:?} with token {}", e, the_rule.as_str()),
+        }
+    }
+    Expr::lam(loc, params, ret.unwrap())
+}
+
+fn ascription(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let ascribed = next_rule!(inner, expr);
+    let ty = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    Expr::ascription(loc, ascribed, ty)
+}
+
+fn pattern_lambda(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let clauses = rules.into_inner().map(pattern_clause).collect();
+    Expr::pattern_lambda(loc, clauses)
+}
+
+fn pattern_clause(rules: Tok) -> (Pattern, Expr) {
+    let mut inner: Tik = rules.into_inner();
+    let pattern = next_rule!(inner, pattern);
+    let body = next_rule!(inner, expr);
+    end_of_rule(&mut inner);
+    (pattern, body)
+}
+
+fn pattern(rules: Tok) -> Pattern {
+    let mut patterns: Vec<Pattern> = rules.into_inner().map(pattern_primary).collect();
+    let last = patterns.pop().unwrap();
+    let pair = |pair, pattern| Pattern::Pair(Box::new(pattern), Box::new(pair));
+    patterns.into_iter().rev().fold(last, pair)
+}
+
+fn pattern_primary(rules: Tok) -> Pattern {
+    let mut inner: Tik = rules.into_inner();
+    let the_rule: Tok = inner.next().unwrap();
+    let pattern = match the_rule.as_rule() {
+        Rule::cons_pattern => cons_pattern(the_rule),
+        Rule::record_pattern => record_pattern(the_rule),
+        Rule::wildcard => Pattern::Wildcard(From::from(the_rule.as_span())),
+        Rule::ident => Pattern::Var(ident(the_rule)),
+        Rule::pattern => pattern(the_rule),
+        e => panic!("Unexpected rule: {:?} with token {}", e, the_rule.as_str()),
+    };
+    end_of_rule(&mut inner);
+    pattern
+}
+
+fn cons_pattern(rules: Tok) -> Pattern {
+    let mut inner: Tik = rules.into_inner();
+    // The constructor without the `@`, just like labels without the quote.
+    let cons = label(inner.next().unwrap());
+    let pattern = next_rule!(inner, pattern_primary);
+    end_of_rule(&mut inner);
+    Pattern::Cons(cons, Box::new(pattern))
+}
+
+fn record_pattern(rules: Tok) -> Pattern {
+    let loc = Loc::from(rules.as_span());
+    let fields = rules.into_inner().map(field_pattern).collect();
+    Pattern::Rec(loc, fields)
+}
+
+fn field_pattern(rules: Tok) -> Labelled<Pattern> {
+    let mut inner: Tik = rules.into_inner();
+    let label = next_ident(&mut inner);
+    let expr = next_rule!(inner, pattern);
+    end_of_rule(&mut inner);
+    Labelled { expr, label }
+}
+
+fn let_expr(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let name = next_ident(&mut inner);
+    let mut exprs: Vec<Expr> = inner.map(expr).collect();
+    let body = exprs.pop().unwrap();
+    let def = exprs.pop().unwrap();
+    Expr::let_in(loc, name, exprs.pop(), def, body)
 }
 
 fn type_keyword(rules: Tok) -> Expr {
@@ -283,6 +678,54 @@ fn type_keyword(rules: Tok) -> Expr {
     Expr::Type(loc, level)
 }
 
+fn level_type(rules: Tok) -> Expr {
+    let loc = Loc::from(rules.as_span());
+    let mut inner: Tik = rules.into_inner();
+    let level = level(inner.next().unwrap());
+    end_of_rule(&mut inner);
+    Expr::Type(loc, level)
+}
+
+fn level(rules: Tok) -> Level {
+    let mut inner: Tik = rules.into_inner();
+    let the_rule: Tok = inner.next().unwrap();
+    let level = match the_rule.as_rule() {
+        Rule::level_suc => {
+            let mut inner: Tik = the_rule.into_inner();
+            // Skip the keyword
+            inner.next();
+            let level = next_rule!(inner, level);
+            end_of_rule(&mut inner);
+            level + 1
+        }
+        Rule::level_max => {
+            let mut inner: Tik = the_rule.into_inner();
+            inner.next();
+            let a = next_rule!(inner, level);
+            let b = next_rule!(inner, level);
+            end_of_rule(&mut inner);
+            a.max(b)
+        }
+        Rule::level_num => Level::Num(the_rule.as_str().parse().unwrap()),
+        Rule::ident => Level::var(LevelVar::Bound(the_rule.as_str().to_owned())),
+        Rule::level => level(the_rule),
+        e => panic!("Unexpected rule: {:?} with token {}", e, the_rule.as_str()),
+    };
+    end_of_rule(&mut inner);
+    level
+}
+
+fn nat_lit(rule: Tok) -> BigUint {
+    rule.as_str().parse().unwrap()
+}
+
+fn label(rule: Tok) -> Ident {
+    Ident {
+        text: rule.as_str()[1..].to_owned(),
+        loc: From::from(rule.as_span()),
+    }
+}
+
 fn ident(rule: Tok) -> Ident {
     Ident {
         text: rule.as_str().to_owned(),
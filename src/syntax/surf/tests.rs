@@ -25,6 +25,22 @@ fn simple_declaration_parsing() {
     parse_str_err_printed("a = b").unwrap_err();
 }
 
+#[test]
+fn pragma_parsing() {
+    success!("{-# NON_TERMINATING #-}\nlet a = a;");
+    success!("val a : A;\n{-# NON_TERMINATING #-} let a = a;");
+    parse_str_err_printed("{-# NON_TERMINATING #-} val a : A;").unwrap_err();
+    parse_str_err_printed("{-# TERMINATING #-} let a = a;").unwrap_err();
+}
+
+#[test]
+fn import_parsing() {
+    success!("import Data;");
+    success!("import Data.Bool;\nval a : Data.Bool.Bool;");
+    parse_str_err_printed("import Data.Bool").unwrap_err();
+    parse_str_err_printed("import Data.;").unwrap_err();
+}
+
 #[test]
 fn primary_expr_parsing() {
     success!("let a = Type;");
@@ -49,6 +65,8 @@ fn standalone_expr_parsing() {
     success_expr!("Rec { n : Coq; ... = Epigram }");
     success_expr!("Sum { n : Coq; ... = Epigram }");
     success_expr!("Sum { n : HOL; m : Isabelle; ... = Epigram }");
+    success_expr!("Rec { n : Lean; ... }");
+    success_expr!("Sum { ... }");
 }
 
 #[test]
@@ -60,6 +78,65 @@ fn simple_expr_parsing() {
     success!("let scarlet = devil, mansion;");
     success!("val deep : @Dark Fantasy;");
     success!("val uw : ^^^pl se;");
+    success!("let swap = \\p. p.2, p.1;");
+    success!("let nested = p.2.1.x;");
+    parse_str_err_printed("let three = p.3;").unwrap_err();
+    parse_str_err_printed("let twelve = p.12;").unwrap_err();
+}
+
+#[test]
+fn record_operation_parsing() {
+    success!("let setX = \\r a. {| r with x = a |};");
+    success!("let setXY = \\r. {| r with x = a; y = b; |};");
+    success!("let noX = r [-x];");
+    success!("let yToZ = r [y => z] [-x];");
+    success!("let nested = (r.a [-x]).b;");
+    success!("let applied = f \\x. x;");
+    success!("let getX = \\r. r.x;");
+    parse_str_err_printed("let noX = r \\ x;").unwrap_err();
+    parse_str_err_printed("let empty = {| r with |};").unwrap_err();
+    parse_str_err_printed("let with = r;").unwrap_err();
+}
+
+#[test]
+fn let_in_parsing() {
+    success!("let a = let b = c in b;");
+    success!("let a = let b : B = c in let d = b in f b d;");
+    success!("let a = \\x. let y = x in y, y;");
+    parse_str_err_printed("let a = let b = c;").unwrap_err();
+    parse_str_err_printed("let a = let in = c in in;").unwrap_err();
+}
+
+#[test]
+fn ascription_parsing() {
+    success!("let a = (b : B);");
+    success!("let id = (\\a. a : A -> A);");
+    success!("val pi : (a : A) -> B;");
+    success!("let app = f (a : A) (b, c : B * C);");
+    parse_str_err_printed("let a = (b : );").unwrap_err();
+}
+
+#[test]
+fn implicit_parsing() {
+    success!("let id = \\{A} a. a;");
+    success!("let const = \\{A B} a b. a;");
+    success!("let app = f {A} a {B};");
+    success!("let rec = f {| a = b; |} {A};");
+    success!("val t : {A : Type} -> A;");
+    parse_str_err_printed("let id = \\{}. a;").unwrap_err();
+    parse_str_err_printed("let app = f {};").unwrap_err();
+    parse_str_err_printed("let app = {A};").unwrap_err();
+}
+
+#[test]
+fn universe_level_parsing() {
+    success!("val id : (A : Type[u]) -> A -> A;");
+    success!("val big : Type[lsuc (lmax u 2)];");
+    success!("val pair : Type[lmax u v] -> Type[lsuc u];");
+    success!("val lsucc : Type[lsucc];");
+    parse_str_err_printed("val a : Type[];").unwrap_err();
+    parse_str_err_printed("val a : Type[lsuc];").unwrap_err();
+    parse_str_err_printed("val a : Type[lmax u];").unwrap_err();
 }
 
 #[test]
@@ -68,3 +145,96 @@ fn row_polymorphic_type_def_expr_parsing() {
     success!("val crimson : Sum {};");
     success!("val experience : Rec {};");
 }
+
+#[test]
+fn label_parsing() {
+    success!("val x : Label;");
+    success!("let x = 'x;");
+    success!("let get = \\l r. r.l;");
+    success!("val get : (l : Label) -> {r : Rec [[l]]} -> Rec { [l]: A; ... = r } -> A;");
+    parse_str_err_printed("let x = ';").unwrap_err();
+}
+
+#[test]
+fn row_operator_parsing() {
+    success!("val p : Rec { x: A; } ++ Rec { y: B; };");
+    success!("val p : Maybe <$> Rec { x: A; y: B; };");
+    success!("val p : Rec { x: A; y: B; } -- [x y];");
+    success!("val p : F <$> r ++ s -- [x] -> r;");
+    parse_str_err_printed("val p : r -- x;").unwrap_err();
+}
+
+#[test]
+fn dependent_record_parsing() {
+    success!("val Monoid : DRec { A: Type; empty: A; op: A -> A -> A; };");
+    success!("val Unit : DRec {};");
+    success!("val DRecord : Type;");
+    parse_str_err_printed("val Bad : DRec { A: Type; ... };").unwrap_err();
+}
+
+#[test]
+fn match_parsing() {
+    success!("let f = \\b. match b as x return P x with case True u: t or whatever;");
+    success!("let f = match b as x return P (not x) with g;");
+    success!("let matched = match;");
+    parse_str_err_printed("let f = match b return P with g;").unwrap_err();
+}
+
+#[test]
+fn pattern_lambda_parsing() {
+    success!("let f = \\case { @Just (@True _) => a; _ => b; };");
+    success!("let f = \\case { a, @Cons (x, xs), _ => a; };");
+    success!("let f = \\case { {| x = @True _; y = y; |} => y; };");
+    success!("let absurd = \\case { };");
+    parse_str_err_printed("let f = \\case { a => b };").unwrap_err();
+}
+
+#[test]
+fn recursive_type_parsing() {
+    success!("let Nat = Fix N. Sum { Zero: Unit; Suc: N; };");
+    success!("let Nat = μ N. Sum { Zero: Unit; Suc: N; };");
+    success!("let suc = \\n. fold (@Suc n);");
+    success!("let pred = \\n. unfold n;");
+    success!("let Fixed = folded unfolded;");
+    parse_str_err_printed("let Nat = Fix. Sum { Zero: Unit; };").unwrap_err();
+}
+
+#[test]
+fn default_case_parsing() {
+    success!("let f = case A a: a or case _ e: e;");
+    success!("let f = case _ e: @Rethrown e;");
+    parse_str_err_printed("let f = case A a: a or case _: a;").unwrap_err();
+}
+
+#[test]
+fn data_declaration_parsing() {
+    success!("data Bool = True | False Unit;");
+    success!("data Pair (A B : Type) = MkPair A (Maybe B);");
+    success!("data Tagged (A : Type) : (tag : Label) -> Type = Tag A;");
+    success!("data Void;");
+    success!("let dataType = Sum {};");
+    parse_str_err_printed("data Bool = | True;").unwrap_err();
+}
+
+#[test]
+fn identity_type_parsing() {
+    success!("val sym : {a : A} -> {b : A} -> a = b -> b = a;");
+    success!("let sym = \\p. J p as y q return y = a with refl;");
+    success!("let cong = \\f p. J p as y q return f a = f y with refl;");
+    success!("let a = let b : c = d = refl in b;");
+    success!("let a = let b : B = c in b;");
+    success!("data Bool : Type = True | False;");
+    parse_str_err_printed("let a = b = c = d;").unwrap_err();
+}
+
+#[test]
+fn natural_number_parsing() {
+    success!("val a : Nat;");
+    success!("let Peano = Nat;");
+    success!("let a = 1 + 2 \u{00D7} 3 - 4;");
+    success!("let a = n + 1 < 2 = @True {||};");
+    success!("let double = \\n. natElim n as m return Nat with 0 or k r. r + 2;");
+    success!("let f = F <$> r -- [x];");
+    parse_str_err_printed("let a = 1 < 2 < 3;").unwrap_err();
+    parse_str_err_printed("let a = natElim n with 0 or k. k;").unwrap_err();
+}
@@ -3,31 +3,149 @@ use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Error, Formatter};
 use std::ops::{Add, Sub};
 
+use crate::meta::MI;
+
+/// Level variable, in a level-polymorphic signature.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum LevelVar {
+    /// Rigid level variable, bound by the signature it appears in.
+    Bound(String),
+    /// Level meta variable, which is solved during type-checking.
+    Meta(MI),
+}
+
 /// Level, can be inferred or user-specified.
-#[derive(Debug, Copy, Clone, Ord, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Level {
     Omega,
     Num(u32),
+    /// `lmax n (v_1 + k_1) (v_2 + k_2) ...`, where `vars` maps each `v_i` to `k_i`.
+    /// Always has at least one variable, otherwise it's a `Num`.
+    Max(u32, BTreeMap<LevelVar, u32>),
 }
 
 impl Level {
+    /// A single level variable.
+    pub fn var(var: LevelVar) -> Self {
+        let mut vars = BTreeMap::new();
+        vars.insert(var, 0);
+        Level::Max(0, vars)
+    }
+
+    /// `lmax n (v_1 + k_1) ...`, where `n` is dropped if it's implied by some `v_i + k_i`.
+    fn max_of(n: u32, vars: BTreeMap<LevelVar, u32>) -> Self {
+        if vars.is_empty() {
+            Level::Num(n)
+        } else if vars.values().any(|k| *k >= n) {
+            Level::Max(0, vars)
+        } else {
+            Level::Max(n, vars)
+        }
+    }
+
     pub fn max(self, other: Self) -> Self {
         use Level::*;
         match (self, other) {
             (Num(a), Num(b)) => Num(max(a, b)),
+            (Num(a), Max(b, vars)) | (Max(b, vars), Num(a)) => Self::max_of(max(a, b), vars),
+            (Max(a, mut a_vars), Max(b, b_vars)) => {
+                for (var, offset) in b_vars {
+                    let a_offset = a_vars.entry(var).or_insert(offset);
+                    *a_offset = max(*a_offset, offset);
+                }
+                Self::max_of(max(a, b), a_vars)
+            }
             _ => Omega,
         }
     }
 
-    pub fn map(self, f: impl FnOnce(u32) -> u32) -> Self {
-        self.and_then(|a| Level::Num(f(a)))
+    /// Apply `f` on the constant and the offset of every variable.
+    pub fn map(self, mut f: impl FnMut(u32) -> u32) -> Self {
+        use Level::*;
+        match self {
+            Omega => Omega,
+            Num(n) => Num(f(n)),
+            Max(n, vars) => {
+                let n = f(n);
+                Self::max_of(n, vars.into_iter().map(|(v, k)| (v, f(k))).collect())
+            }
+        }
     }
 
-    pub fn and_then(self, f: impl FnOnce(u32) -> Self) -> Self {
+    /// Apply `f` on a constant level, `None` if this level has variables.
+    pub fn and_then(self, f: impl FnOnce(u32) -> Self) -> Option<Self> {
         use Level::*;
         match self {
-            Omega => Omega,
-            Num(n) => f(n),
+            Omega => Some(Omega),
+            Num(n) => Some(f(n)),
+            Max(..) => None,
+        }
+    }
+
+    /// Variables in this level, with their offsets.
+    pub fn vars(&self) -> Option<&BTreeMap<LevelVar, u32>> {
+        match self {
+            Level::Max(_, vars) => Some(vars),
+            _ => None,
+        }
+    }
+
+    /// Replace every variable `v + k` with `f(v) + k`.
+    pub fn subst(self, f: &mut impl FnMut(&LevelVar) -> Option<Level>) -> Self {
+        match self {
+            Level::Max(n, vars) => {
+                let mut level = Level::Num(n);
+                for (var, offset) in vars {
+                    let var = f(&var).unwrap_or_else(|| Level::var(var));
+                    level = level.max(var + offset);
+                }
+                level
+            }
+            level => level,
+        }
+    }
+
+    /// If this level is `v + k` for a single variable `v`,
+    /// (with a constant no larger than `k`), returns `(v, k)`.
+    pub fn as_single_var(&self) -> Option<(&LevelVar, u32)> {
+        match self {
+            Level::Max(n, vars) if vars.len() == 1 => {
+                let (var, offset) = vars.iter().next().unwrap();
+                if n <= offset {
+                    Some((var, *offset))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The smallest value this level can be.
+    fn lower_bound(&self) -> u32 {
+        match self {
+            Level::Omega => u32::MAX,
+            Level::Num(n) => *n,
+            Level::Max(n, vars) => vars.values().fold(*n, |a, b| max(a, *b)),
+        }
+    }
+
+    /// `self` is no larger than `other`, no matter how the variables are instantiated.
+    fn le(&self, other: &Self) -> bool {
+        use Level::*;
+        match (self, other) {
+            (_, Omega) => true,
+            (Omega, _) => false,
+            (Num(a), b) => *a <= b.lower_bound(),
+            (Max(a, a_vars), b) => {
+                let b_vars = b.vars();
+                *a <= b.lower_bound()
+                    && a_vars.iter().all(|(var, offset)| {
+                        b_vars
+                            .and_then(|vars| vars.get(var))
+                            .is_some_and(|k| offset <= k)
+                    })
+            }
         }
     }
 }
@@ -67,11 +185,39 @@ impl From<usize> for Level {
     }
 }
 
+impl Display for LevelVar {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            LevelVar::Bound(name) => f.write_str(name),
+            LevelVar::Meta(mi) => write!(f, "?{}", mi),
+        }
+    }
+}
+
 impl Display for Level {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         match self {
             Level::Omega => f.write_str("\u{03C9}"),
             Level::Num(n) => n.fmt(f),
+            Level::Max(n, vars) => {
+                f.write_str("(")?;
+                let mut first = true;
+                if *n > 0 {
+                    n.fmt(f)?;
+                    first = false;
+                }
+                for (var, offset) in vars {
+                    if !first {
+                        f.write_str(" \u{2294} ")?;
+                    }
+                    first = false;
+                    var.fmt(f)?;
+                    if *offset > 0 {
+                        write!(f, " + {}", offset)?;
+                    }
+                }
+                f.write_str(")")
+            }
         }
     }
 }
@@ -82,14 +228,14 @@ impl Default for Level {
     }
 }
 
+/// Levels with variables are only partially ordered.
 impl PartialOrd for Level {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        use Level::*;
-        match (self, other) {
-            (Omega, Omega) => Some(Ordering::Equal),
-            (Omega, Num(..)) => Some(Ordering::Greater),
-            (Num(..), Omega) => Some(Ordering::Less),
-            (Num(a), Num(b)) => a.partial_cmp(b),
+        match (self.le(other), other.le(self)) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None,
         }
     }
 }
@@ -100,7 +246,8 @@ impl Add for Level {
     fn add(self, rhs: Self) -> Self::Output {
         use Level::*;
         match (self, rhs) {
-            (Num(a), Num(b)) => Num(a + b),
+            (Num(a), b) | (b, Num(a)) => b + a,
+            // Sum of two variables is not expressible
             _ => Omega,
         }
     }
@@ -118,7 +265,7 @@ impl Sub<u32> for Level {
     type Output = Self;
 
     fn sub(self, rhs: u32) -> Self::Output {
-        self.map(|a| a - rhs)
+        self.map(|a| a.saturating_sub(rhs))
     }
 }
 
@@ -136,7 +283,7 @@ pub fn lift_hash_map<T: LiftEx>(levels: u32, map: HashMap<String, T>) -> HashMap
 
 pub fn calc_tree_map_level(map: &BTreeMap<String, impl LiftEx>) -> LevelCalcState {
     let levels: Option<Vec<_>> = map.values().map(LiftEx::calc_level).collect();
-    Some(levels?.into_iter().max().unwrap_or_default())
+    Some(levels?.into_iter().fold(Default::default(), Level::max))
 }
 
 pub fn calc_tree_map_plus_one_level(
@@ -144,22 +291,22 @@ pub fn calc_tree_map_plus_one_level(
     map: &BTreeMap<String, impl LiftEx>,
 ) -> LevelCalcState {
     let levels: Option<Vec<_>> = map.values().map(LiftEx::calc_level).collect();
-    let level = levels?.into_iter().max().unwrap_or_default();
+    let level = levels?.into_iter().fold(Default::default(), Level::max);
     Some(one.calc_level()?.max(level))
 }
 
 pub fn calc_slice_level(vec: &[impl LiftEx]) -> LevelCalcState {
     let levels: Option<Vec<_>> = vec.iter().map(LiftEx::calc_level).collect();
-    Some(levels?.into_iter().max().unwrap_or_default())
+    Some(levels?.into_iter().fold(Default::default(), Level::max))
 }
 
 pub fn calc_slice_plus_one_level(one: &impl LiftEx, vec: &[impl LiftEx]) -> LevelCalcState {
     let levels: Option<Vec<_>> = vec.iter().map(LiftEx::calc_level).collect();
-    let level = levels?.into_iter().max().unwrap_or_default();
+    let level = levels?.into_iter().fold(Default::default(), Level::max);
     Some(one.calc_level()?.max(level))
 }
 
 pub fn calc_hash_map_level(map: &HashMap<String, impl LiftEx>) -> LevelCalcState {
     let levels: Option<Vec<_>> = map.values().map(LiftEx::calc_level).collect();
-    Some(levels?.into_iter().max().unwrap_or_default())
+    Some(levels?.into_iter().fold(Default::default(), Level::max))
 }
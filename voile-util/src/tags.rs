@@ -38,3 +38,26 @@ pub enum Plicit {
     Ex,
     Im,
 }
+
+/// Primitive binary operations on natural numbers
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Ord, PartialOrd, Hash)]
+pub enum NatOp {
+    Add,
+    /// Truncated subtraction, `a - b` is zero if `a` is smaller than `b`.
+    Sub,
+    Mul,
+    Lt,
+    Le,
+}
+
+impl Display for NatOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_str(match self {
+            NatOp::Add => "+",
+            NatOp::Sub => "-",
+            NatOp::Mul => "\u{00D7}",
+            NatOp::Lt => "<",
+            NatOp::Le => "<=",
+        })
+    }
+}
@@ -69,6 +69,14 @@ impl<T> Vec1<T> {
     }
 }
 
+impl<T> From<Vec1<T>> for Vec<T> {
+    fn from(vec1: Vec1<T>) -> Self {
+        let mut vec = vec1.tail;
+        vec.insert(0, vec1.head);
+        vec
+    }
+}
+
 impl<T: Default> Default for Vec1<T> {
     fn default() -> Self {
         Self::from(T::default())